@@ -0,0 +1,92 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// 書き込み待ちの行の上限（書き込みが追いつかない場合、超えた分は捨てる）
+const AUDIO_LOG_CAPACITY: usize = 1024;
+/// ファイルに書き出す間隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// CSVのヘッダー行
+const CSV_HEADER: &str = "timestamp,rms,peak,threshold,voice";
+
+/// 処理した音声ブロック1つ分の入力レベルと判定
+#[derive(Debug, Clone, Copy)]
+struct AudioLogEntry {
+    timestamp: DateTime<Local>,
+    rms: f32,
+    peak: f32,
+    threshold: f32,
+    voice: bool,
+}
+
+/// 音声ブロックごとの入力レベルをCSVに追記するログ（閾値の調整用）
+///
+/// オーディオスレッドを待たせないよう、行は別スレッドに渡してまとめて書き出す
+pub struct AudioLog {
+    tx: SyncSender<AudioLogEntry>,
+}
+
+impl AudioLog {
+    /// ログファイルを追記モードで開き、書き込み用のスレッドを開始する
+    ///
+    /// ファイルが空の場合はヘッダー行を書き込む
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        if writer.get_ref().metadata()?.len() == 0 {
+            writeln!(writer, "{}", CSV_HEADER)?;
+        }
+        
+        let (tx, rx) = mpsc::sync_channel::<AudioLogEntry>(AUDIO_LOG_CAPACITY);
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            let mut last_flush = Instant::now();
+            loop {
+                let result = match rx.recv_timeout(FLUSH_INTERVAL) {
+                    Ok(entry) => writeln!(
+                        writer,
+                        "{},{:.6},{:.6},{:.6},{}",
+                        entry.timestamp.format("%Y-%m-%dT%H:%M:%S%.3f"),
+                        entry.rms, entry.peak, entry.threshold, entry.voice as u8
+                    ),
+                    Err(RecvTimeoutError::Timeout) => Ok(()),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                let result = result.and_then(|()| {
+                    if last_flush.elapsed() >= FLUSH_INTERVAL {
+                        last_flush = Instant::now();
+                        writer.flush()
+                    } else {
+                        Ok(())
+                    }
+                });
+                if let Err(e) = result {
+                    warn!("音声ログの書き込みエラー: {}: {}", path.display(), e);
+                    return;
+                }
+            }
+            if let Err(e) = writer.flush() {
+                warn!("音声ログの書き込みエラー: {}: {}", path.display(), e);
+            }
+        });
+        
+        Ok(Self { tx })
+    }
+    
+    /// 音声ブロック1つ分のRMS・ピーク・判定に使った閾値・音声と判定したかを記録
+    pub fn record(&self, rms: f32, peak: f32, threshold: f32, voice: bool) {
+        let entry = AudioLogEntry { timestamp: Local::now(), rms, peak, threshold, voice };
+        if let Err(e) = self.tx.try_send(entry) {
+            debug!("音声ログの行を捨てました: {}", e);
+        }
+    }
+}
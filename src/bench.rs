@@ -0,0 +1,107 @@
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::audio::{AudioChunk, load_audio_file};
+use crate::config::Config;
+use crate::transcriber::{Transcriber, TranscriptionResult};
+
+/// モデルごとの計測結果
+struct BenchResult {
+    model: String,
+    /// 成功した呼び出しの処理時間（ウォームアップを除く）
+    latencies: Vec<Duration>,
+    /// 失敗した呼び出しの回数
+    failures: usize,
+}
+
+impl BenchResult {
+    /// 処理時間の最小・中央値・最大（秒）
+    fn summary(&self) -> Option<(f64, f64, f64)> {
+        let mut secs: Vec<f64> = self.latencies.iter().map(Duration::as_secs_f64).collect();
+        if secs.is_empty() {
+            return None;
+        }
+        secs.sort_by(|a, b| a.total_cmp(b));
+        
+        let mid = secs.len() / 2;
+        let median = if secs.len().is_multiple_of(2) {
+            (secs[mid - 1] + secs[mid]) / 2.0
+        } else {
+            secs[mid]
+        };
+        Some((secs[0], median, secs[secs.len() - 1]))
+    }
+}
+
+/// 音声ファイルを各モデルで`iterations`回ずつ文字起こしし、処理時間を表で表示
+///
+/// 各モデルの最初の1回は接続の確立やモデルの読み込みを含むウォームアップとして集計から除く。
+/// RTF（リアルタイム係数）は処理時間の中央値を音声の長さで割った値で、1未満なら実時間より速い
+pub async fn run_bench(config: Config, test_file: &Path, models: &[String], iterations: usize) -> Result<()> {
+    if iterations == 0 {
+        return Err(anyhow!("計測回数は1以上を指定してください"));
+    }
+    
+    let samples = load_audio_file(test_file, config.sample_rate)?;
+    let audio_sec = samples.len() as f64 / config.sample_rate as f64;
+    if audio_sec <= 0.0 {
+        return Err(anyhow!("音声ファイルが空です: {}", test_file.display()));
+    }
+    
+    // チャネルの設定 (ダミー)
+    let (_audio_tx, audio_rx) = mpsc::channel::<AudioChunk>(1);
+    let (result_tx, _) = mpsc::channel::<TranscriptionResult>(1);
+    let transcriber = Transcriber::new(config, audio_rx, result_tx, Arc::new(AtomicU64::new(0)));
+    
+    let mut results = Vec::with_capacity(models.len());
+    for model in models {
+        info!("計測中: {} (ウォームアップ1回 + {}回)", model, iterations);
+        let mut result = BenchResult {
+            model: model.clone(),
+            latencies: Vec::with_capacity(iterations),
+            failures: 0,
+        };
+        
+        for i in 0..=iterations {
+            let started = Instant::now();
+            let outcome = transcriber.transcribe_file(test_file, model).await;
+            let elapsed = started.elapsed();
+            
+            match outcome {
+                // ウォームアップは成否にかかわらず集計しない
+                _ if i == 0 => {}
+                Ok(_) => result.latencies.push(elapsed),
+                Err(e) => {
+                    warn!("{} の文字起こしに失敗しました: {}", model, e);
+                    result.failures += 1;
+                }
+            }
+        }
+        results.push(result);
+    }
+    
+    println!("音声ファイル: {} ({:.2}秒)", test_file.display(), audio_sec);
+    println!("計測回数: {}回 (ウォームアップの1回を除く)", iterations);
+    println!();
+    // 見出しは全角文字を含むため、表示幅に合わせて空白を入れている
+    println!("モデル                  最小(秒)    中央(秒)    最大(秒)     RTF    失敗");
+    for result in &results {
+        match result.summary() {
+            Some((min, median, max)) => println!(
+                "{:<20} {:>10.2} {:>10.2} {:>10.2} {:>7.3} {:>7}",
+                result.model, min, median, max, median / audio_sec, result.failures
+            ),
+            None => println!(
+                "{:<20} {:>10} {:>10} {:>10} {:>7} {:>7}",
+                result.model, "-", "-", "-", "-", result.failures
+            ),
+        }
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use anyhow::{Result, anyhow};
+use std::io::Write;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::audio::{AudioCapture, AudioChunk, InputLevel};
+use crate::config::{Config, RecordingMode};
+use crate::utils::amplitude_to_db;
+
+/// 周囲の音・話し声をそれぞれ測定する時間
+const MEASURE_DURATION: Duration = Duration::from_secs(3);
+/// 入力レベルを集計する間隔（この間隔ごとのRMSとピークを1つの値として扱う）
+const MEASURE_INTERVAL: Duration = Duration::from_millis(50);
+/// 雑音レベルとみなす区間ピークのパーセンタイル（ときどき入る物音は除く）
+const NOISE_PERCENTILE: f32 = 0.95;
+/// 話し声のレベルとみなす区間ピークのパーセンタイル（息継ぎの区間を含むため中央値）
+const SPEECH_PERCENTILE: f32 = 0.5;
+/// 話し声が雑音のこの倍率に満たない場合は、閾値で区別できないとみなす
+const MIN_SPEECH_TO_NOISE_RATIO: f32 = 2.0;
+/// 閾値を保存する場合に、無音検知モードでなければ使う録音を終了する無音の長さ（ミリ秒）
+const DEFAULT_SILENCE_DURATION_MS: u32 = 1000;
+
+/// 一定間隔で取得した入力レベルの集計
+#[derive(Debug, Default)]
+struct LevelStats {
+    rms: Vec<f32>,
+    peaks: Vec<f32>,
+}
+
+impl LevelStats {
+    /// 入力レベルを1区間分加える
+    fn push(&mut self, level: InputLevel) {
+        self.rms.push(level.rms);
+        self.peaks.push(level.peak);
+    }
+    
+    /// RMSのパーセンタイル
+    fn rms_percentile(&self, p: f32) -> f32 {
+        percentile(&self.rms, p)
+    }
+    
+    /// 区間ピークのパーセンタイル
+    fn peak_percentile(&self, p: f32) -> f32 {
+        percentile(&self.peaks, p)
+    }
+}
+
+/// 値のパーセンタイル（空の場合は0）
+fn percentile(values: &[f32], p: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[((sorted.len() - 1) as f32 * p).round() as usize]
+}
+
+/// 周囲の音と話し声を測定し、無音検知の閾値（`silence_threshold`）を提案する
+///
+/// 閾値はサンプルの振幅と比べられるため、区間ピークの雑音レベルと話し声のレベルの
+/// dBでの中間（相乗平均）を提案する。`save`の場合は無音検知モードの閾値として保存する
+pub async fn run_calibration(config: Config, save: bool) -> Result<()> {
+    let (audio_tx, _audio_rx) = mpsc::channel::<AudioChunk>(1);
+    let mut audio_capture = AudioCapture::new(config.clone(), audio_tx);
+    audio_capture.start_meter()?;
+    
+    println!("周囲の音を測定します。{}秒間、話さずにお待ちください...", MEASURE_DURATION.as_secs());
+    // ストリームの開始直後の不安定な入力は捨てる
+    tokio::time::sleep(MEASURE_INTERVAL * 4).await;
+    audio_capture.level_meter().take();
+    let noise = measure(&audio_capture).await;
+    
+    println!();
+    println!("続いて話し声を測定します。Enterキーを押してから、{}秒間普段の声で話し続けてください", MEASURE_DURATION.as_secs());
+    tokio::task::spawn_blocking(|| std::io::stdin().read_line(&mut String::new())).await??;
+    audio_capture.level_meter().take();
+    let speech = measure(&audio_capture).await;
+    audio_capture.stop();
+    
+    if noise.peaks.is_empty() || speech.peaks.is_empty() {
+        return Err(anyhow!("入力レベルを取得できませんでした。入力デバイスを確認してください"));
+    }
+    
+    let noise_floor = noise.peak_percentile(NOISE_PERCENTILE);
+    let speech_level = speech.peak_percentile(SPEECH_PERCENTILE);
+    println!();
+    println!("雑音レベル: ピーク {:.4} ({:.1}dB)  RMS {:.4} ({:.1}dB)",
+        noise_floor, amplitude_to_db(noise_floor), noise.rms_percentile(0.5), amplitude_to_db(noise.rms_percentile(0.5)));
+    println!("話し声のレベル: ピーク {:.4} ({:.1}dB)  RMS {:.4} ({:.1}dB)",
+        speech_level, amplitude_to_db(speech_level), speech.rms_percentile(0.5), amplitude_to_db(speech.rms_percentile(0.5)));
+    
+    if speech_level < noise_floor * MIN_SPEECH_TO_NOISE_RATIO {
+        return Err(anyhow!(
+            "話し声と雑音のレベルの差が小さすぎるため、閾値を決められません。マイクに近づくか、静かな場所でやり直してください"
+        ));
+    }
+    
+    // 振幅のdBでちょうど中間の値（設定できる範囲に収め、表示に合わせて丸める）
+    let threshold = ((noise_floor * speech_level).sqrt().clamp(0.001, 0.99) * 1000.0).round() / 1000.0;
+    println!("推奨する閾値: {:.3} ({:.1}dB)", threshold, amplitude_to_db(threshold));
+    
+    if save {
+        // 入力デバイスの一時的な指定などを保存しないよう、設定ファイルを読み直す
+        let mut config = Config::load()?;
+        let duration_ms = match config.recording_mode {
+            RecordingMode::VoiceActivity { silence_duration_ms, .. } => silence_duration_ms,
+            _ => DEFAULT_SILENCE_DURATION_MS,
+        };
+        config.set_voice_activity(threshold, duration_ms)?;
+        println!("無音検知モードの閾値を{:.3}に設定しました (無音時間: {}ms)", threshold, duration_ms);
+    } else {
+        println!("設定するには: voilip config set-voice-activity {:.3} <無音時間(ms)>  (または voilip calibrate --save)", threshold);
+    }
+    
+    Ok(())
+}
+
+/// 入力レベルを一定時間、一定間隔で集計
+async fn measure(audio_capture: &AudioCapture) -> LevelStats {
+    let mut stats = LevelStats::default();
+    let mut interval = tokio::time::interval(MEASURE_INTERVAL);
+    // 最初のtickはすぐに完了するため読み飛ばす
+    interval.tick().await;
+    
+    let steps = (MEASURE_DURATION.as_millis() / MEASURE_INTERVAL.as_millis()) as usize;
+    for step in 0..steps {
+        interval.tick().await;
+        stats.push(audio_capture.level_meter().take());
+        // 1秒ごとに進み具合を表示
+        if step % (steps / MEASURE_DURATION.as_secs() as usize).max(1) == 0 {
+            print!(".");
+            let _ = std::io::stdout().flush();
+        }
+    }
+    println!();
+    
+    stats
+}
@@ -0,0 +1,277 @@
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::audio::AudioBuffer;
+use crate::utils::AppState;
+
+#[cfg(unix)]
+pub use self::imp::ControlSocket;
+
+/// 制御ソケットで受け付けるコマンド
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    /// 録音を開始
+    Start,
+    /// 録音を停止して文字起こしに送る
+    Stop,
+    /// 録音の開始と停止を切り替え
+    Toggle,
+    /// 録音中かどうかと起動からの経過時間を返す
+    Status,
+    /// voilipを終了
+    Quit,
+}
+
+impl std::str::FromStr for ControlCommand {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "start" => Ok(ControlCommand::Start),
+            "stop" => Ok(ControlCommand::Stop),
+            "toggle" => Ok(ControlCommand::Toggle),
+            "status" => Ok(ControlCommand::Status),
+            "quit" => Ok(ControlCommand::Quit),
+            other => Err(format!("不明なコマンド: {}", other)),
+        }
+    }
+}
+
+/// コマンドを実行し、応答の1行（`ok ...`または`error ...`）を返す
+///
+/// 応答は他のツールで扱いやすいよう英語のキーワードと`key=value`で返す
+pub fn execute(line: &str, audio_buffer: &AudioBuffer, app_state: &AppState) -> String {
+    let command = match line.parse::<ControlCommand>() {
+        Ok(command) => command,
+        Err(e) => return format!("error {}", e),
+    };
+    
+    let result = match command {
+        ControlCommand::Start => audio_buffer.start_recording(),
+        ControlCommand::Stop => audio_buffer.stop_recording(),
+        ControlCommand::Toggle if audio_buffer.is_recording() => audio_buffer.stop_recording(),
+        ControlCommand::Toggle => audio_buffer.start_recording(),
+        ControlCommand::Status => Ok(()),
+        ControlCommand::Quit => {
+            app_state.set_running(false);
+            return "ok quitting".to_string();
+        }
+    };
+    
+    match result {
+        Ok(()) => format!(
+            "ok state={} uptime_sec={}",
+            if audio_buffer.is_recording() { "recording" } else { "idle" },
+            app_state.elapsed().as_secs()
+        ),
+        Err(e) => format!("error {}", e),
+    }
+}
+
+/// 制御ソケットに対応していないプラットフォームではエラーを返す
+#[cfg(not(unix))]
+pub struct ControlSocket;
+
+#[cfg(not(unix))]
+impl ControlSocket {
+    pub fn bind(_path: &Path, _audio_buffer: Arc<AudioBuffer>, _app_state: Arc<AppState>) -> Result<Self> {
+        Err(anyhow!("制御ソケットはUnix系のOS（Linux・macOS）でのみ使用できます"))
+    }
+    
+    pub fn stop(self) {}
+}
+
+/// Unixドメインソケットによる制御
+#[cfg(unix)]
+mod imp {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::task::JoinHandle;
+    use tracing::{debug, info, warn};
+    
+    /// 1行のコマンドを受け付けるUnixドメインソケット
+    ///
+    /// 停止時（`stop`）にソケットファイルを削除する
+    pub struct ControlSocket {
+        path: PathBuf,
+        task: JoinHandle<()>,
+    }
+    
+    impl ControlSocket {
+        /// ソケットを作成し、接続の受け付けを開始する（tokioのランタイム上で呼び出す）
+        ///
+        /// 同じパスに前回のソケットファイルが残っていれば削除するが、
+        /// 別のvoilipが使用中の場合はエラーにする
+        pub fn bind(path: &Path, audio_buffer: Arc<AudioBuffer>, app_state: Arc<AppState>) -> Result<Self> {
+            if path.exists() {
+                if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                    return Err(anyhow!("制御ソケットはすでに使用されています: {}", path.display()));
+                }
+                std::fs::remove_file(path)
+                    .map_err(|e| anyhow!("古い制御ソケットを削除できません: {}: {}", path.display(), e))?;
+            }
+            let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            std::fs::create_dir_all(parent)?;
+            
+            // 録音の開始・停止ができるため、他のユーザーからは接続できないようにする
+            // （作成直後から権限を絞った後までの間も接続されないよう、所有者のみが入れる一時ディレクトリ内で
+            // 作成してから移動する。umaskはプロセス全体に影響するため変更しない）
+            let private_dir = tempfile::Builder::new().prefix(".voilip-control-").tempdir_in(parent)
+                .map_err(|e| anyhow!("制御ソケットを作成できません: {}: {}", parent.display(), e))?;
+            let private_path = private_dir.path().join("socket");
+            let listener = UnixListener::bind(&private_path)
+                .map_err(|e| anyhow!("制御ソケットを作成できません: {}: {}", path.display(), e))?;
+            std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&private_path, path)
+                .map_err(|e| anyhow!("制御ソケットを作成できません: {}: {}", path.display(), e))?;
+            info!("制御ソケットで待ち受けます: {}", path.display());
+            
+            let task = tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(handle_connection(stream, audio_buffer.clone(), app_state.clone()));
+                        }
+                        Err(e) => warn!("制御ソケットの接続エラー: {}", e),
+                    }
+                }
+            });
+            
+            Ok(Self { path: path.to_path_buf(), task })
+        }
+        
+        /// 接続の受け付けを止めてソケットファイルを削除
+        pub fn stop(self) {
+            self.task.abort();
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                warn!("制御ソケットの削除エラー: {}", e);
+            }
+        }
+    }
+    
+    /// 1つの接続から1行ずつコマンドを読み、応答を返す（接続が閉じられるまで続ける）
+    async fn handle_connection(stream: UnixStream, audio_buffer: Arc<AudioBuffer>, app_state: Arc<AppState>) {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    debug!("制御ソケットの読み込みエラー: {}", e);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            // 録音の開始・停止は通知の表示などで待つことがあるため、ブロッキング用のスレッドで実行する
+            let response = {
+                let audio_buffer = audio_buffer.clone();
+                let app_state = app_state.clone();
+                let command = line.clone();
+                tokio::task::spawn_blocking(move || execute(&command, &audio_buffer, &app_state)).await
+                    .unwrap_or_else(|e| format!("error {}", e))
+            };
+            info!("制御ソケット: {} -> {}", line.trim(), response);
+            if writer.write_all(format!("{}\n", response).as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, NotificationLevel, RecordingMode};
+    use tokio::sync::mpsc;
+
+    fn audio_buffer() -> AudioBuffer {
+        let config = Config {
+            recording_mode: RecordingMode::Toggle { key: "F9".to_string() },
+            notifications: Some(NotificationLevel::Off),
+            ..Config::default()
+        };
+        let (tx, _rx) = mpsc::channel(8);
+        AudioBuffer::new(16000, tx, &config)
+    }
+
+    #[test]
+    fn parses_commands_case_insensitively() {
+        assert_eq!(" Start\r\n".parse::<ControlCommand>(), Ok(ControlCommand::Start));
+        assert_eq!("TOGGLE".parse::<ControlCommand>(), Ok(ControlCommand::Toggle));
+        assert_eq!("quit".parse::<ControlCommand>(), Ok(ControlCommand::Quit));
+        assert!("record".parse::<ControlCommand>().is_err());
+    }
+
+    #[test]
+    fn execute_dispatches_commands_to_recording_state() {
+        let buffer = audio_buffer();
+        let app_state = AppState::new();
+        let state = |response: String| response.split_whitespace().nth(1).map(str::to_string);
+
+        assert_eq!(state(execute("status", &buffer, &app_state)).as_deref(), Some("state=idle"));
+        assert_eq!(state(execute("start", &buffer, &app_state)).as_deref(), Some("state=recording"));
+        assert_eq!(state(execute("toggle", &buffer, &app_state)).as_deref(), Some("state=idle"));
+        assert_eq!(state(execute("toggle", &buffer, &app_state)).as_deref(), Some("state=recording"));
+        assert_eq!(state(execute("stop", &buffer, &app_state)).as_deref(), Some("state=idle"));
+        assert!(execute("status", &buffer, &app_state).contains("uptime_sec="));
+
+        assert_eq!(execute("record", &buffer, &app_state), "error 不明なコマンド: record");
+        assert!(app_state.is_running());
+        assert_eq!(execute("quit", &buffer, &app_state), "ok quitting");
+        assert!(!app_state.is_running());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_is_only_accessible_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voilip.sock");
+        let socket = ControlSocket::bind(&path, Arc::new(audio_buffer()), Arc::new(AppState::new())).unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        // 作成に使った一時ディレクトリは残さない
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+
+        socket.stop();
+        assert!(!path.exists());
+    }
+    
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn client_toggles_recording_through_socket() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("voilip.sock");
+        let buffer = Arc::new(audio_buffer());
+        let app_state = Arc::new(AppState::new());
+        let socket = ControlSocket::bind(&path, buffer.clone(), app_state.clone()).unwrap();
+        
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        
+        writer.write_all(b"toggle\n").await.unwrap();
+        assert!(lines.next_line().await.unwrap().unwrap().starts_with("ok state=recording "));
+        assert!(buffer.is_recording());
+        writer.write_all(b"toggle\n").await.unwrap();
+        assert!(lines.next_line().await.unwrap().unwrap().starts_with("ok state=idle "));
+        assert!(!buffer.is_recording());
+        writer.write_all(b"quit\n").await.unwrap();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "ok quitting");
+        assert!(!app_state.is_running());
+        
+        socket.stop();
+    }
+}
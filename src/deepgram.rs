@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use tokio::sync::{mpsc, Notify};
+
+use crate::audio::LiveAudio;
+use crate::config::Config;
+use crate::events::RecordingEvent;
+use crate::transcriber::{SharedLanguage, TranscriptionResult};
+
+#[cfg(feature = "deepgram")]
+pub use self::imp::run_stream;
+
+/// deepgramフィーチャーを有効にしてビルドしたかどうか
+pub const AVAILABLE: bool = cfg!(feature = "deepgram");
+
+/// deepgramフィーチャーなしでビルドした場合はエラーを返す
+#[cfg(not(feature = "deepgram"))]
+pub async fn run_stream(
+    _config: Config,
+    _live_rx: mpsc::Receiver<LiveAudio>,
+    _result_tx: mpsc::Sender<TranscriptionResult>,
+    _latest_sequence: Arc<AtomicU64>,
+    _events: Option<mpsc::Sender<RecordingEvent>>,
+    _language: SharedLanguage,
+    _shutdown: Arc<Notify>,
+) -> Result<()> {
+    Err(anyhow!("deepgramエンジンを使うには、deepgramフィーチャーを有効にしてビルドする必要があります (cargo build --features deepgram)"))
+}
+
+/// DeepgramのWebSocket APIを使ったストリーミング音声認識
+///
+/// 録音の開始ごとに接続し、録音中の音声をそのまま送って途中経過を受け取る。
+/// 録音の停止時に未確定の音声の確定を要求し、確定した文をまとめて最終結果にする
+#[cfg(feature = "deepgram")]
+mod imp {
+    use super::*;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::Value;
+    use tokio::net::TcpStream;
+    use tokio::time::{sleep, Instant};
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+    use tracing::{debug, error, info, warn};
+    
+    use crate::audio::{NotificationKind, downmix_to_mono, notify};
+    use crate::config::{AUTO_LANGUAGE, DEEPGRAM_API_KEY_ENV};
+    use crate::events::emit;
+    use crate::transcriber::{NOTIFICATION_ERROR_MAX_CHARS, retry_delay, segment_separator};
+    use crate::utils::{apply_replacements, format_text, truncate_chars};
+    
+    /// DeepgramのストリーミングAPIのURL
+    const DEEPGRAM_LISTEN_URL: &str = "wss://api.deepgram.com/v1/listen";
+    /// 録音の停止後に確定結果を待つ最大時間
+    const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+    /// 未確定の音声の確定を要求するメッセージ
+    const FINALIZE_MESSAGE: &str = r#"{"type":"Finalize"}"#;
+    /// 接続の終了を伝えるメッセージ
+    const CLOSE_STREAM_MESSAGE: &str = r#"{"type":"CloseStream"}"#;
+    
+    type DeepgramSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+    
+    /// Deepgramから受け取った認識結果
+    struct StreamResponse {
+        transcript: String,
+        /// 確定した結果か（falseの場合は後で書き換わる途中経過）
+        is_final: bool,
+        /// 確定の要求に対する結果か
+        from_finalize: bool,
+    }
+    
+    /// 1回の録音の状態
+    struct Recording {
+        /// 確定した文
+        finals: Vec<String>,
+        /// 送信した音声のサンプル数（モノラル）
+        sent_samples: usize,
+        /// 録音の停止を受け取ったか
+        stopped: bool,
+        /// 録音中の音声のチャンネル数（送信前にモノラルにする）
+        channels: usize,
+        /// 言語（録音の開始時の値）
+        language: Option<String>,
+    }
+    
+    impl Recording {
+        /// 確定した文と未確定の途中経過をつなげたテキスト
+        fn text(&self, interim: Option<&str>) -> String {
+            let separator = segment_separator(self.language.as_deref());
+            self.finals.iter().map(String::as_str)
+                .chain(interim)
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>()
+                .join(separator)
+        }
+    }
+    
+    /// 録音中の音声を受け取り、録音ごとにDeepgramで文字起こしして結果を送る
+    ///
+    /// `shutdown`で終了を要求されるまで録音の開始を待ち続ける
+    pub async fn run_stream(
+        config: Config,
+        mut live_rx: mpsc::Receiver<LiveAudio>,
+        result_tx: mpsc::Sender<TranscriptionResult>,
+        latest_sequence: Arc<AtomicU64>,
+        events: Option<mpsc::Sender<RecordingEvent>>,
+        language: SharedLanguage,
+        shutdown: Arc<Notify>,
+    ) -> Result<()> {
+        let api_key = std::env::var(DEEPGRAM_API_KEY_ENV)
+            .ok()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| anyhow!("DeepgramのAPIキーが設定されていません ({})", DEEPGRAM_API_KEY_ENV))?;
+        // 依存クレートの組み合わせによっては暗号ライブラリが自動で選ばれないため明示する
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        if config.proxy_url().is_some() {
+            warn!("Deepgramのストリーミング接続はプロキシに対応していません。直接接続します");
+        }
+        info!("Deepgram: ストリーミング音声認識を開始します");
+        
+        loop {
+            // 録音の開始を待つ（録音していない間に届いた音声は捨てる）
+            tokio::select! {
+                audio = live_rx.recv() => match audio {
+                    Some(LiveAudio::Started) => {}
+                    Some(_) => continue,
+                    None => break,
+                },
+                _ = shutdown.notified() => break,
+            }
+            
+            let mut recording = Recording {
+                finals: Vec::new(),
+                sent_samples: 0,
+                stopped: false,
+                channels: config.output_channels() as usize,
+                language: language.read().ok()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty() && !l.eq_ignore_ascii_case(AUTO_LANGUAGE)),
+            };
+            let outcome = transcribe_recording(&config, &api_key, &mut live_rx, &mut recording, &result_tx, &latest_sequence).await;
+            
+            let sequence = latest_sequence.fetch_add(1, Ordering::SeqCst) + 1;
+            match outcome {
+                Ok(()) => {
+                    emit(events.as_ref(), RecordingEvent::Transcribing);
+                    let result = TranscriptionResult {
+                        text: apply_replacements(&format_text(&recording.text(None)), &config.replacements),
+                        language: recording.language.clone().unwrap_or_else(|| AUTO_LANGUAGE.to_string()),
+                        duration_sec: recording.sent_samples as f32 / config.sample_rate as f32,
+                        is_final: true,
+                        sequence,
+                        segments: Vec::new(),
+                        no_speech_prob: None,
+                        diagnostics: None,
+                        fallback_engine: None,
+                        epoch: None,
+                    };
+                    debug!("Deepgram: 最終結果 #{} ({:.1}秒)", sequence, result.duration_sec);
+                    emit(events.as_ref(), RecordingEvent::Done(result.clone()));
+                    if let Err(e) = result_tx.send(result).await {
+                        error!("結果送信エラー: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Deepgram 音声認識エラー: {}", e);
+                    emit(events.as_ref(), RecordingEvent::Error(e.to_string()));
+                    let message = format!("文字起こしに失敗しました: {}", truncate_chars(&e.to_string(), NOTIFICATION_ERROR_MAX_CHARS));
+                    notify(config.notification_level(), NotificationKind::Error, "voilip", &message);
+                }
+            }
+        }
+        
+        info!("Deepgram: ストリーミング音声認識を終了します");
+        Ok(())
+    }
+    
+    /// 1回の録音を文字起こしする（接続が切れた場合は設定の回数まで接続し直す）
+    async fn transcribe_recording(
+        config: &Config,
+        api_key: &str,
+        live_rx: &mut mpsc::Receiver<LiveAudio>,
+        recording: &mut Recording,
+        result_tx: &mpsc::Sender<TranscriptionResult>,
+        latest_sequence: &AtomicU64,
+    ) -> Result<()> {
+        let mut retry_count = 0;
+        loop {
+            let error = match connect(config, api_key, recording.language.as_deref()).await {
+                Ok(socket) => match stream_audio(socket, live_rx, recording, result_tx, latest_sequence).await {
+                    Ok(()) => return Ok(()),
+                    Err(e) => e,
+                },
+                Err(e) => e,
+            };
+            
+            // 停止後に切れた場合は、送り直す音声がないためそれまでの確定結果で終える
+            if recording.stopped {
+                warn!("Deepgram: 確定結果を受け取る前に接続が切れました: {}", error);
+                return Ok(());
+            }
+            if retry_count >= config.api_retry_max {
+                return Err(error);
+            }
+            retry_count += 1;
+            warn!("Deepgram: 接続エラー ({}/{}): {}. 接続し直します...", retry_count, config.api_retry_max, error);
+            sleep(retry_delay(config, retry_count)).await;
+        }
+    }
+    
+    /// Deepgramに接続
+    async fn connect(config: &Config, api_key: &str, language: Option<&str>) -> Result<DeepgramSocket> {
+        let url = format!(
+            "{}?encoding=linear16&sample_rate={}&channels=1&model={}&language={}&interim_results=true&punctuate=true&smart_format=true",
+            DEEPGRAM_LISTEN_URL, config.sample_rate, config.model, language.unwrap_or("multi")
+        );
+        let mut request = url.into_client_request()?;
+        request.headers_mut().insert("Authorization", HeaderValue::from_str(&format!("Token {}", api_key))?);
+        
+        let (socket, _) = connect_async(request).await
+            .map_err(|e| anyhow!("Deepgramへの接続に失敗しました: {}", e))?;
+        debug!("Deepgram: 接続しました");
+        Ok(socket)
+    }
+    
+    /// 録音中の音声を送りながら結果を受け取り、録音の停止後に確定結果を受け取るまで続ける
+    async fn stream_audio(
+        socket: DeepgramSocket,
+        live_rx: &mut mpsc::Receiver<LiveAudio>,
+        recording: &mut Recording,
+        result_tx: &mpsc::Sender<TranscriptionResult>,
+        latest_sequence: &AtomicU64,
+    ) -> Result<()> {
+        let (mut write, mut read) = socket.split();
+        let mut deadline = None;
+        // 接続し直した場合など、すでに停止していればすぐに確定を要求する
+        if recording.stopped {
+            write.send(Message::Text(FINALIZE_MESSAGE.into())).await?;
+            deadline = Some(Instant::now() + FINALIZE_TIMEOUT);
+        }
+        
+        loop {
+            let finalize_timeout = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            
+            tokio::select! {
+                audio = live_rx.recv(), if !recording.stopped => match audio {
+                    Some(LiveAudio::Samples(samples)) => {
+                        let mono = downmix_to_mono(&samples, recording.channels);
+                        recording.sent_samples += mono.len();
+                        write.send(Message::Binary(encode_pcm16(&mono))).await?;
+                    }
+                    Some(LiveAudio::Started) => {}
+                    Some(LiveAudio::Stopped) | None => {
+                        recording.stopped = true;
+                        write.send(Message::Text(FINALIZE_MESSAGE.into())).await?;
+                        deadline = Some(Instant::now() + FINALIZE_TIMEOUT);
+                    }
+                },
+                message = read.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        let Some(response) = parse_response(&text) else {
+                            continue;
+                        };
+                        if response.is_final {
+                            recording.finals.push(response.transcript);
+                        } else if !recording.stopped {
+                            send_interim(recording, &response.transcript, result_tx, latest_sequence);
+                        }
+                        if response.from_finalize {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => return Err(anyhow!("Deepgramとの接続が切れました")),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(anyhow!("Deepgramとの通信エラー: {}", e)),
+                },
+                _ = finalize_timeout => {
+                    warn!("Deepgram: {}秒以内に確定結果を受け取れませんでした", FINALIZE_TIMEOUT.as_secs());
+                    break;
+                }
+            }
+        }
+        
+        let _ = write.send(Message::Text(CLOSE_STREAM_MESSAGE.into())).await;
+        let _ = write.close().await;
+        Ok(())
+    }
+    
+    /// 途中経過を送る（受信側が追いつかない場合は捨てる）
+    fn send_interim(recording: &Recording, interim: &str, result_tx: &mpsc::Sender<TranscriptionResult>, latest_sequence: &AtomicU64) {
+        let result = TranscriptionResult {
+            text: recording.text(Some(interim)),
+            language: recording.language.clone().unwrap_or_else(|| AUTO_LANGUAGE.to_string()),
+            duration_sec: 0.0,
+            is_final: false,
+            sequence: latest_sequence.load(Ordering::SeqCst) + 1,
+            segments: Vec::new(),
+            no_speech_prob: None,
+            diagnostics: None,
+            fallback_engine: None,
+            epoch: None,
+        };
+        if let Err(e) = result_tx.try_send(result) {
+            debug!("途中経過を送信できませんでした: {}", e);
+        }
+    }
+    
+    /// f32のサンプルを16bitリトルエンディアンのPCMに変換
+    fn encode_pcm16(samples: &[f32]) -> Vec<u8> {
+        samples.iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes())
+            .collect()
+    }
+    
+    /// Deepgramのメッセージから認識結果を取り出す（認識結果以外のメッセージは`None`）
+    fn parse_response(text: &str) -> Option<StreamResponse> {
+        let value: Value = serde_json::from_str(text).ok()?;
+        if value["type"] != "Results" {
+            return None;
+        }
+        Some(StreamResponse {
+            transcript: value["channel"]["alternatives"][0]["transcript"].as_str().unwrap_or_default().to_string(),
+            is_final: value["is_final"].as_bool().unwrap_or(false),
+            from_finalize: value["from_finalize"].as_bool().unwrap_or(false),
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use arboard::Clipboard;
+use cpal::traits::HostTrait;
+use std::env;
+
+use crate::audio::find_input_device;
+use crate::config::{AZURE_API_KEY_ENV, DEEPGRAM_API_KEY_ENV, ApiKeySource, Config, OutputMode, TranscriptionEngine};
+use crate::{deepgram, local_whisper};
+use crate::utils::command_exists;
+
+/// 診断結果の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CheckStatus {
+    /// 問題なし
+    Pass,
+    /// 動作はするが一部の機能が使えない
+    Warn,
+    /// 必須の要件を満たしていない
+    Fail,
+}
+
+impl CheckStatus {
+    /// 表示用のラベル
+    fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "[OK]  ",
+            CheckStatus::Warn => "[WARN]",
+            CheckStatus::Fail => "[FAIL]",
+        }
+    }
+}
+
+/// 診断結果を1行ずつ表示しながら集計する
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    warnings: usize,
+}
+
+impl Report {
+    /// 診断結果を1件記録して表示
+    fn check(&mut self, status: CheckStatus, name: &str, detail: impl AsRef<str>) {
+        match status {
+            CheckStatus::Pass => {}
+            CheckStatus::Warn => self.warnings += 1,
+            CheckStatus::Fail => self.failures += 1,
+        }
+        println!("{} {}: {}", status.label(), name, detail.as_ref());
+    }
+
+    fn pass(&mut self, name: &str, detail: impl AsRef<str>) {
+        self.check(CheckStatus::Pass, name, detail);
+    }
+
+    fn warn(&mut self, name: &str, detail: impl AsRef<str>) {
+        self.check(CheckStatus::Warn, name, detail);
+    }
+
+    fn fail(&mut self, name: &str, detail: impl AsRef<str>) {
+        self.check(CheckStatus::Fail, name, detail);
+    }
+}
+
+/// 実行環境を診断して結果を表示
+///
+/// 設定、文字起こしエンジンの依存関係、出力先のツール、通知、入力デバイスを順に確認し、
+/// 必須の要件を満たしていない項目があれば`false`を返す
+pub fn run_doctor() -> bool {
+    let mut report = Report::default();
+    println!("【環境診断】");
+    
+    let config = match Config::load() {
+        Ok(config) => {
+            let path = Config::get_config_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|_| "不明".to_string());
+            report.pass("設定ファイル", format!("読み込みました ({})", path));
+            config
+        }
+        Err(e) => {
+            report.fail("設定ファイル", format!("読み込めません: {}（以降はデフォルト設定で確認します）", e));
+            Config::default()
+        }
+    };
+    
+    check_engine(&mut report, &config);
+    check_outputs(&mut report, &config);
+    check_notification(&mut report);
+    check_input_devices(&mut report, &config);
+    
+    println!();
+    if report.failures > 0 {
+        println!("{}件の必須項目に問題があります（警告: {}件）", report.failures, report.warnings);
+    } else if report.warnings > 0 {
+        println!("必須項目は問題ありません（警告: {}件）", report.warnings);
+    } else {
+        println!("すべての項目に問題はありません");
+    }
+    
+    report.failures == 0
+}
+
+/// 文字起こしエンジンの依存関係とAPIキーを確認
+fn check_engine(report: &mut Report, config: &Config) {
+    match &config.transcription_engine {
+        TranscriptionEngine::WhisperCpp { path, model } | TranscriptionEngine::WhisperCppServer { path, model, .. } => {
+            let name = config.transcription_engine.name();
+            // パスではなくコマンド名で指定されている場合はPATHから探す
+            if path.is_file() || command_exists(&path.to_string_lossy()) {
+                report.pass(name, path.display().to_string());
+            } else {
+                report.fail(name, format!("実行ファイルが見つかりません: {}", path.display()));
+            }
+            
+            if model.is_file() {
+                report.pass(&format!("{}モデル", name), model.display().to_string());
+            } else {
+                report.fail(&format!("{}モデル", name), format!("モデルファイルが見つかりません: {}", model.display()));
+            }
+            // サーバーへのリクエストにはcurlを使う
+            if matches!(config.transcription_engine, TranscriptionEngine::WhisperCppServer { .. }) {
+                check_curl(report);
+            }
+        }
+        TranscriptionEngine::Groq => {
+            check_curl(report);
+            if !config.groq_api_key.is_empty() || env::var("GROQ_API_KEY").is_ok_and(|k| !k.is_empty()) {
+                report.pass("Groq APIキー", "設定済み");
+            } else {
+                report.fail("Groq APIキー", "未設定です。`voilip config set-groq-api-key`または環境変数GROQ_API_KEYで設定してください");
+            }
+        }
+        TranscriptionEngine::WhisperRs { model } => {
+            if local_whisper::AVAILABLE {
+                report.pass("whisper-rs", "有効です");
+            } else {
+                report.fail("whisper-rs", "whisper-cppフィーチャーを有効にしてビルドされていません");
+            }
+            
+            if model.is_file() {
+                report.pass("whisper-rsモデル", model.display().to_string());
+            } else {
+                report.fail("whisper-rsモデル", format!("モデルファイルが見つかりません: {}", model.display()));
+            }
+        }
+        TranscriptionEngine::Azure { endpoint, deployment } => {
+            check_curl(report);
+            report.pass("Azure OpenAI", format!("{} (デプロイ名: {})", endpoint, deployment));
+            if env::var(AZURE_API_KEY_ENV).is_ok_and(|k| !k.is_empty()) {
+                report.pass("Azure OpenAI APIキー", "設定済み (環境変数)");
+            } else {
+                report.fail("Azure OpenAI APIキー", format!("未設定です。環境変数{}で設定してください", AZURE_API_KEY_ENV));
+            }
+        }
+        TranscriptionEngine::Deepgram => {
+            if deepgram::AVAILABLE {
+                report.pass("Deepgram", "有効です");
+            } else {
+                report.fail("Deepgram", "deepgramフィーチャーを有効にしてビルドされていません");
+            }
+            
+            if env::var(DEEPGRAM_API_KEY_ENV).is_ok_and(|k| !k.is_empty()) {
+                report.pass("Deepgram APIキー", "設定済み (環境変数)");
+            } else {
+                report.fail("Deepgram APIキー", format!("未設定です。環境変数{}で設定してください", DEEPGRAM_API_KEY_ENV));
+            }
+        }
+        TranscriptionEngine::GPT4o | TranscriptionEngine::Whisper1 => {
+            check_curl(report);
+            match config.api_key_source {
+                ApiKeySource::Keyring => report.pass("OpenAI APIキー", "設定済み (キーリング)"),
+                ApiKeySource::ConfigFile => report.warn("OpenAI APIキー", "設定ファイルに平文で保存されています。`voilip config migrate-key`でキーリングに移行してください"),
+                ApiKeySource::Env => report.pass("OpenAI APIキー", "設定済み (環境変数)"),
+                // 独自のベースURLを使う場合はキーなしでも動作する
+                ApiKeySource::None if config.api_base_url.is_some() => report.warn("OpenAI APIキー", "未設定です（独自のベースURLを使用しているため続行できます）"),
+                ApiKeySource::None => report.fail("OpenAI APIキー", "未設定です。`voilip config set-api-key`または環境変数OPENAI_API_KEYで設定してください"),
+            }
+        }
+    }
+}
+
+/// API呼び出しに使うcurlを確認
+fn check_curl(report: &mut Report) {
+    if command_exists("curl") {
+        report.pass("curl", "見つかりました");
+    } else {
+        report.fail("curl", "見つかりません。API呼び出しとWebhookの送信に必要です");
+    }
+}
+
+/// 設定された出力先ごとに必要なツールを確認
+fn check_outputs(report: &mut Report, config: &Config) {
+    let needs_clipboard = config.output_modes.iter().any(|m| matches!(m, OutputMode::Clipboard | OutputMode::Both));
+    let needs_typing = config.output_modes.iter().any(|m| matches!(m, OutputMode::Type | OutputMode::Both));
+    
+    if needs_clipboard {
+        match Clipboard::new() {
+            Ok(_) => report.pass("クリップボード", "使用できます"),
+            Err(e) => report.fail("クリップボード", format!("使用できません: {}", e)),
+        }
+        
+        // コンポジタによっては終了後にクリップボードの内容が消えるため、wl-copyを併用する
+        if cfg!(target_os = "linux") && env::var_os("WAYLAND_DISPLAY").is_some() && !command_exists("wl-copy") {
+            report.warn("wl-copy", "見つかりません。終了後にクリップボードの内容が消える場合はwl-clipboardをインストールしてください");
+        }
+    }
+    
+    if needs_typing {
+        check_typing_tool(report);
+    }
+    
+    for mode in &config.output_modes {
+        match mode {
+            OutputMode::File { path } => {
+                match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    Some(parent) if !parent.is_dir() => report.warn("ファイル出力", format!("{}は存在しません（初回の出力時に作成されます）", parent.display())),
+                    _ => report.pass("ファイル出力", path.display().to_string()),
+                }
+            }
+            OutputMode::Webhook { url } => {
+                // 文字起こしエンジンがAPIの場合はすでに確認済み
+                if matches!(config.transcription_engine, TranscriptionEngine::WhisperCpp { .. } | TranscriptionEngine::WhisperRs { .. } | TranscriptionEngine::Deepgram) {
+                    check_curl(report);
+                }
+                report.pass("Webhook", url);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// タイプ出力に使うツールを確認
+fn check_typing_tool(report: &mut Report) {
+    #[cfg(target_os = "macos")]
+    {
+        if command_exists("osascript") {
+            report.pass("タイプ出力", "osascript");
+        } else {
+            report.fail("タイプ出力", "osascriptが見つかりません");
+        }
+    }
+    
+    #[cfg(target_os = "linux")]
+    {
+        let is_wayland = env::var("WAYLAND_DISPLAY").is_ok();
+        let is_x11 = env::var("DISPLAY").is_ok();
+        
+        if is_wayland && command_exists("wtype") {
+            report.pass("タイプ出力", "wtype (Wayland)");
+        } else if is_x11 && command_exists("xdotool") {
+            report.pass("タイプ出力", "xdotool (X11)");
+        } else if is_wayland {
+            report.fail("タイプ出力", "wtypeが見つかりません。wtypeをインストールしてください");
+        } else if is_x11 {
+            report.fail("タイプ出力", "xdotoolが見つかりません。xdotoolをインストールしてください");
+        } else {
+            report.fail("タイプ出力", "ディスプレイサーバー（WaylandまたはX11）を検出できません");
+        }
+    }
+    
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        report.fail("タイプ出力", "このプラットフォームはサポートされていません");
+    }
+}
+
+/// 通知の表示に使うツールを確認
+fn check_notification(report: &mut Report) {
+    let tool = if cfg!(target_os = "linux") {
+        "notify-send"
+    } else if cfg!(target_os = "macos") {
+        "osascript"
+    } else {
+        "powershell"
+    };
+    
+    // 通知が出なくても文字起こしは動作するため警告にとどめる
+    if command_exists(tool) {
+        report.pass("通知", tool);
+    } else {
+        report.warn("通知", format!("{}が見つからないため通知は表示されません", tool));
+    }
+}
+
+/// 入力デバイスを確認
+fn check_input_devices(report: &mut Report, config: &Config) {
+    let host = cpal::default_host();
+    let count = match host.input_devices() {
+        Ok(devices) => devices.count(),
+        Err(e) => {
+            report.fail("入力デバイス", format!("列挙に失敗しました: {}", e));
+            return;
+        }
+    };
+    
+    if count == 0 {
+        report.fail("入力デバイス", "見つかりません");
+        return;
+    }
+    report.pass("入力デバイス", format!("{}個見つかりました（`voilip devices`で一覧を表示）", count));
+    
+    if let Some(name) = &config.input_device {
+        match find_input_device(&host, name) {
+            Ok(Some(_)) => report.pass("設定された入力デバイス", name),
+            // 見つからない場合は他のデバイスにフォールバックする
+            _ => report.warn("設定された入力デバイス", format!("'{}' が見つからないため、他のデバイスが使われます", name)),
+        }
+    }
+}
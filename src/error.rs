@@ -0,0 +1,74 @@
+use crate::transcriber::api_error_message;
+
+/// voilipの主な処理が返すエラー（ライブラリとして組み込む場合に種類で判別できる）
+///
+/// 種類で判別する必要のないエラーは`Other`に入れる。`anyhow::Error`に包まれていても、
+/// `downcast_ref::<VoilipError>()`で取り出せる
+#[derive(Debug, thiserror::Error)]
+pub enum VoilipError {
+    /// 使用可能な入力デバイスが1つもない
+    #[error("使用可能な入力デバイスが見つかりません")]
+    NoInputDevice {
+        /// 試行したが使用できなかったデバイス名
+        tried: Vec<String>,
+    },
+    /// キー入力の監視を開始できない（入力監視の権限がない、ディスプレイサーバーに接続できないなど）
+    #[error("キー入力を監視できません: {detail}")]
+    KeyListener {
+        /// `rdev`が返したエラーの内容
+        detail: String,
+    },
+    /// 入力デバイスのサンプル形式からストリームを構築できない
+    #[error("非対応のサンプル形式: {format}")]
+    UnsupportedSampleFormat {
+        /// デバイスのサンプル形式
+        format: String,
+    },
+    /// 接続先のAPIキーが設定されていない
+    #[error("{provider}のAPIキーが設定されていません")]
+    MissingApiKey {
+        /// 接続先のサービス名
+        provider: &'static str,
+    },
+    /// APIがエラーのHTTPステータスを返した（リトライできる場合はリトライした後）
+    #[error("API呼び出しエラー (HTTP {status}): {}", api_error_message(.body))]
+    ApiError {
+        /// HTTPステータスコード
+        status: u16,
+        /// 応答本文
+        body: String,
+    },
+    /// 音声認識エンジンを使うための指定が足りない、またはエンジンが使えない
+    #[error("{detail}")]
+    EngineConfig {
+        /// 指定されたエンジン名
+        engine: String,
+        /// エラーの内容
+        detail: String,
+    },
+    /// その他のエラー
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for VoilipError {
+    /// 内部の処理から`anyhow::Error`に包まれて返ってきた`VoilipError`は、包みを外して種類を保つ
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<VoilipError>() {
+            Ok(error) => error,
+            Err(error) => VoilipError::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwraps_voilip_error_from_anyhow() {
+        let wrapped = anyhow::Error::from(VoilipError::MissingApiKey { provider: "OpenAI" });
+        assert!(matches!(VoilipError::from(wrapped), VoilipError::MissingApiKey { provider: "OpenAI" }));
+        assert!(matches!(VoilipError::from(anyhow::anyhow!("その他")), VoilipError::Other(_)));
+    }
+}
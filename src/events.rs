@@ -0,0 +1,30 @@
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::transcriber::TranscriptionResult;
+
+/// 録音・文字起こしの状態の変化（UIとの連携用）
+#[derive(Debug, Clone)]
+pub enum RecordingEvent {
+    /// 録音を開始した
+    Started,
+    /// 録音を停止した
+    Stopped,
+    /// 確定した録音の文字起こしを開始した
+    Transcribing,
+    /// 文字起こしが完了した
+    Done(TranscriptionResult),
+    /// 文字起こしに失敗した
+    Error(String),
+}
+
+/// イベントを送信（送信先が未設定、または受信側が詰まっている・閉じている場合は捨てる）
+///
+/// 音声処理のスレッドを止めないよう、受信側の処理は待たない
+pub fn emit(events: Option<&mpsc::Sender<RecordingEvent>>, event: RecordingEvent) {
+    if let Some(events) = events {
+        if let Err(e) = events.try_send(event) {
+            debug!("状態の変化を送信できませんでした: {}", e);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use anyhow::{Result, anyhow};
+use chrono::{SecondsFormat, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::transcriber::{AudioDiagnostics, TranscriptionResult};
+
+/// 履歴ファイル名
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+/// 文字起こし履歴の1件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// 記録日時（UTC、RFC 3339）
+    pub timestamp: String,
+    pub text: String,
+    pub language: String,
+    pub duration_sec: f32,
+    /// 文字起こしに送った音声の診断情報（不具合の報告用）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<AudioDiagnostics>,
+}
+
+/// 履歴ファイルのパスを取得（データディレクトリ内のhistory.jsonl）
+pub fn history_path() -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "volment", "voilip")
+        .ok_or_else(|| anyhow!("データディレクトリを特定できません"))?;
+    Ok(proj_dirs.data_dir().join(HISTORY_FILE_NAME))
+}
+
+/// 文字起こし結果を履歴に追記
+pub fn append_history(result: &TranscriptionResult) -> Result<()> {
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    
+    let entry = HistoryEntry {
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        text: result.text.clone(),
+        language: result.language.clone(),
+        duration_sec: result.duration_sec,
+        diagnostics: result.diagnostics,
+    };
+    
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    
+    Ok(())
+}
+
+/// 履歴ファイルを空にする（履歴ファイルがなかった場合は`false`を返す）
+pub fn clear_history() -> Result<bool> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::File::create(&path)?;
+    Ok(true)
+}
+
+/// 直近の履歴を古い順に取得（壊れた行は読み飛ばす）
+pub fn read_history(limit: usize) -> Result<Vec<HistoryEntry>> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    
+    let content = fs::read_to_string(&path)?;
+    let entries: Vec<HistoryEntry> = content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
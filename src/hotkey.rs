@@ -0,0 +1,553 @@
+use anyhow::{Result, anyhow};
+use rdev::{EventType, Key};
+use std::time::{Duration, SystemTime};
+
+/// 解放から再押下までがこれより短い場合はキーリピートとみなす
+///
+/// X11ではキーを押し続けると解放と押下のイベントが交互に届くため、
+/// 解放の直後に届いた押下は同じ押下の続きとして扱う
+const REPEAT_RELEASE_GAP: Duration = Duration::from_millis(50);
+
+/// 修飾キーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    AltGr,
+    Shift,
+    Meta,
+}
+
+impl Modifier {
+    /// 設定文字列の修飾キー名から変換
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "CTRL" | "CONTROL" => Some(Modifier::Ctrl),
+            "ALT" => Some(Modifier::Alt),
+            "ALTGR" => Some(Modifier::AltGr),
+            "SHIFT" => Some(Modifier::Shift),
+            "META" | "SUPER" | "WIN" | "CMD" => Some(Modifier::Meta),
+            _ => None,
+        }
+    }
+    
+    /// 押されたキーが修飾キーなら種類を返す（左右は区別しない）
+    pub fn from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::ControlLeft | Key::ControlRight => Some(Modifier::Ctrl),
+            Key::Alt => Some(Modifier::Alt),
+            Key::AltGr => Some(Modifier::AltGr),
+            Key::ShiftLeft | Key::ShiftRight => Some(Modifier::Shift),
+            Key::MetaLeft | Key::MetaRight | Key::Unknown(0xE05B) | Key::Unknown(0xE05C) => Some(Modifier::Meta),
+            _ => None,
+        }
+    }
+}
+
+/// ホットキー（必要な修飾キーの組み合わせとメインキー）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotkey {
+    /// 同時に押されている必要がある修飾キー（設定文字列の順）
+    pub modifiers: Vec<Modifier>,
+    /// 押下・解放を監視するメインキー
+    pub key: Key,
+}
+
+impl Hotkey {
+    /// 押下中のキーに必要な修飾キーがすべて含まれているか
+    pub fn modifiers_held(&self, pressed: &[Key]) -> bool {
+        self.modifiers.iter()
+            .all(|modifier| pressed.iter().any(|&key| Modifier::from_key(key) == Some(*modifier)))
+    }
+    
+    /// このキーがホットキーに必要な修飾キーか
+    pub fn requires_modifier(&self, key: Key) -> bool {
+        Modifier::from_key(key).is_some_and(|modifier| self.modifiers.contains(&modifier))
+    }
+}
+
+/// 押下中の修飾キーを追跡
+#[derive(Debug, Default)]
+pub struct ModifierState {
+    pressed: Vec<Key>,
+}
+
+impl ModifierState {
+    /// キー押下を記録
+    pub fn press(&mut self, key: Key) {
+        if Modifier::from_key(key).is_some() && !self.pressed.contains(&key) {
+            self.pressed.push(key);
+        }
+    }
+    
+    /// キー解放を記録
+    pub fn release(&mut self, key: Key) {
+        self.pressed.retain(|&pressed| pressed != key);
+    }
+    
+    /// ホットキーに必要な修飾キーがすべて押されているか
+    pub fn satisfies(&self, hotkey: &Hotkey) -> bool {
+        hotkey.modifiers_held(&self.pressed)
+    }
+}
+
+/// ホットキーの押下を、物理的な1回の押下につき1度だけ検出する
+///
+/// キーを押し続けるとOSのキーリピートで押下イベントが繰り返し届くため、
+/// 一度処理した押下はメインキーが解放されるまで無視する。
+/// トグルキーを長押しして録音が開始・停止を繰り返さないことで確認できる
+#[derive(Debug)]
+pub struct HotkeyTrigger {
+    hotkey: Hotkey,
+    modifiers: ModifierState,
+    /// 現在の押下を処理済みか
+    handled: bool,
+    /// 処理済みの押下が最後に解放された時刻
+    released_at: Option<SystemTime>,
+}
+
+impl HotkeyTrigger {
+    pub fn new(hotkey: Hotkey) -> Self {
+        Self {
+            hotkey,
+            modifiers: ModifierState::default(),
+            handled: false,
+            released_at: None,
+        }
+    }
+    
+    /// キーイベントを処理し、ホットキーが新たに押された場合にtrueを返す
+    pub fn handle(&mut self, event_type: EventType, time: SystemTime) -> bool {
+        match event_type {
+            EventType::KeyPress(key) => {
+                self.modifiers.press(key);
+                if key != self.hotkey.key || self.handled {
+                    return false;
+                }
+                
+                let is_repeat = self.released_at
+                    .and_then(|released_at| time.duration_since(released_at).ok())
+                    .is_some_and(|gap| gap < REPEAT_RELEASE_GAP);
+                if is_repeat {
+                    // 解放は見かけだけなので、押下を処理済みのまま保つ
+                    self.handled = true;
+                    return false;
+                }
+                
+                if !self.modifiers.satisfies(&self.hotkey) {
+                    return false;
+                }
+                self.handled = true;
+                true
+            }
+            EventType::KeyRelease(key) => {
+                self.modifiers.release(key);
+                if key == self.hotkey.key {
+                    if self.handled {
+                        self.released_at = Some(time);
+                    }
+                    self.handled = false;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// キー操作で行う録音の操作（ハイブリッドモード・開始/停止キーモード）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    /// 録音を開始
+    Start,
+    /// 録音を停止
+    Stop,
+    /// 何もしない
+    None,
+}
+
+/// 短く押すとトグル、長押しするとPush-To-Talkとして動くキー操作の判定
+///
+/// 録音していない状態で押すと録音を開始し、`tap_threshold`より長く押していた場合は離したときに停止する。
+/// それより短い押下（タップ）の場合は録音を続け、次の押下で停止する
+#[derive(Debug)]
+pub struct HybridTrigger {
+    trigger: HotkeyTrigger,
+    hotkey: Hotkey,
+    tap_threshold: Duration,
+    /// 録音を開始した押下の時刻（押下中のみ）
+    pressed_at: Option<SystemTime>,
+}
+
+impl HybridTrigger {
+    pub fn new(hotkey: Hotkey, tap_threshold: Duration) -> Self {
+        Self {
+            trigger: HotkeyTrigger::new(hotkey.clone()),
+            hotkey,
+            tap_threshold,
+            pressed_at: None,
+        }
+    }
+    
+    /// キーイベントを処理し、行う録音の操作を返す
+    ///
+    /// `recording`には現在録音中かを渡す（自動停止などでキー操作以外から停止した場合に状態を合わせるため）
+    pub fn handle(&mut self, event_type: EventType, time: SystemTime, recording: bool) -> KeyAction {
+        if self.trigger.handle(event_type, time) {
+            return if recording {
+                // タップで録音を続けている状態からの押下は停止（離したときは何もしない）
+                self.pressed_at = None;
+                KeyAction::Stop
+            } else {
+                self.pressed_at = Some(time);
+                KeyAction::Start
+            };
+        }
+        
+        let EventType::KeyRelease(key) = event_type else {
+            return KeyAction::None;
+        };
+        if key != self.hotkey.key && !self.hotkey.requires_modifier(key) {
+            return KeyAction::None;
+        }
+        let Some(pressed_at) = self.pressed_at.take() else {
+            return KeyAction::None;
+        };
+        
+        let held = time.duration_since(pressed_at).unwrap_or_default();
+        if held > self.tap_threshold && recording {
+            KeyAction::Stop
+        } else {
+            KeyAction::None
+        }
+    }
+}
+
+/// 録音の開始と停止を別々のキーで行うキー操作の判定
+///
+/// 録音中の開始キーと、録音していないときの停止キーは何もしない。
+/// 両方に同じキーを指定した場合は、押すたびに開始と停止を切り替える
+#[derive(Debug)]
+pub struct SeparateKeysTrigger {
+    start: HotkeyTrigger,
+    stop: HotkeyTrigger,
+}
+
+impl SeparateKeysTrigger {
+    pub fn new(start: Hotkey, stop: Hotkey) -> Self {
+        Self {
+            start: HotkeyTrigger::new(start),
+            stop: HotkeyTrigger::new(stop),
+        }
+    }
+    
+    /// キーイベントを処理し、行う録音の操作を返す
+    ///
+    /// `recording`には現在録音中かを渡す（自動停止などでキー操作以外から停止した場合に状態を合わせるため）
+    pub fn handle(&mut self, event_type: EventType, time: SystemTime, recording: bool) -> KeyAction {
+        // 修飾キーの状態を揃えるため、どちらのキーにも必ずイベントを渡す
+        let start_pressed = self.start.handle(event_type, time);
+        let stop_pressed = self.stop.handle(event_type, time);
+        match (recording, start_pressed, stop_pressed) {
+            (false, true, _) => KeyAction::Start,
+            (true, _, true) => KeyAction::Stop,
+            _ => KeyAction::None,
+        }
+    }
+}
+
+/// ホットキーの設定文字列を解析（例: "Ctrl+Shift+F9", "RCtrl"）
+///
+/// 最後の要素をメインキー、それより前を修飾キーとして扱う。
+/// 修飾キーだけの指定（"RCtrl"など）はそのキー自体をメインキーとする
+pub fn parse_hotkey(name: &str) -> Result<Hotkey> {
+    let parts: Vec<&str> = name.split('+')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect();
+    
+    let (main, modifier_names) = parts.split_last()
+        .ok_or_else(|| anyhow!("ホットキーが空です"))?;
+    
+    let mut modifiers = Vec::new();
+    for modifier_name in modifier_names {
+        let modifier = Modifier::from_name(modifier_name)
+            .ok_or_else(|| anyhow!("未対応の修飾キー: {} ({})", modifier_name, name))?;
+        if !modifiers.contains(&modifier) {
+            modifiers.push(modifier);
+        }
+    }
+    
+    Ok(Hotkey {
+        modifiers,
+        key: parse_key_name(main)?,
+    })
+}
+
+/// 単一のキー名をrdevのKeyに変換
+pub fn parse_key_name(key_name: &str) -> Result<Key> {
+    let name = key_name.trim().to_uppercase();
+    
+    // 英字・数字キー
+    if let [c] = name.as_bytes() {
+        let key = match c {
+            b'A' => Key::KeyA,
+            b'B' => Key::KeyB,
+            b'C' => Key::KeyC,
+            b'D' => Key::KeyD,
+            b'E' => Key::KeyE,
+            b'F' => Key::KeyF,
+            b'G' => Key::KeyG,
+            b'H' => Key::KeyH,
+            b'I' => Key::KeyI,
+            b'J' => Key::KeyJ,
+            b'K' => Key::KeyK,
+            b'L' => Key::KeyL,
+            b'M' => Key::KeyM,
+            b'N' => Key::KeyN,
+            b'O' => Key::KeyO,
+            b'P' => Key::KeyP,
+            b'Q' => Key::KeyQ,
+            b'R' => Key::KeyR,
+            b'S' => Key::KeyS,
+            b'T' => Key::KeyT,
+            b'U' => Key::KeyU,
+            b'V' => Key::KeyV,
+            b'W' => Key::KeyW,
+            b'X' => Key::KeyX,
+            b'Y' => Key::KeyY,
+            b'Z' => Key::KeyZ,
+            b'0' => Key::Num0,
+            b'1' => Key::Num1,
+            b'2' => Key::Num2,
+            b'3' => Key::Num3,
+            b'4' => Key::Num4,
+            b'5' => Key::Num5,
+            b'6' => Key::Num6,
+            b'7' => Key::Num7,
+            b'8' => Key::Num8,
+            b'9' => Key::Num9,
+            _ => return Err(anyhow!("未対応のキー名: {}", key_name)),
+        };
+        return Ok(key);
+    }
+    
+    // F13〜F24はrdevに定義がないため、プラットフォームのキーコードで扱う
+    if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        if (13..=24).contains(&number) {
+            return extended_function_key(number)
+                .ok_or_else(|| anyhow!("このプラットフォームでは{}は使用できません", key_name));
+        }
+    }
+    
+    let key = match name.as_str() {
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        "SHIFT" | "LSHIFT" => Key::ShiftLeft,
+        "RSHIFT" => Key::ShiftRight,
+        "CTRL" | "LCTRL" => Key::ControlLeft,
+        "RCTRL" => Key::ControlRight,
+        "ALT" | "LALT" => Key::Alt,
+        "RALT" => Key::Alt,
+        "ALTGR" => Key::AltGr,
+        "META" | "SUPER" | "LMETA" | "LSUPER" => Key::MetaLeft, // Windows/Super key
+        "RMETA" | "RSUPER" => Key::MetaRight, // Right Windows/Super key
+        "SPACE" => Key::Space,
+        "TAB" => Key::Tab,
+        "ESCAPE" | "ESC" => Key::Escape,
+        "RETURN" | "ENTER" => Key::Return,
+        "BACKSPACE" => Key::Backspace,
+        "CAPSLOCK" => Key::CapsLock,
+        "NUMLOCK" => Key::NumLock,
+        // テンキー
+        "KP0" => Key::Kp0,
+        "KP1" => Key::Kp1,
+        "KP2" => Key::Kp2,
+        "KP3" => Key::Kp3,
+        "KP4" => Key::Kp4,
+        "KP5" => Key::Kp5,
+        "KP6" => Key::Kp6,
+        "KP7" => Key::Kp7,
+        "KP8" => Key::Kp8,
+        "KP9" => Key::Kp9,
+        "KPPLUS" => Key::KpPlus,
+        "KPMINUS" => Key::KpMinus,
+        "KPMULTIPLY" => Key::KpMultiply,
+        "KPDIVIDE" => Key::KpDivide,
+        "KPRETURN" | "KPENTER" => Key::KpReturn,
+        "KPDELETE" | "KPDOT" => Key::KpDelete,
+        // マルチメディアキー（rdevでサポートされていないのでUnknownとして処理）
+        "PLAY" | "PLAYPAUSE" => Key::Unknown(0xE022), // Play/Pause
+        "STOP" => Key::Unknown(0xE024), // Media Stop
+        "NEXT" | "NEXTTRACK" => Key::Unknown(0xE019), // Next Track
+        "PREV" | "PREVTRACK" => Key::Unknown(0xE010), // Previous Track
+        "VOLUMEUP" => Key::Unknown(0xE030), // Volume Up
+        "VOLUMEDOWN" => Key::Unknown(0xE02E), // Volume Down
+        "MUTE" => Key::Unknown(0xE020), // Volume Mute
+        // その他の特殊キー
+        "PRINT" | "PRINTSCREEN" => Key::PrintScreen,
+        "SCROLLLOCK" => Key::ScrollLock,
+        "PAUSE" => Key::Pause,
+        "INSERT" => Key::Insert,
+        "HOME" => Key::Home,
+        "PAGEUP" => Key::PageUp,
+        "DELETE" => Key::Delete,
+        "END" => Key::End,
+        "PAGEDOWN" => Key::PageDown,
+        "RIGHT" => Key::RightArrow,
+        "LEFT" => Key::LeftArrow,
+        "DOWN" => Key::DownArrow,
+        "UP" => Key::UpArrow,
+        _ => return Err(anyhow!("未対応のキー名: {}", key_name)),
+    };
+    
+    Ok(key)
+}
+
+/// F13〜F24をプラットフォームごとのキーコードに変換
+fn extended_function_key(number: u32) -> Option<Key> {
+    #[cfg(target_os = "linux")]
+    {
+        // X11のキーコード（F13=191）
+        Some(Key::Unknown(191 + number - 13))
+    }
+    
+    #[cfg(target_os = "windows")]
+    {
+        // 仮想キーコード（VK_F13=0x7C）
+        Some(Key::Unknown(0x7C + number - 13))
+    }
+    
+    #[cfg(target_os = "macos")]
+    {
+        // macOSのキーコード（F13〜F20のみ）
+        const CODES: [u32; 8] = [105, 107, 113, 106, 64, 79, 80, 90];
+        CODES.get((number - 13) as usize).map(|&code| Key::Unknown(code))
+    }
+    
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    {
+        let _ = number;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn holding_the_key_triggers_once_despite_auto_repeat() {
+        let mut trigger = HotkeyTrigger::new(parse_hotkey("F9").unwrap());
+        assert!(trigger.handle(EventType::KeyPress(Key::F9), at(0)));
+        // OSのキーリピートによる押下の繰り返し
+        for ms in (30..600).step_by(30) {
+            assert!(!trigger.handle(EventType::KeyPress(Key::F9), at(ms)));
+        }
+        // X11では長押し中に解放と押下が交互に届く
+        assert!(!trigger.handle(EventType::KeyRelease(Key::F9), at(600)));
+        assert!(!trigger.handle(EventType::KeyPress(Key::F9), at(610)));
+        assert!(!trigger.handle(EventType::KeyRelease(Key::F9), at(1000)));
+    }
+
+    #[test]
+    fn each_physical_press_toggles_once() {
+        let mut trigger = HotkeyTrigger::new(parse_hotkey("F9").unwrap());
+        let mut recording = false;
+        for start in [0, 500, 1000] {
+            for ms in [start, start + 30, start + 60] {
+                if trigger.handle(EventType::KeyPress(Key::F9), at(ms)) {
+                    recording = !recording;
+                }
+            }
+            trigger.handle(EventType::KeyRelease(Key::F9), at(start + 200));
+        }
+        assert!(recording);
+    }
+
+    #[test]
+    fn release_without_press_is_ignored() {
+        let mut trigger = HotkeyTrigger::new(parse_hotkey("Ctrl+F9").unwrap());
+        assert!(!trigger.handle(EventType::KeyRelease(Key::F9), at(0)));
+        // 修飾キーが押されていない押下は処理済みにしない
+        assert!(!trigger.handle(EventType::KeyPress(Key::F9), at(10)));
+        assert!(!trigger.handle(EventType::KeyRelease(Key::F9), at(20)));
+
+        assert!(!trigger.handle(EventType::KeyPress(Key::ControlLeft), at(100)));
+        assert!(trigger.handle(EventType::KeyPress(Key::F9), at(110)));
+    }
+
+    #[test]
+    fn parses_modifier_combinations_in_order() {
+        let cases = [
+            ("Ctrl+Shift+F9", vec![Modifier::Ctrl, Modifier::Shift], Key::F9),
+            ("Alt+Ctrl+Space", vec![Modifier::Alt, Modifier::Ctrl], Key::Space),
+            ("ctrl + alt + shift + a", vec![Modifier::Ctrl, Modifier::Alt, Modifier::Shift], Key::KeyA),
+            // 重複した修飾キーは1つにまとめる
+            ("Ctrl+Control+F9", vec![Modifier::Ctrl], Key::F9),
+            // 修飾キーだけの指定はそのキー自体をメインキーにする
+            ("RCtrl", vec![], Key::ControlRight),
+        ];
+        for (name, modifiers, key) in cases {
+            assert_eq!(parse_hotkey(name).unwrap(), Hotkey { modifiers, key }, "{}", name);
+        }
+        assert!(parse_hotkey("Hyper+F9").is_err());
+        assert!(parse_hotkey("+").is_err());
+    }
+
+    #[test]
+    fn combination_requires_every_modifier() {
+        let mut trigger = HotkeyTrigger::new(parse_hotkey("Ctrl+Shift+F9").unwrap());
+        trigger.handle(EventType::KeyPress(Key::ControlLeft), at(0));
+        assert!(!trigger.handle(EventType::KeyPress(Key::F9), at(10)));
+        trigger.handle(EventType::KeyRelease(Key::F9), at(20));
+
+        trigger.handle(EventType::KeyPress(Key::ShiftRight), at(100));
+        assert!(trigger.handle(EventType::KeyPress(Key::F9), at(110)));
+    }
+
+    #[test]
+    fn key_name_table() {
+        let cases = [
+            ("Kp0", Key::Kp0),
+            ("KP9", Key::Kp9),
+            ("KpPlus", Key::KpPlus),
+            ("KpMinus", Key::KpMinus),
+            ("KpEnter", Key::KpReturn),
+            ("F12", Key::F12),
+            ("Esc", Key::Escape),
+            ("7", Key::Num7),
+        ];
+        for (name, key) in cases {
+            assert_eq!(parse_key_name(name).unwrap(), key, "{}", name);
+        }
+        assert_eq!(parse_hotkey("Ctrl+Kp0").unwrap(), Hotkey { modifiers: vec![Modifier::Ctrl], key: Key::Kp0 });
+
+        // 認識できない名前は別のキーに読み替えずエラーにする
+        for name in ["Fq", "F25", "F0", "Kp10", ""] {
+            assert!(parse_key_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn function_keys_above_f12_use_x11_keycodes() {
+        assert_eq!(parse_key_name("F13").unwrap(), Key::Unknown(191));
+        assert_eq!(parse_key_name("F24").unwrap(), Key::Unknown(202));
+        assert_eq!(parse_hotkey("Shift+F20").unwrap().key, Key::Unknown(198));
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// レート制限を受けたキーを後回しにする時間（Retry-Afterの指定がない場合）
+pub const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// APIキーの使用状況（録音ごとに設定を複製するため、プロセス全体で共有する）
+static ROTATION: OnceLock<Mutex<KeyRotation>> = OnceLock::new();
+
+/// 複数のAPIキーをリクエストごとに順に使い、レート制限を受けたキーをしばらく後回しにする
+#[derive(Debug, Default)]
+pub struct KeyRotation {
+    /// 次に使うキーの位置
+    next: usize,
+    /// レート制限を受けたキーと、後回しにする期限
+    limited_until: HashMap<String, Instant>,
+}
+
+impl KeyRotation {
+    /// 次に使うキーを選ぶ
+    ///
+    /// レート制限中のキーは飛ばし、すべて制限中の場合は最も早く制限が解けるキーを使う
+    pub fn select<'a>(&mut self, keys: &'a [String], now: Instant) -> Option<&'a str> {
+        if keys.is_empty() {
+            return None;
+        }
+        self.limited_until.retain(|_, until| *until > now);
+        
+        let start = self.next % keys.len();
+        let index = (0..keys.len())
+            .map(|offset| (start + offset) % keys.len())
+            .find(|&i| !self.limited_until.contains_key(&keys[i]))
+            .or_else(|| (0..keys.len()).min_by_key(|&i| self.limited_until.get(&keys[i]).copied()))
+            .unwrap_or(start);
+        self.next = index + 1;
+        Some(&keys[index])
+    }
+    
+    /// キーがレート制限を受けたことを記録し、`until`まで後回しにする
+    pub fn mark_rate_limited(&mut self, key: &str, until: Instant) {
+        self.limited_until.insert(key.to_string(), until);
+    }
+    
+    /// レート制限を受けていないキーがあるか
+    pub fn has_available(&self, keys: &[String], now: Instant) -> bool {
+        keys.iter().any(|key| self.limited_until.get(key).is_none_or(|until| *until <= now))
+    }
+}
+
+/// プロセス全体で共有する使用状況
+fn rotation() -> std::sync::MutexGuard<'static, KeyRotation> {
+    // ロック中にパニックしても使用状況が壊れることはないため、そのまま使い続ける
+    ROTATION.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 次のリクエストで使うキーを選ぶ
+pub fn select_key(keys: &[String]) -> Option<String> {
+    rotation().select(keys, Instant::now()).map(str::to_string)
+}
+
+/// キーがレート制限を受けたことを記録し、`cooldown`の間は後回しにする
+pub fn mark_rate_limited(key: &str, cooldown: Duration) {
+    rotation().mark_rate_limited(key, Instant::now() + cooldown);
+}
+
+/// レート制限を受けていないキーがあるか
+pub fn has_available_key(keys: &[String]) -> bool {
+    rotation().has_available(keys, Instant::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn keys() -> Vec<String> {
+        vec!["key-a".to_string(), "key-b".to_string(), "key-c".to_string()]
+    }
+    
+    #[test]
+    fn select_rotates_across_keys() {
+        let keys = keys();
+        let mut rotation = KeyRotation::default();
+        let now = Instant::now();
+        let selected: Vec<&str> = (0..4).map(|_| rotation.select(&keys, now).unwrap()).collect();
+        assert_eq!(selected, vec!["key-a", "key-b", "key-c", "key-a"]);
+        assert_eq!(rotation.select(&[], now), None);
+    }
+    
+    #[test]
+    fn select_skips_rate_limited_key_until_retry_after_expires() {
+        let keys = keys();
+        let mut rotation = KeyRotation::default();
+        let now = Instant::now();
+        assert_eq!(rotation.select(&keys, now), Some("key-a"));
+        // key-bが429を受け、Retry-Afterで30秒後回しにされた
+        rotation.mark_rate_limited("key-b", now + Duration::from_secs(30));
+        
+        let selected: Vec<&str> = (0..3).map(|_| rotation.select(&keys, now).unwrap()).collect();
+        assert_eq!(selected, vec!["key-c", "key-a", "key-c"]);
+        assert!(rotation.has_available(&keys, now));
+        
+        // 期限が過ぎれば再び順番に使う
+        let later = now + Duration::from_secs(31);
+        let selected: Vec<&str> = (0..3).map(|_| rotation.select(&keys, later).unwrap()).collect();
+        assert_eq!(selected, vec!["key-a", "key-b", "key-c"]);
+    }
+    
+    #[test]
+    fn select_uses_key_released_first_when_all_are_rate_limited() {
+        let keys = keys();
+        let mut rotation = KeyRotation::default();
+        let now = Instant::now();
+        rotation.mark_rate_limited("key-a", now + Duration::from_secs(30));
+        rotation.mark_rate_limited("key-b", now + Duration::from_secs(10));
+        rotation.mark_rate_limited("key-c", now + Duration::from_secs(20));
+        assert!(!rotation.has_available(&keys, now));
+        assert_eq!(rotation.select(&keys, now), Some("key-b"));
+    }
+}
@@ -0,0 +1,36 @@
+//! voilip: マイク入力をリアルタイムで文字起こしするライブラリ
+//!
+//! CLIのほか、`VoilipSession`を使って他のアプリケーションに組み込める
+
+pub mod audio;
+pub mod audio_log;
+pub mod transcriber;
+pub mod output;
+pub mod config;
+pub mod utils;
+pub mod vad;
+pub mod hotkey;
+pub mod history;
+pub mod doctor;
+pub mod session;
+pub mod events;
+pub mod local_whisper;
+pub mod bench;
+pub mod subtitle;
+pub mod tui;
+pub mod deepgram;
+pub mod calibrate;
+pub mod control;
+pub mod stats;
+pub mod verify;
+pub mod whisper_server;
+pub mod key_rotation;
+pub mod error;
+
+pub use crate::audio::AudioCapture;
+pub use crate::config::Config;
+pub use crate::error::VoilipError;
+pub use crate::events::RecordingEvent;
+pub use crate::output::OutputManager;
+pub use crate::session::VoilipSession;
+pub use crate::transcriber::{Transcriber, TranscriptionResult};
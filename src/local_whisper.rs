@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::transcriber::TranscriptionResult;
+
+#[cfg(feature = "whisper-cpp")]
+pub use self::imp::transcribe;
+
+/// whisper-cppフィーチャーを有効にしてビルドしたかどうか
+pub const AVAILABLE: bool = cfg!(feature = "whisper-cpp");
+
+/// whisper-cppフィーチャーなしでビルドした場合はエラーを返す
+#[cfg(not(feature = "whisper-cpp"))]
+pub fn transcribe(
+    _config: &Config,
+    _model_path: &Path,
+    _samples: &[f32],
+    _channels: u16,
+    _sample_rate: u32,
+) -> Result<TranscriptionResult> {
+    Err(anyhow!("whisper-rsエンジンを使うには、whisper-cppフィーチャーを有効にしてビルドする必要があります (cargo build --features whisper-cpp)"))
+}
+
+/// whisper-rsを使ったプロセス内での音声認識
+#[cfg(feature = "whisper-cpp")]
+mod imp {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use tracing::{debug, info};
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+    
+    use crate::audio::to_mono_at_rate;
+    use crate::config::AUTO_LANGUAGE;
+    use crate::transcriber::{Segment, segment_separator};
+    
+    /// Whisperのモデルが受け取るサンプルレート
+    const WHISPER_SAMPLE_RATE: u32 = 16000;
+    /// セグメントのタイムスタンプの単位（秒）
+    const TIMESTAMP_UNIT_SEC: f32 = 0.01;
+    
+    /// 読み込み済みのモデル（読み込みには数秒かかるため、録音ごとに読み直さない）
+    static CONTEXT: Mutex<Option<(PathBuf, Arc<WhisperContext>)>> = Mutex::new(None);
+    
+    /// モデルを読み込む（同じモデルが読み込み済みならそれを使う）
+    fn load_context(model_path: &Path) -> Result<Arc<WhisperContext>> {
+        let mut cached = CONTEXT.lock().map_err(|_| anyhow!("モデルのキャッシュのロックに失敗しました"))?;
+        if let Some((path, context)) = cached.as_ref() {
+            if path == model_path {
+                return Ok(context.clone());
+            }
+        }
+        
+        info!("Whisperモデルを読み込みます: {}", model_path.display());
+        let path = model_path.to_str().ok_or_else(|| anyhow!("モデルのパスが不正です: {}", model_path.display()))?;
+        let context = Arc::new(
+            WhisperContext::new_with_params(path, WhisperContextParameters::default())
+                .map_err(|e| anyhow!("Whisperモデルの読み込みに失敗しました: {}", e))?,
+        );
+        *cached = Some((model_path.to_path_buf(), context.clone()));
+        Ok(context)
+    }
+    
+    /// インターリーブされたf32のサンプルをWAVにエンコードせずにそのまま認識
+    pub fn transcribe(
+        config: &Config,
+        model_path: &Path,
+        samples: &[f32],
+        channels: u16,
+        sample_rate: u32,
+    ) -> Result<TranscriptionResult> {
+        let context = load_context(model_path)?;
+        let mut state = context.create_state()
+            .map_err(|e| anyhow!("Whisperの状態の作成に失敗しました: {}", e))?;
+        
+        let audio = to_mono_at_rate(samples, channels as usize, sample_rate, WHISPER_SAMPLE_RATE);
+        let duration = audio.len() as f32 / WHISPER_SAMPLE_RATE as f32;
+        
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(config.language_hint().unwrap_or(AUTO_LANGUAGE)));
+        params.set_translate(config.translate);
+        if let Some(threads) = config.whisper_cpp_threads {
+            params.set_n_threads(threads as i32);
+        }
+        if let Some(prompt) = config.effective_prompt() {
+            params.set_initial_prompt(prompt);
+        }
+        if let Some(temperature) = config.temperature {
+            params.set_temperature(temperature);
+        }
+        // 標準出力への進捗表示は不要
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        
+        state.full(params, &audio)
+            .map_err(|e| anyhow!("whisper-rsによる音声認識に失敗しました: {}", e))?;
+        
+        // 言語を自動検出した場合は検出結果を使う
+        let language = state.full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .map(|l| l.to_string());
+        
+        let count = state.full_n_segments().map_err(|e| anyhow!("セグメント数の取得に失敗しました: {}", e))?;
+        let mut segments = Vec::with_capacity(count.max(0) as usize);
+        for i in 0..count {
+            let text = state.full_get_segment_text_lossy(i)
+                .map_err(|e| anyhow!("セグメントの取得に失敗しました: {}", e))?;
+            let start = state.full_get_segment_t0(i).unwrap_or(0) as f32 * TIMESTAMP_UNIT_SEC;
+            let end = state.full_get_segment_t1(i).unwrap_or(0) as f32 * TIMESTAMP_UNIT_SEC;
+            segments.push(Segment {
+                start,
+                end,
+                text: text.trim().to_string(),
+                no_speech_prob: None,
+            });
+        }
+        
+        let text = segments.iter()
+            .map(|segment| segment.text.as_str())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(segment_separator(language.as_deref()));
+        
+        info!("whisper-rsによる文字起こし完了 ({:.2}秒)", duration);
+        debug!("文字起こし結果: {}", text);
+        
+        Ok(TranscriptionResult {
+            text,
+            language: if config.translate {
+                "en".to_string()
+            } else {
+                language.unwrap_or_else(|| config.language.clone())
+            },
+            duration_sec: duration,
+            is_final: true,
+            sequence: 0,
+            segments: if config.timestamps { segments } else { Vec::new() },
+            no_speech_prob: None,
+            diagnostics: None,
+            fallback_engine: None,
+            epoch: None,
+        })
+    }
+}
@@ -1,22 +1,29 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use anyhow::Result;
-use tracing::{info, error, Level};
+use tracing::{info, warn, error};
 use tracing_subscriber::FmtSubscriber;
+use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
 use tokio::sync::mpsc;
 
-mod audio;
-mod transcriber;
-mod output;
-mod config;
-mod utils;
+use voilip::{audio, bench, calibrate, config, doctor, history, tui, verify};
+use voilip::subtitle::{SubtitleFormat, format_subtitles};
+use voilip::config::{CliOverrides, Config, OutputMode, TranscriptionEngine};
+use voilip::audio::{AudioCapture, AudioChunk, describe_input_devices};
+use voilip::VoilipError;
+use voilip::transcriber::{Transcriber, TranscriptionResult, latest_recording, retranscribe_recording};
+use voilip::output::{OutputManager, format_test_result, set_clipboard_text};
+use voilip::session::VoilipSession;
+use voilip::control::ControlSocket;
+use voilip::utils::{AppState, setup_signal_handler, log_system_info, resolve_log_level, render_level_meter};
 
-use crate::config::Config;
-use crate::audio::AudioCapture;
-use crate::transcriber::{Transcriber, TranscriptionResult};
-use crate::output::OutputManager;
-use crate::utils::{AppState, setup_signal_handler, log_system_info};
+/// ダッシュボードへの状態の変化の通知のバッファサイズ
+const TUI_EVENT_CAPACITY: usize = 64;
+/// `--once`で発話を待つ時間のデフォルト（秒）
+const DEFAULT_ONCE_TIMEOUT_SEC: u64 = 30;
 
 #[derive(Parser)]
 #[command(name = "voilip")]
@@ -24,19 +31,48 @@ use crate::utils::{AppState, setup_signal_handler, log_system_info};
 #[command(version)]
 #[command(about = "CLI音声入力ユーティリティ - 音声をリアルタイムで文字起こしして出力", long_about = None)]
 struct Cli {
+    /// ログレベル (error, warn, info, debug, trace)
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+    
+    /// 詳細なログを出力 (-v: debug, -vv: trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    
+    /// 警告とエラーのみ出力
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    
+    /// 設定ファイルのパス (環境変数VOILIP_CONFIGより優先)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    
+    /// 使用するプロファイル (config.<名前>.jsonを使う、defaultで通常の設定)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // 起動時に一度だけ解析するため問題にならない
 enum Command {
     /// 音声認識を開始
     Start {
-        /// 出力モード: clipboard
+        /// 出力モード: clipboard, type, both, file, webhook, stdout, jsonl (カンマ区切りで複数指定可)
         #[arg(short, long)]
         mode: Option<String>,
         
-        /// 言語コード (例: ja, en)
+        /// ファイル出力時の出力先パス
+        #[arg(long)]
+        output_path: Option<PathBuf>,
+        
+        /// Webhook出力時のPOST先URL
+        #[arg(long)]
+        webhook_url: Option<String>,
+        
+        /// 言語コード (例: ja, en、autoで自動検出)
         #[arg(short, long)]
         lang: Option<String>,
         
@@ -48,21 +84,129 @@ enum Command {
         #[arg(long)]
         toggle: Option<String>,
         
-        /// 音声エンジン: gpt-4o, whisper-1, whisper-cpp
+        /// ハイブリッドモードのキー (短く押すとトグル、長押しするとPush-To-Talk)
+        #[arg(long)]
+        hybrid: Option<String>,
+        
+        /// 録音を開始するキー (--stop-keyと組み合わせて使用、例: F9)
+        #[arg(long, requires = "stop_key")]
+        start_key: Option<String>,
+        
+        /// 録音を停止するキー (--start-keyと組み合わせて使用、例: F10)
+        #[arg(long, requires = "start_key")]
+        stop_key: Option<String>,
+        
+        /// 無音判定の閾値 (振幅、無音検知モードのみ)
+        #[arg(long)]
+        silence_threshold: Option<f32>,
+        
+        /// 録音を終了する無音の長さ (ミリ秒、無音検知モードのみ)
+        #[arg(long)]
+        silence_duration: Option<u32>,
+        
+        /// 音声エンジン: gpt-4o, whisper-1, groq, whisper-cpp, whisper-server, whisper-rs, azure, deepgram
         #[arg(long)]
         engine: Option<String>,
         
-        /// Whisper.cppのパス (whisper-cppエンジン使用時)
+        /// Whisper.cppのパス (whisper-cppエンジン使用時、whisper-serverエンジンではwhisper-serverのパス)
         #[arg(long)]
         whisper_cpp_path: Option<PathBuf>,
         
-        /// Whisper.cppのモデルパス (whisper-cppエンジン使用時)
+        /// Whisper.cppのモデルパス (whisper-cpp・whisper-server・whisper-rsエンジン使用時)
         #[arg(long)]
         whisper_cpp_model: Option<PathBuf>,
         
+        /// Whisper.cppのスレッド数
+        #[arg(long)]
+        whisper_threads: Option<u32>,
+        
+        /// whisper-serverを待ち受けさせるポート (whisper-serverエンジン使用時、デフォルト: 8178)
+        #[arg(long)]
+        whisper_server_port: Option<u16>,
+        
+        /// Azure OpenAIのエンドポイント (azureエンジン使用時、例: https://example.openai.azure.com)
+        #[arg(long)]
+        azure_endpoint: Option<String>,
+        
+        /// Azure OpenAIのデプロイ名 (azureエンジン使用時)
+        #[arg(long)]
+        azure_deployment: Option<String>,
+        
+        /// Whisper.cppに追加で渡す引数 (複数回指定可、例: --whisper-arg=-ng)
+        #[arg(long = "whisper-arg", allow_hyphen_values = true)]
+        whisper_args: Vec<String>,
+        
         /// 使用するモデル (例: gpt-4o-transcribe)
         #[arg(long)]
         model: Option<String>,
+        
+        /// 連続ディクテーションモード (発話ごとに文字起こしして録音を継続)
+        #[arg(long)]
+        continuous: bool,
+        
+        /// 文字起こし結果をログと通知にのみ表示し、クリップボード・タイプ入力・ファイル・Webhookには出力しない
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// 標準出力 (--mode stdout) に本文の代わりに文字起こし結果全体のJSONを1行ずつ書き出す
+        #[arg(long)]
+        json_lines: bool,
+        
+        /// タイプ入力に使うツールを指定し、自動検出を行わない (wtype, xdotool, ydotool, osascript)
+        #[arg(long)]
+        type_tool: Option<String>,
+        
+        /// ログの代わりに録音状態・入力レベル・最近の文字起こし結果をダッシュボードで表示 (tuiフィーチャーが必要)
+        #[arg(long)]
+        tui: bool,
+        
+        /// 1回分の発話を録音・文字起こし・出力したら終了する (スクリプト向け)
+        #[arg(long, conflicts_with_all = ["tui", "continuous"])]
+        once: bool,
+        
+        /// --onceで発話を待つ時間 (秒、超えたらエラーで終了、デフォルト30)
+        #[arg(long, requires = "once")]
+        once_timeout: Option<u64>,
+        
+        /// 録音中に途中経過を文字起こしする間隔 (秒)
+        #[arg(long)]
+        stream_interval: Option<u32>,
+        
+        /// 録音の開始・停止などのコマンド (start, stop, toggle, status, quit) を受け付けるUnixドメインソケットのパス
+        #[arg(long)]
+        control_socket: Option<PathBuf>,
+        
+        /// 入力デバイス名 (部分一致)
+        #[arg(long)]
+        device: Option<String>,
+        
+        /// マイクの代わりに既定の出力先の音声 (システムの音声) を録音する
+        #[arg(long, conflicts_with = "device")]
+        loopback: bool,
+        
+        /// OpenAI互換APIのベースURL (例: http://localhost:8000/v1)
+        #[arg(long)]
+        api_base: Option<String>,
+        
+        /// API呼び出しとWebhookに使うHTTPプロキシ (例: http://proxy.example.com:8080)
+        #[arg(long)]
+        proxy: Option<String>,
+        
+        /// 認識を補助するプロンプト (専門用語や固有名詞など)
+        #[arg(long)]
+        prompt: Option<String>,
+        
+        /// 文字起こしのtemperature (0.0〜1.0)
+        #[arg(long)]
+        temperature: Option<f32>,
+        
+        /// 文字起こしの代わりに英語へ翻訳する
+        #[arg(long)]
+        translate: bool,
+        
+        /// 音声ブロックごとの入力レベル（時刻、RMS、ピーク、閾値、音声と判定したか）をCSVファイルに追記する（閾値の調整用）
+        #[arg(long)]
+        audio_log: Option<PathBuf>,
     },
     
     /// テストモード (音声ファイルから文字起こし)
@@ -71,11 +215,114 @@ enum Command {
         #[arg(long, required = true)]
         test_file: PathBuf,
         
-        /// 使用するモデル
+        /// 使用するモデル（設定のエンジンのまま、送るモデル名だけを上書きする）
+        #[arg(long)]
+        model: Option<String>,
+        
+        /// 認識を補助するプロンプト
+        #[arg(long)]
+        prompt: Option<String>,
+        
+        /// セグメントごとのタイムスタンプを表示
+        #[arg(long)]
+        timestamps: bool,
+        
+        /// 文字起こしの代わりに英語へ翻訳する
+        #[arg(long)]
+        translate: bool,
+        
+        /// SRT形式の字幕ファイルを出力 (タイムスタンプを自動で有効にする)
+        #[arg(long)]
+        srt: Option<PathBuf>,
+        
+        /// WebVTT形式の字幕ファイルを出力 (タイムスタンプを自動で有効にする)
+        #[arg(long)]
+        vtt: Option<PathBuf>,
+        
+        /// 結果を標準出力に書き出す形式: text, json, srt, vtt (ログは標準エラー出力、srt・vttはタイムスタンプを自動で有効にする)
+        #[arg(long, value_parser = ["text", "json", "srt", "vtt"])]
+        format: Option<String>,
+    },
+    
+    /// 保存した最新の録音を、別のエンジン・モデルで文字起こしし直す (録音の保存が必要)
+    Retry {
+        /// 使用するエンジン: gpt-4o, whisper-1, groq, azure, whisper-rs, whisper.cpp (省略すると設定のエンジン)
+        #[arg(long)]
+        engine: Option<String>,
+        
+        /// 使用するモデル (省略すると設定のモデル、エンジンを切り替えた場合はそのエンジンのデフォルト)
         #[arg(long)]
         model: Option<String>,
     },
     
+    /// 音声ファイルの文字起こしにかかる時間をモデルごとに計測
+    Bench {
+        /// 計測に使う音声ファイルパス
+        #[arg(long, required = true)]
+        test_file: PathBuf,
+        
+        /// 計測する回数 (最初のウォームアップの1回は含まない)
+        #[arg(long, default_value_t = 5)]
+        iterations: usize,
+        
+        /// 比較するモデル (カンマ区切り、省略時は設定のモデル)
+        #[arg(long, value_delimiter = ',')]
+        models: Vec<String>,
+    },
+    
+    /// 利用可能な入力デバイスを一覧表示
+    Devices,
+    
+    /// 実行環境を診断 (依存ツール、APIキー、入力デバイスなど)
+    Doctor,
+    
+    /// APIキーが有効か、モデル一覧APIを呼び出して確認
+    Verify {
+        /// 文字起こしに使えるモデルを一覧表示
+        #[arg(long)]
+        models: bool,
+    },
+    
+    /// 以降の起動で使うプロファイルを切り替え (省略すると現在のプロファイルを表示)
+    Profile {
+        /// プロファイル名 (defaultで通常の設定に戻す)
+        name: Option<String>,
+    },
+    
+    /// 入力レベルメーターを表示 (無音検知の閾値の調整用)
+    Meter {
+        /// 入力デバイス名 (部分一致)
+        #[arg(long)]
+        device: Option<String>,
+    },
+    
+    /// 周囲の音と話し声を測定して、無音検知の閾値を提案
+    Calibrate {
+        /// 入力デバイス名 (部分一致)
+        #[arg(long)]
+        device: Option<String>,
+        
+        /// 提案した閾値を無音検知モードの設定として保存
+        #[arg(long)]
+        save: bool,
+    },
+    
+    /// 文字起こし履歴を表示
+    History {
+        /// 表示する件数 (新しいものから)
+        #[arg(long, short = 'n', default_value = "10")]
+        limit: usize,
+    },
+    
+    /// 文字起こし履歴を消去
+    ClearHistory,
+    
+    /// シェル補完スクリプトを標準出力に出力
+    Completions {
+        /// 対象のシェル: bash, zsh, fish, powershell, elvish
+        shell: Shell,
+    },
+    
     /// 設定の管理
     Config {
         #[command(subcommand)]
@@ -88,196 +335,1129 @@ enum ConfigAction {
     /// 現在の設定を表示
     Show,
     
+    /// プロファイルの一覧を表示 (現在のプロファイルには*を付与)
+    ListProfiles,
+    
+    /// プロファイルの設定を別名で複製
+    CopyProfile {
+        /// 複製元のプロファイル名 (defaultで通常の設定)
+        from: String,
+        /// 複製先のプロファイル名
+        to: String,
+    },
+    
+    /// 設定を別のマシンに持ち運べるJSONファイルに書き出す
+    Export {
+        /// 書き出すファイル
+        path: PathBuf,
+        
+        /// APIキー (OpenAI・Groq・追加のキー) も書き出す
+        #[arg(long)]
+        include_api_keys: bool,
+    },
+    
+    /// exportで書き出した設定を読み込み、現在のプロファイルの設定として保存
+    Import {
+        /// 読み込むファイル
+        path: PathBuf,
+    },
+    
     /// APIキーを設定
     SetApiKey {
         /// OpenAI API キー
         api_key: String,
     },
     
+    /// 設定ファイルに平文で保存されたAPIキーをキーリングに移行
+    MigrateKey,
+    
+    /// レート制限を分散するために順に使うOpenAI APIキーを追加 (キーリングに保存)
+    AddApiKey {
+        /// OpenAI API キー
+        api_key: String,
+    },
+    
+    /// 追加のAPIキーをすべて削除
+    ClearExtraApiKeys,
+    
+    /// Groq APIキーを設定
+    SetGroqApiKey {
+        /// Groq API キー
+        api_key: String,
+    },
+    
+    /// HTTPプロキシを設定 (省略すると環境変数HTTPS_PROXY・ALL_PROXYに従う)
+    SetProxy {
+        /// プロキシのURL (例: http://proxy.example.com:8080)
+        url: Option<String>,
+    },
+    
+    /// 文字起こしAPIのリクエストに追加するHTTPヘッダーを設定 (同じ名前のヘッダーは置き換え)
+    AddHeader {
+        /// ヘッダー名 (例: X-Org-Id)
+        name: String,
+        /// 値
+        value: String,
+        /// AuthorizationやAPI-Keyヘッダーを指定して、APIキーによる認証を置き換える
+        #[arg(long)]
+        override_auth: bool,
+    },
+    
+    /// 追加のHTTPヘッダーを削除
+    RemoveHeader {
+        /// ヘッダー名
+        name: String,
+    },
+    
+    /// OpenAI互換APIのベースURLを設定 (省略すると公式API)
+    SetApiBase {
+        /// ベースURL (例: http://localhost:8000/v1)
+        url: Option<String>,
+    },
+    
+    /// 認識を補助するプロンプトを設定 (省略すると解除)
+    SetPrompt {
+        /// プロンプト (例: "Voilip, cpal, whisper.cpp")
+        prompt: Option<String>,
+    },
+    
+    /// プロンプトが未設定の場合に言語ごとの既定のプロンプトを使うか設定 (true/false)
+    SetDefaultPrompt {
+        /// 有効/無効
+        #[arg(action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// マルチチャンネル入力をダウンミックスせずに保持するか設定 (true/false)
+    SetPreserveChannels {
+        /// 有効にするかどうか
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    
+    /// 無音検知モードのVADの積極度を設定 (0〜3)
+    SetVadAggressiveness {
+        /// 積極度 (大きいほど雑音を音声と判定しにくい)
+        level: u8,
+    },
+    
+    /// 入力音声にかけるゲインを設定 (閾値・VADの判定の前にかける、1.0でそのまま)
+    SetInputGain {
+        /// 倍率 (例: 2.0)
+        gain: f32,
+    },
+    
+    /// 文字起こし前に雑音区間を減衰させるノイズゲートの強さを設定 (0で無効)
+    SetNoiseGate {
+        /// 強さ (0.0〜1.0)
+        strength: f32,
+    },
+    
+    /// 録音のピーク正規化を設定 (小さく録音された音声を持ち上げる)
+    SetNormalize {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+        
+        /// 目標のピーク (0.0より大きく1.0以下、デフォルト0.9)
+        #[arg(long)]
+        target: Option<f32>,
+    },
+    
+    /// 文字起こしする録音の最短の長さを設定 (これより短い録音は送信しない、0で無効)
+    SetMinRecording {
+        /// 最短の長さ (ミリ秒、デフォルト300)
+        ms: u32,
+    },
+    
+    /// 録音の先頭に含める録音開始前の音声の長さを設定 (発話の頭が切れる場合に長くする、0で無効)
+    SetPreRoll {
+        /// 長さ (ミリ秒、2000以下、デフォルト300)
+        ms: u32,
+    },
+    
+    /// 録音の先頭の無音を、最初の音の手前に残す長さまで切り詰める (省略すると切り詰めない)
+    SetLeadingTrim {
+        /// 最初の音の手前に残す長さ (ミリ秒、2000以下)
+        guard_ms: Option<u32>,
+    },
+    
+    /// セグメントごとのタイムスタンプ取得を設定 (true/false)
+    SetTimestamps {
+        /// 有効にするかどうか
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    
+    /// 文字起こしの代わりに英語へ翻訳するか設定
+    SetTranslate {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// 文字起こしのtemperatureを設定 (省略するとデフォルト)
+    SetTemperature {
+        /// temperature (0.0〜1.0)
+        temperature: Option<f32>,
+    },
+    
     /// トグルキーを設定 (例: F9, Ctrl+F10)
     SetToggleKey {
         /// キー名
         key: String,
     },
     
+    /// 録音を一時停止/再開するキーを設定 (省略すると解除)
+    SetPauseKey {
+        /// キー名 (例: F8, CTRL+p)
+        key: Option<String>,
+    },
+    
+    /// 文字起こしの言語を切り替えるキーを設定 (キーを省略すると解除)
+    SetLanguageKey {
+        /// キー名 (例: F7, CTRL+l)
+        key: Option<String>,
+        
+        /// 順に切り替える言語コード (カンマ区切り、例: ja,en)
+        #[arg(long, value_delimiter = ',')]
+        languages: Vec<String>,
+    },
+    
     /// Push-To-Talkキーを設定
     SetPttKey {
         /// キー名
         key: String,
     },
     
-    /// 言語を設定
-    SetLanguage {
-        /// 言語コード (例: ja, en)
-        lang: String,
+    /// ハイブリッドモードのキーを設定 (短く押すとトグル、長押しするとPush-To-Talk)
+    SetHybridKey {
+        /// キー名
+        key: String,
+        /// これ以下の押下時間をタップとみなす (ミリ秒)
+        #[arg(long, default_value_t = config::DEFAULT_TAP_THRESHOLD_MS)]
+        tap_threshold_ms: u32,
     },
     
-    /// モデルを設定
-    SetModel {
-        /// モデル名 (例: gpt-4o-transcribe)
-        model: String,
+    /// 録音の開始と停止を別々のキーで行うよう設定
+    SetSeparateKeys {
+        /// 録音を開始するキー (例: F9)
+        start_key: String,
+        /// 録音を停止するキー (例: F10)
+        stop_key: String,
     },
     
-    /// 無音除去を設定
-    SetRemoveSilence {
+    /// 無音検知モードを設定
+    SetVoiceActivity {
+        /// 無音判定の閾値 (振幅、0より大きく1.0未満)
+        threshold: f32,
+        /// 録音を終了する無音の長さ (ミリ秒)
+        duration_ms: u32,
+    },
+    
+    /// 文章モードを設定（無音検知モードで、長い無音が続くまで発話をまとめて録音する）
+    SetEndSilence {
+        /// 録音を確定する無音の長さ (ミリ秒、録音を終了する無音の長さより長く、省略すると無効)
+        ms: Option<u32>,
+    },
+    
+    /// 出力モードを設定
+    SetOutputMode {
+        /// 出力モード: clipboard, type, both, file, webhook, stdout, jsonl (カンマ区切りで複数指定可)
+        mode: String,
+        
+        /// ファイル出力時の出力先パス
+        #[arg(long)]
+        path: Option<PathBuf>,
+        
+        /// Webhook出力時のPOST先URL
+        #[arg(long)]
+        url: Option<String>,
+    },
+    
+    /// 文字起こし結果をJSONでPOSTするWebhook出力を設定
+    SetWebhook {
+        /// POST先のURL
+        url: String,
+    },
+    
+    /// ファイル出力時のタイムスタンプ付与を設定
+    SetOutputTimestamp {
         /// 有効/無効
-        #[arg(default_value = "true")]
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
         enable: bool,
     },
     
-    /// 再生速度を設定
-    SetSpeedFactor {
-        /// 速度倍率 (例: 1.0, 1.1, 1.5)
-        factor: f32,
+    /// 出力する文字起こし結果の前に付ける文字列を設定 (\n、\tが使える、省略すると解除)
+    SetPrefix {
+        /// 前置文字列
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        prefix: String,
     },
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 環境変数の読み込み
-    dotenv::dotenv().ok();
     
-    // ロガーの初期化
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
+    /// 出力する文字起こし結果の後に付ける文字列を設定 (\n、\tが使える、省略すると解除)
+    SetSuffix {
+        /// 後置文字列
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        suffix: String,
+    },
     
-    // システム情報のログ出力
-    log_system_info();
+    /// 出力モードごとの前置・後置文字列を設定 (共通の設定より優先、両方省略すると共通の設定に戻す)
+    SetModeAffix {
+        /// 出力モード: clipboard, type, both, file, webhook, stdout
+        mode: String,
+        
+        /// 前置文字列
+        #[arg(long, allow_hyphen_values = true)]
+        prefix: Option<String>,
+        
+        /// 後置文字列
+        #[arg(long, allow_hyphen_values = true)]
+        suffix: Option<String>,
+    },
     
-    // CLIの解析
-    let cli = Cli::parse();
+    /// 日本語の結果の表記を整えるか設定（全角数字を半角にし、句読点や日本語の文字の間の空白を除く）
+    SetJaNormalize {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
     
-    match cli.command {
-        Command::Start { 
-            mode, 
-            lang, 
-            ptt, 
-            toggle,
-            engine, 
-            whisper_cpp_path, 
-            whisper_cpp_model,
-            model,
-        } => {
-            // 設定の読み込み
-            let mut config = Config::new(
-                mode.as_deref(),
-                lang.as_deref(),
-                ptt.as_deref(),
-                engine.as_deref(),
-                whisper_cpp_path.as_ref(),
-                whisper_cpp_model.as_ref(),
-                toggle.as_deref(),
-                model.as_deref(),
-            )?;
-            
-            // トグルモードの場合、録音の最大持続時間を長く設定
-            if let config::RecordingMode::Toggle { .. } = config.recording_mode {
-                config.max_recording_duration_sec = Some(300); // 5分
-            }
-            
-            info!("音声認識を開始します: 言語={}, エンジン={}, モデル={}", 
-                config.language, 
-                match config.transcription_engine {
-                    config::TranscriptionEngine::GPT4o => "GPT-4o",
-                    config::TranscriptionEngine::Whisper1 => "Whisper-1",
-                    config::TranscriptionEngine::WhisperCpp { .. } => "Whisper.cpp",
-                },
-                config.model);
-            
-            // チャネルの設定
-            let (audio_tx, audio_rx) = mpsc::channel::<Vec<f32>>(32);
-            let (result_tx, result_rx) = mpsc::channel::<TranscriptionResult>(32);
-            
-            // アプリケーション状態の初期化
-            let app_state = Arc::new(AppState::new());
-            
-            // シグナルハンドラのセットアップ
-            setup_signal_handler(app_state.clone()).await?;
-            
-            // コンポーネントの初期化
-            let mut audio_capture = AudioCapture::new(config.clone(), audio_tx);
-            let mut transcriber = Transcriber::new(config.clone(), audio_rx, result_tx);
-            let mut output_manager = OutputManager::new(config.clone(), result_rx);
-            
-            // 音声キャプチャの開始
-            audio_capture.start()?;
-            
-            // 録音制御モードの設定
-            match config.recording_mode {
-                config::RecordingMode::PushToTalk { .. } => {
-                    audio_capture.setup_ptt_control()?;
-                }
-                config::RecordingMode::Toggle { .. } => {
-                    audio_capture.setup_toggle_control()?;
-                }
-                _ => {}
-            }
-            
-            // 各コンポーネントの実行
-            let transcriber_future = tokio::spawn(async move {
-                if let Err(e) = transcriber.run().await {
-                    error!("音声認識エラー: {}", e);
-                }
-            });
-            
-            let output_future = tokio::spawn(async move {
-                if let Err(e) = output_manager.run().await {
-                    error!("出力処理エラー: {}", e);
-                }
-            });
-            
-            // アプリケーションのメインループ
-            while app_state.is_running() {
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            }
-            
-            // 終了処理
-            info!("アプリケーションを終了しています...");
+    /// 文字起こし結果の大文字・小文字の整え方を設定
+    SetTextCase {
+        /// none (そのまま), lower (小文字), sentence (文頭を大文字)
+        case: String,
+    },
+    
+    /// 文字起こし結果の置換ルールを追加 (同じ置換対象は上書き)
+    AddReplacement {
+        /// 置換対象
+        from: String,
+        /// 置換後の文字列
+        to: String,
+        /// 大文字小文字を区別しない
+        #[arg(long)]
+        ignore_case: bool,
+    },
+    
+    /// 置換ルールを削除
+    RemoveReplacement {
+        /// 削除する置換対象
+        from: String,
+    },
+    
+    /// 出力しない定型文 (無音時の幻覚) を追加
+    AddBlocklist {
+        /// 定型文 (前後の空白・句読点と大文字小文字は無視して全体一致)
+        text: String,
+    },
+    
+    /// 出力しない定型文を削除
+    RemoveBlocklist {
+        /// 削除する定型文
+        text: String,
+    },
+    
+    /// 幻覚フィルタの閾値を設定
+    SetHallucinationFilter {
+        /// これより短い録音の結果は出力しない (秒)
+        #[arg(long)]
+        min_duration: Option<f32>,
+        
+        /// セグメントの無音確率の平均がこれを超える結果は出力しない (0.0〜1.0)
+        #[arg(long)]
+        max_no_speech_prob: Option<f32>,
+    },
+    
+    /// 録音をWAVファイルとして保存するディレクトリを設定 (省略すると保存しない)
+    SetSaveRecordings {
+        /// 保存先ディレクトリ
+        dir: Option<PathBuf>,
+    },
+    
+    /// 1回のAPIリクエストで送る音声の最大の長さを設定 (超える録音は無音部分で分割、省略すると無制限)
+    SetMaxApiDuration {
+        /// 秒数 (デフォルト1400)
+        sec: Option<u32>,
+    },
+    
+    /// 保存する録音の上限数を設定 (超えた分は古いものから削除、省略すると無制限)
+    SetMaxRecordings {
+        /// 上限数
+        count: Option<usize>,
+    },
+    
+    /// 録音の開始・停止などのコマンドを受け付ける制御ソケットのパスを設定 (省略すると無効)
+    SetControlSocket {
+        /// Unixドメインソケットのパス (例: /tmp/voilip.sock)
+        path: Option<PathBuf>,
+    },
+    
+    /// 設定をデフォルトに戻す (APIキーは引き継ぐ)
+    Reset {
+        /// キーリングに保存したAPIキーも削除する
+        #[arg(long)]
+        wipe_api_key: bool,
+    },
+    
+    /// デスクトップ通知の範囲を設定 (省略すると録音モードに応じた既定値)
+    SetNotifications {
+        /// 通知レベル: off, errors, all
+        level: Option<String>,
+    },
+    
+    /// 文字起こし履歴を保存するか設定
+    SetSaveHistory {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// bothモードでタイプ入力後に元のクリップボードを復元するか設定
+    SetRestoreClipboard {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// タイプ入力で1文字ごとに待つ時間を設定（入力を取りこぼすアプリケーション向け）
+    SetTypeDelay {
+        /// 待機時間 (ミリ秒、1000以下、0で待たない)
+        ms: u32,
+    },
+    
+    /// タイプ入力に使うツールを設定
+    SetTypeTool {
+        /// ツール: wtype, xdotool, ydotool, osascript (省略するかautoで自動)
+        tool: Option<String>,
+    },
+    
+    /// タイプ入力を許可するウィンドウタイトルを追加 (1つ以上あると、一致しないウィンドウではクリップボードにコピーするだけにする)
+    AddTypeAllowlist {
+        /// ウィンドウタイトル (部分一致、大文字小文字は無視)
+        title: String,
+    },
+    
+    /// タイプ入力を許可するウィンドウタイトルを削除
+    RemoveTypeAllowlist {
+        /// 削除するウィンドウタイトル
+        title: String,
+    },
+    
+    /// 言語を設定
+    SetLanguage {
+        /// 言語コード (例: ja, en、autoで自動検出)
+        lang: String,
+    },
+    
+    /// モデルを設定
+    SetModel {
+        /// モデル名 (例: gpt-4o-transcribe)
+        model: String,
+    },
+    
+    /// エンジンが失敗した場合に同じ録音を認識し直すフォールバックエンジンを設定 (省略すると無効)
+    SetFallbackEngine {
+        /// 音声エンジン: gpt-4o, whisper-1, groq, whisper-cpp, whisper-rs, azure
+        engine: Option<String>,
+        
+        /// Whisper.cppのパス (whisper-cppエンジン使用時)
+        #[arg(long)]
+        whisper_cpp_path: Option<PathBuf>,
+        
+        /// Whisper.cppのモデルパス (whisper-cpp・whisper-rsエンジン使用時)
+        #[arg(long)]
+        whisper_cpp_model: Option<PathBuf>,
+        
+        /// Azure OpenAIのエンドポイント (azureエンジン使用時)
+        #[arg(long)]
+        azure_endpoint: Option<String>,
+        
+        /// Azure OpenAIのデプロイ名 (azureエンジン使用時)
+        #[arg(long)]
+        azure_deployment: Option<String>,
+    },
+    
+    /// 無音除去を設定
+    SetRemoveSilence {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// 再生速度を設定
+    SetSpeedFactor {
+        /// 速度倍率 (0.25〜4.0、例: 1.0, 1.1, 1.5)
+        factor: f32,
+    },
+    
+    /// トグルモードで無音が続いた場合に自動停止するまでの秒数を設定
+    SetToggleSilence {
+        /// 秒数 (例: 10)
+        sec: u32,
+    },
+    
+    /// PTTモードで無音が続いた場合に録音を止めるまでの秒数を設定 (キーを離したイベントの取りこぼし対策、省略すると無効)
+    SetPttSilence {
+        /// 秒数 (デフォルト30)
+        sec: Option<u32>,
+    },
+    
+    /// トグルモードのノイズゲートを設定 (無効ならキーを押してから次に押すまでをすべて録音)
+    SetToggleNoiseGate {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+        
+        /// 有意な音声とみなす振幅の、閾値に対する倍率 (1.0〜20.0、デフォルト2.0)
+        #[arg(long)]
+        voice_ratio: Option<f32>,
+        
+        /// 有意な音声でなくても録音に含める最大振幅の下限 (デフォルト0.005)
+        #[arg(long)]
+        weak_floor: Option<f32>,
+    },
+    
+    /// 入力デバイスを設定 (省略するとデフォルトデバイス)
+    SetDevice {
+        /// デバイス名 (部分一致)
+        name: Option<String>,
+    },
+    
+    /// 録音中の途中経過を文字起こしする間隔を設定
+    SetStreamInterval {
+        /// 間隔秒数 (0で無効)
+        sec: u32,
+    },
+    
+    /// 文字起こしのタイムアウトを設定
+    SetTimeout {
+        /// タイムアウト秒数 (例: 60, 120)
+        sec: u64,
+    },
+    
+    /// APIへの1回のリクエストのタイムアウトを設定 (タイムアウト時はリトライ)
+    SetRequestTimeout {
+        /// タイムアウト秒数 (例: 30)
+        sec: u64,
+    },
+    
+    /// アップロードするWAVのビット深度を設定
+    SetBitDepth {
+        /// ビット深度: 16, 24, float
+        depth: String,
+    },
+    
+    /// 16・24ビットのWAVに変換する際にディザを加えるか設定 (小さな音の量子化ひずみを抑える)
+    SetDither {
+        /// 有効/無効
+        #[arg(default_value = "true", action = clap::ArgAction::Set)]
+        enable: bool,
+    },
+    
+    /// 無音除去のパラメータを設定 (指定した項目のみ変更)
+    SetSilenceParams {
+        /// 無音判定の閾値 (振幅)
+        #[arg(long)]
+        threshold: Option<f32>,
+        
+        /// これより短い音声区間をポップノイズとして捨てる (ミリ秒)
+        #[arg(long)]
+        min_segment_ms: Option<u32>,
+        
+        /// 音声区間の終わりとみなす無音の長さ (ミリ秒)
+        #[arg(long)]
+        trailing_silence_ms: Option<u32>,
+        
+        /// 音声区間の前後に残す余白 (ミリ秒)
+        #[arg(long)]
+        padding_ms: Option<u32>,
+    },
+    
+    /// 保存する録音で、音声区間の間の無音を取り除かずに残す上限を設定 (省略すると取り除く)
+    SetMaxTrimSilence {
+        /// 残す無音の上限 (ミリ秒)
+        ms: Option<u32>,
+    },
+    
+    /// API呼び出し・Webhook送信のリトライを設定 (429/5xxとタイムアウト時)
+    SetRetry {
+        /// 最大リトライ回数 (0でリトライしない)
+        #[arg(long)]
+        max: Option<u8>,
+        
+        /// リトライ間隔の初期値 (ミリ秒、リトライごとに倍になる)
+        #[arg(long)]
+        delay_ms: Option<u64>,
+    },
+    
+    /// Whisper.cppのスレッド数を設定 (省略するとデフォルト)
+    SetWhisperThreads {
+        /// スレッド数
+        threads: Option<u32>,
+    },
+    
+    /// Whisper.cppに追加で渡す引数を設定 (省略すると解除)
+    SetWhisperArgs {
+        /// 引数 (例: -ng --beam-size 5)
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    
+    /// 同時に実行する文字起こしの最大数を設定 (超えた分は順番待ち)
+    SetMaxConcurrent {
+        /// 最大数 (例: 2)
+        count: usize,
+    },
+    
+    /// 文字起こし待ちの録音と出力待ちの結果を溜めておける数を設定 (超えた録音は破棄して通知)
+    SetChannelCapacity {
+        /// 数 (デフォルト32)
+        capacity: usize,
+    },
+    
+    /// 連続モードで発話の間に出力する区切りを設定 (\n、\tが使える、省略すると区切らない)
+    SetUtteranceSeparator {
+        /// 区切り (デフォルトは改行)
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        separator: String,
+    },
+}
+
+fn main() -> Result<()> {
+    // 環境変数の読み込み
+    dotenv::dotenv().ok();
+    
+    // CLIの解析（ログレベルの決定に必要なため先に行う）
+    let mut cli = Cli::parse();
+    
+    // 補完スクリプトは標準出力にそのまま書き出すため、ロガーの初期化より前に処理する
+    if let Command::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "voilip", &mut std::io::stdout());
+        return Ok(());
+    }
+    
+    // ロガーの初期化（ダッシュボードの表示を崩さないよう、TUIモードではログを出力しない）
+    let log_level = resolve_log_level(cli.log_level.as_deref(), cli.verbose, cli.quiet)?;
+    if matches!(cli.command, Command::Start { tui: true, .. }) {
+        let subscriber = FmtSubscriber::builder()
+            .with_max_level(log_level)
+            .with_writer(std::io::sink)
+            .finish();
+        tracing::subscriber::set_global_default(subscriber)?;
+    } else {
+        // 標準出力は文字起こし結果の出力（--mode stdout）に使うため、ログは標準エラー出力に書く
+        let subscriber = FmtSubscriber::builder()
+            .with_max_level(log_level)
+            .with_writer(std::io::stderr)
+            .finish();
+        tracing::subscriber::set_global_default(subscriber)?;
+    }
+    
+    // システム情報のログ出力
+    log_system_info();
+    
+    if let Some(path) = cli.config {
+        Config::set_config_path_override(path)?;
+    }
+    if let Some(profile) = cli.profile.as_deref() {
+        Config::set_profile_override(profile)?;
+    }
+    
+    // 環境変数を変更するため、tokioのランタイムがスレッドを起動する前に行う
+    prepare_input_device(&mut cli.command)?;
+    
+    tokio::runtime::Runtime::new()?.block_on(run(cli.command))
+}
+
+/// 録音するデバイスがモニターソースの場合に、録音できるよう準備する
+///
+/// `--loopback`の場合は既定の出力先のモニターソースを`device`に入れる。
+/// 録音しないコマンドでは何もしない
+fn prepare_input_device(command: &mut Command) -> Result<()> {
+    let device = match command {
+        Command::Start { device, loopback, .. } => {
+            if *loopback {
+                let device_name = audio::default_loopback_device()?;
+                info!("ループバックデバイスを録音します: {}", device_name);
+                *device = Some(device_name);
+            }
+            device.clone()
+        }
+        Command::Meter { device } | Command::Calibrate { device, .. } => device.clone(),
+        Command::Doctor => None,
+        _ => return Ok(()),
+    };
+    
+    // 起動オプションで指定されていなければ設定ファイルのデバイスを使う
+    if let Some(name) = device.or_else(|| Config::load().ok().and_then(|config| config.input_device)) {
+        audio::prepare_monitor_source(&name);
+    }
+    Ok(())
+}
+
+/// サブコマンドを実行
+async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Start { 
+            mode, 
+            output_path,
+            webhook_url,
+            lang, 
+            ptt, 
+            toggle,
+            hybrid,
+            start_key,
+            stop_key,
+            silence_threshold,
+            silence_duration,
+            engine, 
+            whisper_cpp_path, 
+            whisper_cpp_model,
+            whisper_threads,
+            whisper_server_port,
+            whisper_args,
+            azure_endpoint,
+            azure_deployment,
+            model,
+            continuous,
+            dry_run,
+            json_lines,
+            type_tool,
+            tui: use_tui,
+            once,
+            once_timeout,
+            stream_interval,
+            control_socket,
+            audio_log,
+            device,
+            loopback: _,
+            api_base,
+            proxy,
+            prompt,
+            temperature,
+            translate,
+        } => {
+            if use_tui && !tui::AVAILABLE {
+                return Err(anyhow::anyhow!("--tuiを使うには、tuiフィーチャーを有効にしてビルドする必要があります (cargo build --features tui)"));
+            }
             
-            // 音声キャプチャの停止
-            audio_capture.stop();
+            // 設定の読み込み
+            let mut config = Config::new(&CliOverrides {
+                mode,
+                output_path,
+                webhook_url,
+                lang,
+                ptt,
+                engine,
+                whisper_cpp_path,
+                whisper_cpp_model,
+                toggle_key: toggle,
+                hybrid_key: hybrid,
+                separate_keys: start_key.zip(stop_key),
+                model,
+                silence_threshold,
+                silence_duration_ms: silence_duration,
+                azure_endpoint,
+                azure_deployment,
+            })?;
+            
+            // トグルモードの場合、録音の最大持続時間を長く設定
+            if let config::RecordingMode::Toggle { .. } = config.recording_mode {
+                config.max_recording_duration_sec = Some(300); // 5分
+            }
+            
+            // 連続モードは無音検知モードでのみ有効
+            if continuous {
+                if let config::RecordingMode::VoiceActivity { .. } = config.recording_mode {
+                    config.continuous = true;
+                    info!("連続ディクテーションモードで起動します (Ctrl+Cで終了)");
+                } else {
+                    warn!("連続モードは無音検知モードでのみ使用できます。--continuousは無視されます");
+                }
+            }
+            
+            if json_lines {
+                let mut found = false;
+                for mode in config.output_modes.iter_mut() {
+                    if let OutputMode::Stdout { json_lines } = mode {
+                        *json_lines = true;
+                        found = true;
+                    }
+                }
+                if !found {
+                    warn!("--json-linesは出力モードがstdoutの場合のみ有効です。無視されます");
+                }
+            }
+            
+            if let Some(tool) = type_tool {
+                config.type_tool = Some(tool.parse().map_err(|e: String| anyhow::anyhow!("{}", e))?);
+            }
+            
+            if once {
+                config.once = true;
+            }
+            
+            if dry_run {
+                config.dry_run = true;
+                info!("ドライランモードで起動します（文字起こし結果は出力先に送りません）");
+            }
+            
+            if let Some(interval) = stream_interval {
+                config.streaming_interval_sec = Some(interval);
+            }
+            
+            if let Some(device_name) = device {
+                config.input_device = Some(device_name);
+            }
+            
+            if let Some(path) = control_socket {
+                config.control_socket = Some(path);
+            }
+            
+            if let Some(path) = audio_log {
+                config.audio_log = Some(path);
+            }
+            
+            if let Some(threads) = whisper_threads {
+                config.whisper_cpp_threads = Some(threads).filter(|t| *t > 0);
+            }
+            
+            if let Some(server_port) = whisper_server_port {
+                match &mut config.transcription_engine {
+                    TranscriptionEngine::WhisperCppServer { port, .. } => *port = server_port,
+                    _ => warn!("--whisper-server-portはwhisper-serverエンジンでのみ使われます"),
+                }
+            }
+            
+            // コマンドラインの引数は設定ファイルの引数の後に追加
+            if !whisper_args.is_empty() {
+                config::validate_whisper_cpp_args(&whisper_args)?;
+                config.whisper_cpp_args.extend(whisper_args);
+            }
+            
+            if let Some(base_url) = api_base {
+                config.api_base_url = Some(base_url.trim_end_matches('/').to_string());
+            }
+            
+            if let Some(proxy) = proxy {
+                config.proxy = Some(proxy).filter(|p| !p.is_empty());
+            }
+            
+            if let Some(prompt) = prompt {
+                config.transcription_prompt = Some(prompt).filter(|p| !p.is_empty());
+            }
+            
+            if let Some(temperature) = temperature {
+                if !(0.0..=1.0).contains(&temperature) {
+                    return Err(anyhow::anyhow!("temperatureは0.0〜1.0の範囲で指定してください"));
+                }
+                config.temperature = Some(temperature);
+            }
+            
+            if translate {
+                config.translate = true;
+            }
+            
+            info!("音声認識を開始します: 言語={}, エンジン={}, モデル={}", 
+                config.language, 
+                config.transcription_engine.name(),
+                config.model);
+            
+            // アプリケーション状態の初期化
+            let app_state = Arc::new(AppState::new());
+            
+            // シグナルハンドラのセットアップ
+            setup_signal_handler(app_state.clone()).await?;
+            
+            // ダッシュボードは録音・文字起こしの状態の変化の通知で表示を更新する
+            let (events_tx, events_rx) = if use_tui {
+                let (tx, rx) = mpsc::channel(TUI_EVENT_CAPACITY);
+                (Some(tx), Some(rx))
+            } else {
+                (None, None)
+            };
+            
+            // 音声キャプチャと文字起こしの開始
+            let (mut session, result_rx) = VoilipSession::start_with_events(config.clone(), events_tx).map_err(handle_capture_error)?;
+            let mut output_manager = OutputManager::new(config.clone(), result_rx, session.recording_epoch());
+            output_manager.set_stats(session.stats());
+            let stats = session.stats();
+            let mut once_error = None;
+            
+            // ダウンミックスしない場合、実際のチャンネル数が設定と異なるとエンコード結果が崩れる
+            if let Some(format) = session.input_format().filter(|_| config.preserve_channels) {
+                if format.channels != config.channels {
+                    warn!("入力チャンネル数が設定と異なります: {}ch (設定: {}ch)",
+                        format.channels, config.channels);
+                }
+            }
+            
+            let output_future = tokio::spawn(async move {
+                if let Err(e) = output_manager.run().await {
+                    error!("出力処理エラー: {}", e);
+                }
+            });
+            
+            // 外部のツールから録音を操作できるよう、制御ソケットで待ち受ける
+            let control = match &config.control_socket {
+                Some(path) => Some(ControlSocket::bind(path, session.audio_buffer(), app_state.clone())?),
+                None => None,
+            };
+            
+            // アプリケーションのメインループ
+            if let Some(events_rx) = events_rx {
+                if let Err(e) = tui::run_dashboard(&config, &mut session, events_rx, &app_state).await {
+                    // ダッシュボードの表示中はログを出力しないため標準エラー出力に表示する
+                    eprintln!("エラー: {}", e);
+                }
+            } else {
+                let once_timeout = std::time::Duration::from_secs(once_timeout.unwrap_or(DEFAULT_ONCE_TIMEOUT_SEC));
+                let mut heard_speech = false;
+                if once {
+                    info!("1回分の発話を待っています ({}秒以内に話してください)", once_timeout.as_secs());
+                }
+                
+                while app_state.is_running() {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    // 入力デバイスが外れた場合などは音声ストリームを作り直し、復旧しなければ終了する
+                    if let Err(e) = session.check_stream() {
+                        error!("{}", e);
+                        app_state.set_running(false);
+                    }
+                    
+                    // --onceでは最初の結果の出力か失敗、または発話がないまま時間切れになったら終了する
+                    if once {
+                        let summary = stats.summary();
+                        heard_speech |= session.is_recording();
+                        if summary.utterances > 0 {
+                            info!("結果を出力したため終了します");
+                            app_state.set_running(false);
+                        } else if summary.errors > 0 {
+                            once_error = Some(anyhow::anyhow!("文字起こしに失敗しました"));
+                            app_state.set_running(false);
+                        } else if !heard_speech && app_state.elapsed() >= once_timeout {
+                            once_error = Some(anyhow::anyhow!("{}秒以内に発話がありませんでした", once_timeout.as_secs()));
+                            app_state.set_running(false);
+                        }
+                    }
+                }
+            }
             
-            // タスクの終了を待機
-            let _ = transcriber_future.await;
+            // 終了処理
+            info!("アプリケーションを終了しています...");
+            
+            if let Some(control) = control {
+                control.stop();
+            }
+            
+            // 音声キャプチャを停止し、タスクの終了を待機
+            session.stop().await;
             let _ = output_future.await;
             
+            // 標準出力は文字起こし結果の出力先になりうるため、集計は標準エラー出力に表示する
+            eprintln!("{}", stats.summary().describe(app_state.elapsed()));
+            if let Some(e) = once_error {
+                return Err(e);
+            }
             info!("正常に終了しました");
             Ok(())
         },
-        Command::Test { test_file, model } => {
+        Command::Test { test_file, model, prompt, timestamps, translate, srt, vtt, format } => {
             info!("テストモード: ファイル={}", test_file.display());
             
             // 設定ファイルから読み込み
-            let config = Config::load().unwrap_or_default();
+            let mut config = Config::load().unwrap_or_default();
+            
+            // プロンプトはコマンドラインで指定されたものを優先
+            if let Some(prompt) = prompt {
+                config.transcription_prompt = Some(prompt).filter(|p| !p.is_empty());
+            }
+            
+            // 字幕の出力にはセグメントのタイムスタンプが必要
+            let subtitle_output = matches!(format.as_deref(), Some("srt" | "vtt"));
+            if timestamps || srt.is_some() || vtt.is_some() || subtitle_output {
+                config.timestamps = true;
+            }
+            
+            if translate {
+                config.translate = true;
+            }
             
             // モデルはコマンドラインで指定されたものを優先
-            let model_name = model.unwrap_or(config.model.clone());
-            info!("使用モデル: {}", model_name);
+            if let Some(model) = model {
+                config::validate_model(&model)?;
+                config.model = model;
+            }
+            info!("使用エンジン: {} (モデル: {})", config.transcription_engine.name(), config.model);
+            
+            // 任意の形式のファイルをデコードし、設定のレートのモノラル音声に変換
+            let samples = audio::load_audio_file(&test_file, config.sample_rate)?;
+            
+            // チャネルの設定 (ダミー)
+            let (_audio_tx, audio_rx) = mpsc::channel::<AudioChunk>(1);
+            let (result_tx, _) = mpsc::channel::<TranscriptionResult>(1);
+            
+            // Transcriberの作成
+            let transcriber = Transcriber::new(config, audio_rx, result_tx, Arc::new(AtomicU64::new(0)));
+            
+            // テスト実行
+            match transcriber.transcribe_samples(&samples).await {
+                Ok(result) => {
+                    info!("文字起こし結果:");
+                    info!("言語: {}", result.language);
+                    info!("長さ: {:.2}秒", result.duration_sec);
+                    info!("テキスト: {}", result.text);
+                    if let Some(engine) = &result.fallback_engine {
+                        info!("フォールバック: {}で認識しました", engine);
+                    }
+                    for segment in &result.segments {
+                        info!("[{:>7.2}s - {:>7.2}s] {}", segment.start, segment.end, segment.text);
+                    }
+                    
+                    let subtitles = [(srt, SubtitleFormat::Srt), (vtt, SubtitleFormat::Vtt)];
+                    for (path, format) in subtitles {
+                        let Some(path) = path else { continue };
+                        if result.segments.is_empty() {
+                            return Err(anyhow::anyhow!("タイムスタンプ付きのセグメントが得られなかったため、字幕ファイルを出力できません（タイムスタンプに対応したモデルを使用してください）"));
+                        }
+                        std::fs::write(&path, format_subtitles(&result.segments, format))?;
+                        info!("字幕ファイルを出力しました: {}", path.display());
+                    }
+                    
+                    // スクリプトから使えるよう、指定した形式の結果だけを標準出力に書き出す
+                    if let Some(format) = format.as_deref() {
+                        print!("{}", format_test_result(&result, format)?);
+                    }
+                    Ok(())
+                }
+                Err(e) => {
+                    error!("文字起こしエラー: {}", e);
+                    Err(e)
+                }
+            }
+        },
+        Command::Retry { engine, model } => {
+            let config = Config::load().unwrap_or_default();
+            let dir = config.save_recordings.clone().ok_or_else(|| anyhow::anyhow!(
+                "録音が保存されていないため再文字起こしできません。voilip config set-save-recordings <ディレクトリ> で録音の保存を有効にしてください"
+            ))?;
+            let recording = latest_recording(&dir)?
+                .ok_or_else(|| anyhow::anyhow!("保存された録音がありません: {}", dir.display()))?;
+            
+            // エンジン・モデルの指定は設定の複製にだけ適用する（指定がなければ設定のエンジンで文字起こしする）
+            let overrides = CliOverrides { engine, model, ..Default::default() };
+            let result = retranscribe_recording(&config, &recording, &overrides).await
+                .inspect_err(|e| error!("文字起こしエラー: {}", e))?;
+            println!("{}", result.text);
+            
+            // 前回の結果を置き換える（録音の横の文字起こし結果とクリップボード）
+            std::fs::write(recording.with_extension("txt"), &result.text)?;
+            if config.output_modes.iter().any(|mode| matches!(mode, OutputMode::Clipboard | OutputMode::Both)) {
+                set_clipboard_text(&result.text)?;
+                info!("クリップボードにコピーしました");
+            }
+            if config.save_history {
+                if let Err(e) = history::append_history(&result) {
+                    warn!("履歴の保存エラー: {}", e);
+                }
+            }
+            Ok(())
+        },
+        Command::Bench { test_file, iterations, models } => {
+            let config = Config::load().unwrap_or_default();
+            let models = if models.is_empty() {
+                vec![config.model.clone()]
+            } else {
+                models
+            };
+            bench::run_bench(config, &test_file, &models, iterations).await
+        },
+        Command::Devices => {
+            println!("{}", describe_input_devices()?);
+            Ok(())
+        },
+        Command::Doctor => {
+            if !doctor::run_doctor() {
+                std::process::exit(1);
+            }
+            Ok(())
+        },
+        Command::Verify { models } => {
+            let config = Config::load()?;
+            verify::run_verify(&config, models)
+        },
+        Command::Profile { name } => {
+            match name {
+                Some(name) => {
+                    Config::switch_profile(&name)?;
+                    println!("プロファイルを切り替えました: {}", name);
+                }
+                None => println!("現在のプロファイル: {}", Config::active_profile()?),
+            }
+            Ok(())
+        },
+        Command::Meter { device } => {
+            let mut config = Config::load().unwrap_or_default();
+            if let Some(device_name) = device {
+                config.input_device = Some(device_name);
+            }
             
-            // チャネルの設定 (ダミー)
-            let (_audio_tx, audio_rx) = mpsc::channel::<Vec<f32>>(1);
-            let (result_tx, _) = mpsc::channel::<TranscriptionResult>(1);
+            // 無音検知モードの閾値をマーカーとして表示
+            let threshold = match config.recording_mode {
+                config::RecordingMode::VoiceActivity { silence_threshold, .. } => silence_threshold,
+                _ => audio::SILENCE_THRESHOLD,
+            };
             
-            // Transcriberの作成
-            let transcriber = Transcriber::new(config, audio_rx, result_tx);
+            let (audio_tx, _audio_rx) = mpsc::channel::<AudioChunk>(1);
+            let mut audio_capture = AudioCapture::new(config, audio_tx);
+            audio_capture.start_meter().map_err(handle_capture_error)?;
             
-            // テスト実行
-            match transcriber.transcribe_file(&test_file, &model_name).await {
-                Ok(result) => {
-                    info!("文字起こし結果:");
-                    info!("言語: {}", result.language);
-                    info!("長さ: {:.2}秒", result.duration_sec);
-                    info!("テキスト: {}", result.text);
-                    Ok(())
-                }
-                Err(e) => {
-                    error!("文字起こしエラー: {}", e);
-                    Err(e)
+            println!("入力レベルメーター (閾値: {:.3}、Ctrl+Cで終了)", threshold);
+            // 1秒に5回更新
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(200));
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => break,
+                    _ = interval.tick() => {
+                        let level = audio_capture.level_meter().take();
+                        print!("\r{}", render_level_meter(level.rms, level.peak, threshold, 40));
+                        std::io::stdout().flush()?;
+                    }
                 }
             }
+            println!();
+            
+            audio_capture.stop();
+            Ok(())
+        },
+        Command::Calibrate { device, save } => {
+            let mut config = Config::load().unwrap_or_default();
+            if let Some(device_name) = device {
+                config.input_device = Some(device_name);
+            }
+            calibrate::run_calibration(config, save).await.map_err(handle_capture_error)
+        },
+        Command::History { limit } => {
+            let entries = history::read_history(limit)?;
+            if entries.is_empty() {
+                println!("履歴はありません ({})", history::history_path()?.display());
+            }
+            for entry in entries {
+                println!("[{}] ({}, {:.1}秒) {}", entry.timestamp, entry.language, entry.duration_sec, entry.text);
+            }
+            Ok(())
+        },
+        Command::ClearHistory => {
+            if history::clear_history()? {
+                println!("履歴を消去しました ({})", history::history_path()?.display());
+            } else {
+                println!("履歴はありません ({})", history::history_path()?.display());
+            }
+            Ok(())
         },
+        // ロガーの初期化前に処理済み
+        Command::Completions { .. } => Ok(()),
         Command::Config { action } => {
             match action {
                 ConfigAction::Show => {
@@ -285,10 +1465,198 @@ async fn main() -> Result<()> {
                     println!("{}", config.display());
                     Ok(())
                 },
+                ConfigAction::ListProfiles => {
+                    let active = Config::active_profile()?;
+                    for profile in Config::list_profiles()? {
+                        let marker = if profile == active { "*" } else { " " };
+                        println!("{} {}", marker, profile);
+                    }
+                    Ok(())
+                },
+                ConfigAction::CopyProfile { from, to } => {
+                    Config::copy_profile(&from, &to)?;
+                    println!("プロファイル {} を {} に複製しました", from, to);
+                    Ok(())
+                },
+                ConfigAction::Export { path, include_api_keys } => {
+                    let config = Config::load()?;
+                    config.export(&path, include_api_keys)?;
+                    println!("設定を書き出しました: {} (APIキー: {})", path.display(), if include_api_keys { "含む" } else { "含まない" });
+                    Ok(())
+                },
+                ConfigAction::Import { path } => {
+                    let config = Config::load()?;
+                    let (_, report) = config.import(&path)?;
+                    if report.version > config::CONFIG_VERSION as u64 {
+                        println!("警告: 新しいバージョンのvoilipで書き出された設定です (形式のバージョン: {}, 対応: {})",
+                            report.version, config::CONFIG_VERSION);
+                    }
+                    if !report.unknown_fields.is_empty() {
+                        println!("このバージョンにないため無視した項目: {}", report.unknown_fields.join(", "));
+                    }
+                    if !report.invalid_fields.is_empty() {
+                        println!("読み込めないためデフォルト値にした項目: {}", report.invalid_fields.join(", "));
+                    }
+                    if report.api_key_imported {
+                        println!("APIキーをキーリングに保存しました");
+                    }
+                    println!("設定を読み込みました: {} (プロファイル: {})", path.display(), Config::active_profile()?);
+                    Ok(())
+                },
                 ConfigAction::SetApiKey { api_key } => {
                     let mut config = Config::load()?;
                     config.set_api_key(&api_key)?;
-                    println!("APIキーを設定しました");
+                    println!("APIキーをキーリングに保存しました");
+                    Ok(())
+                },
+                ConfigAction::AddApiKey { api_key } => {
+                    let mut config = Config::load()?;
+                    config.add_extra_api_key(&api_key)?;
+                    println!("追加のAPIキーを登録しました ({}個)", config.extra_openai_api_keys.len());
+                    Ok(())
+                },
+                ConfigAction::ClearExtraApiKeys => {
+                    let mut config = Config::load()?;
+                    config.clear_extra_api_keys()?;
+                    println!("追加のAPIキーを削除しました");
+                    Ok(())
+                },
+                ConfigAction::MigrateKey => {
+                    let mut config = Config::load()?;
+                    if config.migrate_api_key()? {
+                        println!("APIキーをキーリングに移行し、設定ファイルから削除しました");
+                    } else {
+                        println!("設定ファイルに移行が必要なAPIキーはありません");
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetGroqApiKey { api_key } => {
+                    let mut config = Config::load()?;
+                    config.set_groq_api_key(&api_key)?;
+                    println!("Groq APIキーをキーリングに保存しました");
+                    Ok(())
+                },
+                ConfigAction::SetPrompt { prompt } => {
+                    let mut config = Config::load()?;
+                    config.set_transcription_prompt(prompt.as_deref())?;
+                    match &config.transcription_prompt {
+                        Some(prompt) => println!("プロンプトを設定しました: {}", prompt),
+                        None => println!("プロンプトを解除しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetDefaultPrompt { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_use_default_prompt(enable)?;
+                    println!("既定のプロンプトを{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetPreserveChannels { enabled } => {
+                    let mut config = Config::load()?;
+                    config.set_preserve_channels(enabled)?;
+                    println!("チャンネル保持を{}にしました", if enabled { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetVadAggressiveness { level } => {
+                    let mut config = Config::load()?;
+                    config.set_vad_aggressiveness(level)?;
+                    println!("VADの積極度を{}に設定しました", level);
+                    Ok(())
+                },
+                ConfigAction::SetMinRecording { ms } => {
+                    let mut config = Config::load()?;
+                    config.set_min_recording_duration(ms)?;
+                    println!("最短録音時間を{}ミリ秒に設定しました", ms);
+                    Ok(())
+                },
+                ConfigAction::SetPreRoll { ms } => {
+                    let mut config = Config::load()?;
+                    config.set_pre_roll(ms)?;
+                    println!("プリロールを{}ミリ秒に設定しました", ms);
+                    Ok(())
+                },
+                ConfigAction::SetLeadingTrim { guard_ms } => {
+                    let mut config = Config::load()?;
+                    config.set_leading_silence_guard(guard_ms)?;
+                    match guard_ms {
+                        Some(ms) => println!("先頭の無音を切り詰めるように設定しました (手前に残す長さ: {}ミリ秒)", ms),
+                        None => println!("先頭の無音を切り詰めないように設定しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetNormalize { enable, target } => {
+                    let mut config = Config::load()?;
+                    config.set_normalize(enable, target)?;
+                    if enable {
+                        println!("ピーク正規化を有効にしました (目標: {:.2})", config.normalize_target_peak);
+                    } else {
+                        println!("ピーク正規化を無効にしました");
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetInputGain { gain } => {
+                    let mut config = Config::load()?;
+                    config.set_input_gain(gain)?;
+                    println!("入力ゲインを{:.2}倍に設定しました", gain);
+                    Ok(())
+                },
+                ConfigAction::SetNoiseGate { strength } => {
+                    let mut config = Config::load()?;
+                    config.set_noise_gate_strength(strength)?;
+                    if strength > 0.0 {
+                        println!("ノイズゲートの強さを{:.2}に設定しました", strength);
+                    } else {
+                        println!("ノイズゲートを無効にしました");
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetTimestamps { enabled } => {
+                    let mut config = Config::load()?;
+                    config.set_timestamps(enabled)?;
+                    println!("タイムスタンプ取得を{}にしました", if enabled { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetTranslate { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_translate(enable)?;
+                    println!("英語への翻訳を{}にしました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetTemperature { temperature } => {
+                    let mut config = Config::load()?;
+                    config.set_temperature(temperature)?;
+                    match temperature {
+                        Some(t) => println!("temperatureを{:.2}に設定しました", t),
+                        None => println!("temperatureをデフォルトに戻しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::AddHeader { name, value, override_auth } => {
+                    let mut config = Config::load()?;
+                    config.add_extra_header(&name, &value, override_auth)?;
+                    println!("HTTPヘッダーを追加しました: {}", name);
+                    Ok(())
+                },
+                ConfigAction::RemoveHeader { name } => {
+                    let mut config = Config::load()?;
+                    config.remove_extra_header(&name)?;
+                    println!("HTTPヘッダーを削除しました: {}", name);
+                    Ok(())
+                },
+                ConfigAction::SetProxy { url } => {
+                    let mut config = Config::load()?;
+                    config.set_proxy(url.as_deref())?;
+                    match config.proxy_url() {
+                        Some(_) if config.proxy.is_none() => println!("プロキシの設定を解除しました（環境変数のプロキシを使用します）"),
+                        Some(_) => println!("プロキシを設定しました"),
+                        None => println!("プロキシの設定を解除しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetApiBase { url } => {
+                    let mut config = Config::load()?;
+                    config.set_api_base_url(url.as_deref())?;
+                    println!("APIベースURLを設定しました: {}", config.transcription_url());
                     Ok(())
                 },
                 ConfigAction::SetToggleKey { key } => {
@@ -297,12 +1665,241 @@ async fn main() -> Result<()> {
                     println!("トグルキーを設定しました: {}", key);
                     Ok(())
                 },
+                ConfigAction::SetPauseKey { key } => {
+                    let mut config = Config::load()?;
+                    config.set_pause_key(key.as_deref())?;
+                    match key {
+                        Some(key) => println!("一時停止キーを設定しました: {}", key),
+                        None => println!("一時停止キーを解除しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetLanguageKey { key, languages } => {
+                    let mut config = Config::load()?;
+                    config.set_language_cycle(key.as_deref(), languages)?;
+                    match key {
+                        Some(key) => println!("言語切り替えキーを設定しました: {} ({})", key, config.language_cycle.join(" → ")),
+                        None => println!("言語切り替えキーを解除しました"),
+                    }
+                    Ok(())
+                },
                 ConfigAction::SetPttKey { key } => {
                     let mut config = Config::load()?;
                     config.set_ptt_key(&key)?;
                     println!("PTTキーを設定しました: {}", key);
                     Ok(())
                 },
+                ConfigAction::SetHybridKey { key, tap_threshold_ms } => {
+                    let mut config = Config::load()?;
+                    config.set_hybrid_key(&key, tap_threshold_ms)?;
+                    println!("ハイブリッドモードのキーを設定しました: {} (タップ判定: {}ms以下)", key, tap_threshold_ms);
+                    Ok(())
+                },
+                ConfigAction::SetSeparateKeys { start_key, stop_key } => {
+                    let mut config = Config::load()?;
+                    config.set_separate_keys(&start_key, &stop_key)?;
+                    println!("開始キーと停止キーを設定しました: {} / {}", start_key, stop_key);
+                    Ok(())
+                },
+                ConfigAction::SetVoiceActivity { threshold, duration_ms } => {
+                    let mut config = Config::load()?;
+                    config.set_voice_activity(threshold, duration_ms)?;
+                    println!("無音検知モードを設定しました (閾値: {}, 無音時間: {}ms)", threshold, duration_ms);
+                    Ok(())
+                },
+                ConfigAction::SetEndSilence { ms } => {
+                    let mut config = Config::load()?;
+                    config.set_end_silence(ms)?;
+                    match ms {
+                        Some(ms) => println!("文章モードを有効にしました (確定までの無音: {}ms)", ms),
+                        None => println!("文章モードを無効にしました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetOutputMode { mode, path, url } => {
+                    let output_modes = OutputMode::parse_list(&mode, path.as_ref(), url.as_deref())
+                        .map_err(|e| anyhow::anyhow!("{}", e))?;
+                    let mut config = Config::load()?;
+                    config.set_output_modes(output_modes)?;
+                    println!("出力モードを設定しました: {}", mode);
+                    Ok(())
+                },
+                ConfigAction::SetWebhook { url } => {
+                    let mut config = Config::load()?;
+                    config.set_webhook(&url)?;
+                    println!("Webhook出力を設定しました: {}", url);
+                    Ok(())
+                },
+                ConfigAction::SetOutputTimestamp { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_output_timestamp(enable)?;
+                    println!("タイムスタンプ付与を{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetPrefix { prefix } => {
+                    let mut config = Config::load()?;
+                    config.set_output_prefix(&prefix)?;
+                    println!("前置文字列を設定しました: {:?}", prefix);
+                    Ok(())
+                },
+                ConfigAction::SetSuffix { suffix } => {
+                    let mut config = Config::load()?;
+                    config.set_output_suffix(&suffix)?;
+                    println!("後置文字列を設定しました: {:?}", suffix);
+                    Ok(())
+                },
+                ConfigAction::SetModeAffix { mode, prefix, suffix } => {
+                    let mut config = Config::load()?;
+                    config.set_mode_affix(&mode, prefix.as_deref(), suffix.as_deref())?;
+                    if prefix.is_none() && suffix.is_none() {
+                        println!("出力モード{}の前置・後置文字列を共通の設定に戻しました", mode);
+                    } else {
+                        println!("出力モード{}の前置・後置文字列を設定しました: {:?} / {:?}", mode, prefix, suffix);
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetJaNormalize { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_ja_normalize(enable)?;
+                    println!("日本語の表記の整形を{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetTextCase { case } => {
+                    let text_case: config::TextCase = case.parse()
+                        .map_err(|e| anyhow::anyhow!("{}", e))?;
+                    let mut config = Config::load()?;
+                    config.set_text_case(text_case)?;
+                    println!("大文字・小文字の整え方を{}に設定しました", text_case.name());
+                    Ok(())
+                },
+                ConfigAction::AddReplacement { from, to, ignore_case } => {
+                    let mut config = Config::load()?;
+                    config.add_replacement(&from, &to, ignore_case)?;
+                    println!("置換ルールを追加しました: {} → {}", from, to);
+                    Ok(())
+                },
+                ConfigAction::RemoveReplacement { from } => {
+                    let mut config = Config::load()?;
+                    config.remove_replacement(&from)?;
+                    println!("置換ルールを削除しました: {}", from);
+                    Ok(())
+                },
+                ConfigAction::AddBlocklist { text } => {
+                    let mut config = Config::load()?;
+                    config.add_blocklist(&text)?;
+                    println!("出力しない定型文を追加しました: {}", text);
+                    Ok(())
+                },
+                ConfigAction::RemoveBlocklist { text } => {
+                    let mut config = Config::load()?;
+                    config.remove_blocklist(&text)?;
+                    println!("出力しない定型文を削除しました: {}", text);
+                    Ok(())
+                },
+                ConfigAction::SetHallucinationFilter { min_duration, max_no_speech_prob } => {
+                    let mut config = Config::load()?;
+                    config.set_hallucination_filter(min_duration, max_no_speech_prob)?;
+                    println!("幻覚フィルタを設定しました (最短: {:.2}秒, 無音確率の上限: {:.2})",
+                        config.min_output_duration_sec, config.max_no_speech_prob);
+                    Ok(())
+                },
+                ConfigAction::SetSaveRecordings { dir } => {
+                    let mut config = Config::load()?;
+                    config.set_save_recordings(dir.clone())?;
+                    match dir {
+                        Some(dir) => println!("録音の保存先を設定しました: {}", dir.display()),
+                        None => println!("録音の保存を無効にしました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetControlSocket { path } => {
+                    let mut config = Config::load()?;
+                    config.set_control_socket(path.clone())?;
+                    match path {
+                        Some(path) => println!("制御ソケットを設定しました: {}", path.display()),
+                        None => println!("制御ソケットを無効にしました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetMaxApiDuration { sec } => {
+                    let mut config = Config::load()?;
+                    config.set_max_api_duration(sec)?;
+                    match sec {
+                        Some(sec) => println!("1回に送る音声の最大の長さを{}秒に設定しました", sec),
+                        None => println!("1回に送る音声の最大の長さの制限を解除しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetMaxRecordings { count } => {
+                    let mut config = Config::load()?;
+                    config.set_max_saved_recordings(count)?;
+                    match count {
+                        Some(count) => println!("保存する録音の上限を{}件に設定しました", count),
+                        None => println!("保存する録音の上限を解除しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::Reset { wipe_api_key } => {
+                    let config = Config::load()?;
+                    config.reset(wipe_api_key)?;
+                    if wipe_api_key {
+                        println!("設定をデフォルトに戻し、キーリングのAPIキーを削除しました");
+                    } else {
+                        println!("設定をデフォルトに戻しました (APIキーは引き継いでいます)");
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetNotifications { level } => {
+                    let level: Option<config::NotificationLevel> = level.as_deref()
+                        .map(str::parse)
+                        .transpose()
+                        .map_err(|e: String| anyhow::anyhow!("{}", e))?;
+                    let mut config = Config::load()?;
+                    config.set_notifications(level)?;
+                    println!("通知を{}に設定しました", config.notification_level().name());
+                    Ok(())
+                },
+                ConfigAction::SetSaveHistory { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_save_history(enable)?;
+                    println!("履歴の保存を{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetRestoreClipboard { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_restore_clipboard(enable)?;
+                    println!("クリップボード復元を{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetTypeDelay { ms } => {
+                    let mut config = Config::load()?;
+                    config.set_type_delay(ms)?;
+                    println!("タイプ入力の文字間隔を{}ミリ秒に設定しました", ms);
+                    Ok(())
+                },
+                ConfigAction::SetTypeTool { tool } => {
+                    let tool: Option<config::TypeTool> = tool.as_deref()
+                        .filter(|tool| !tool.eq_ignore_ascii_case("auto"))
+                        .map(str::parse)
+                        .transpose()
+                        .map_err(|e: String| anyhow::anyhow!("{}", e))?;
+                    let mut config = Config::load()?;
+                    config.set_type_tool(tool)?;
+                    println!("タイプ入力ツールを設定しました: {}", tool.map_or("自動", |tool| tool.command()));
+                    Ok(())
+                },
+                ConfigAction::AddTypeAllowlist { title } => {
+                    let mut config = Config::load()?;
+                    config.add_type_allowlist(&title)?;
+                    println!("タイプ入力を許可するウィンドウを追加しました: {}", title);
+                    Ok(())
+                },
+                ConfigAction::RemoveTypeAllowlist { title } => {
+                    let mut config = Config::load()?;
+                    config.remove_type_allowlist(&title)?;
+                    println!("タイプ入力を許可するウィンドウを削除しました: {}", title);
+                    Ok(())
+                },
                 ConfigAction::SetLanguage { lang } => {
                     let mut config = Config::load()?;
                     config.set_language(&lang)?;
@@ -315,6 +1912,19 @@ async fn main() -> Result<()> {
                     println!("モデルを設定しました: {}", model);
                     Ok(())
                 },
+                ConfigAction::SetFallbackEngine { engine, whisper_cpp_path, whisper_cpp_model, azure_endpoint, azure_deployment } => {
+                    let mut config = Config::load()?;
+                    let engine = engine.map(|engine| TranscriptionEngine::from_options(
+                        &engine, whisper_cpp_path.as_ref(), whisper_cpp_model.as_ref(),
+                        azure_endpoint.as_deref(), azure_deployment.as_deref(), &TranscriptionEngine::GPT4o,
+                    )).transpose()?;
+                    config.set_fallback_engine(engine)?;
+                    match &config.fallback_engine {
+                        Some(engine) => println!("フォールバックエンジンを設定しました: {}", engine.name()),
+                        None => println!("フォールバックエンジンを無効にしました"),
+                    }
+                    Ok(())
+                },
                 ConfigAction::SetRemoveSilence { enable } => {
                     let mut config = Config::load()?;
                     config.set_remove_silence(enable)?;
@@ -327,7 +1937,175 @@ async fn main() -> Result<()> {
                     println!("再生速度を{:.1}倍に設定しました", factor);
                     Ok(())
                 },
+                ConfigAction::SetToggleSilence { sec } => {
+                    let mut config = Config::load()?;
+                    config.set_toggle_silence_threshold(sec)?;
+                    println!("トグルモードの無音自動停止を{}秒に設定しました", sec);
+                    Ok(())
+                },
+                ConfigAction::SetPttSilence { sec } => {
+                    let mut config = Config::load()?;
+                    config.set_ptt_silence_timeout(sec)?;
+                    match sec {
+                        Some(sec) => println!("PTTモードの無音自動停止を{}秒に設定しました", sec),
+                        None => println!("PTTモードの無音自動停止を無効にしました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetToggleNoiseGate { enable, voice_ratio, weak_floor } => {
+                    let mut config = Config::load()?;
+                    config.set_toggle_noise_gate(enable, voice_ratio, weak_floor)?;
+                    if enable {
+                        println!("トグルモードのノイズゲートを有効にしました (音声の倍率: {:.1}, 弱い音声の下限: {:.4})",
+                            config.toggle_voice_ratio, config.toggle_weak_floor);
+                    } else {
+                        println!("トグルモードのノイズゲートを無効にしました (すべて録音します)");
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetDevice { name } => {
+                    let mut config = Config::load()?;
+                    config.set_input_device(name.as_deref())?;
+                    println!("入力デバイスを設定しました: {}", name.as_deref().unwrap_or("デフォルト"));
+                    Ok(())
+                },
+                ConfigAction::SetStreamInterval { sec } => {
+                    let mut config = Config::load()?;
+                    if sec == 0 {
+                        config.set_streaming_interval(None)?;
+                        println!("途中経過の文字起こしを無効にしました");
+                    } else {
+                        config.set_streaming_interval(Some(sec))?;
+                        println!("途中経過の文字起こし間隔を{}秒に設定しました", sec);
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetTimeout { sec } => {
+                    let mut config = Config::load()?;
+                    config.set_transcription_timeout(sec)?;
+                    println!("文字起こしのタイムアウトを{}秒に設定しました", sec);
+                    Ok(())
+                },
+                ConfigAction::SetRequestTimeout { sec } => {
+                    let mut config = Config::load()?;
+                    config.set_request_timeout(sec)?;
+                    println!("リクエストのタイムアウトを{}秒に設定しました", sec);
+                    Ok(())
+                },
+                ConfigAction::SetBitDepth { depth } => {
+                    let bit_depth: config::UploadBitDepth = depth.parse()
+                        .map_err(|e| anyhow::anyhow!("{}", e))?;
+                    let mut config = Config::load()?;
+                    config.set_upload_bit_depth(bit_depth)?;
+                    println!("WAVのサンプル形式を{}に設定しました", bit_depth.name());
+                    Ok(())
+                },
+                ConfigAction::SetDither { enable } => {
+                    let mut config = Config::load()?;
+                    config.set_upload_dither(enable)?;
+                    println!("WAVのディザを{}に設定しました", if enable { "有効" } else { "無効" });
+                    Ok(())
+                },
+                ConfigAction::SetSilenceParams { threshold, min_segment_ms, trailing_silence_ms, padding_ms } => {
+                    let mut config = Config::load()?;
+                    config.set_silence_removal(threshold, min_segment_ms, trailing_silence_ms, padding_ms)?;
+                    let params = config.silence_removal;
+                    println!("無音除去のパラメータを設定しました (閾値: {}, 最短区間: {}ms, 区切りの無音: {}ms, 余白: {}ms)",
+                        params.threshold, params.min_segment_ms, params.trailing_silence_ms, params.padding_ms);
+                    Ok(())
+                },
+                ConfigAction::SetMaxTrimSilence { ms } => {
+                    let mut config = Config::load()?;
+                    config.set_max_trim_silence(ms)?;
+                    match ms {
+                        Some(ms) => println!("保存する録音に残す無音の上限を{}ミリ秒に設定しました", ms),
+                        None => println!("保存する録音の無音を取り除くように設定しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetRetry { max, delay_ms } => {
+                    let mut config = Config::load()?;
+                    config.set_api_retry(max, delay_ms)?;
+                    println!("リトライを設定しました (最大: {}回, 初期間隔: {}ミリ秒)",
+                        config.api_retry_max, config.api_retry_delay_ms);
+                    Ok(())
+                },
+                ConfigAction::SetWhisperThreads { threads } => {
+                    let mut config = Config::load()?;
+                    config.set_whisper_cpp_threads(threads)?;
+                    match threads {
+                        Some(threads) => println!("Whisper.cppのスレッド数を{}に設定しました", threads),
+                        None => println!("Whisper.cppのスレッド数をデフォルトに戻しました"),
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetWhisperArgs { args } => {
+                    let mut config = Config::load()?;
+                    config.set_whisper_cpp_args(args.clone())?;
+                    if args.is_empty() {
+                        println!("Whisper.cppの追加の引数を解除しました");
+                    } else {
+                        println!("Whisper.cppの追加の引数を設定しました: {}", args.join(" "));
+                    }
+                    Ok(())
+                },
+                ConfigAction::SetMaxConcurrent { count } => {
+                    let mut config = Config::load()?;
+                    config.set_max_concurrent_transcriptions(count)?;
+                    println!("同時文字起こし数を{}に設定しました", count);
+                    Ok(())
+                },
+                ConfigAction::SetChannelCapacity { capacity } => {
+                    let mut config = Config::load()?;
+                    config.set_channel_capacity(capacity)?;
+                    println!("待ち行列の長さを{}に設定しました", capacity);
+                    Ok(())
+                },
+                ConfigAction::SetUtteranceSeparator { separator } => {
+                    let mut config = Config::load()?;
+                    config.set_utterance_separator(&separator)?;
+                    println!("発話の区切りを設定しました: {:?}", separator);
+                    Ok(())
+                },
             }
         },
     }
 }
+
+/// 音声キャプチャ開始時のエラーを処理（入力デバイスがない場合は案内を表示して終了）
+fn handle_capture_error(e: anyhow::Error) -> anyhow::Error {
+    if let Some(VoilipError::NoInputDevice { tried }) = e.downcast_ref::<VoilipError>() {
+        print_no_input_device_help(tried);
+        std::process::exit(1);
+    }
+    if let Some(VoilipError::KeyListener { detail }) = e.downcast_ref::<VoilipError>() {
+        print_key_listener_help(detail);
+        std::process::exit(1);
+    }
+    e
+}
+
+/// 入力デバイスが見つからない場合の案内を表示
+fn print_no_input_device_help(tried: &[String]) {
+    eprintln!("エラー: 使用可能な入力デバイス（マイク）が見つかりません");
+    if !tried.is_empty() {
+        eprintln!("試行したデバイス: {}", tried.join(", "));
+    }
+    eprintln!();
+    eprintln!("以下を確認してください:");
+    eprintln!("  - マイクが接続され、OSの設定で有効になっているか");
+    eprintln!("  - `voilip devices` で入力デバイスが表示されるか");
+    eprintln!("  - Linuxの場合、ユーザーがaudioグループに所属しているか、PulseAudio/PipeWireが起動しているか");
+    eprintln!("  - コンテナやCI環境の場合、音声デバイスがホストから渡されているか");
+    eprintln!("  - 特定のデバイスを使う場合は `voilip start --device <名前>` で指定する");
+}
+
+/// キー入力を監視できない場合の案内を表示
+fn print_key_listener_help(detail: &str) {
+    eprintln!("エラー: ホットキーのためのキー入力の監視を開始できません ({})", detail);
+    eprintln!();
+    eprintln!("以下を確認してください:");
+    eprintln!("  - macOSの場合、システム設定の「プライバシーとセキュリティ」で、使用しているターミナル（またはvoilip）に「アクセシビリティ」と「入力監視」の権限を付与し、ターミナルを再起動する");
+    eprintln!("  - Linuxの場合、X11のセッションで実行しているか（Waylandではキー入力を監視できません）、DISPLAYが設定されているか");
+    eprintln!("  - ホットキーを使わない場合は `voilip config set-voice-activity <閾値> <無音の長さ>` で無音検知モードに切り替える");
+}
@@ -1,7 +1,13 @@
-use arboard::Clipboard;
+use arboard::{Clipboard, ImageData};
 use anyhow::{Result, anyhow};
 use tokio::sync::mpsc;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{Local, SecondsFormat};
 use tracing::{info, warn, debug};
 use std::time::Duration;
 use tokio::time::sleep;
@@ -9,167 +15,952 @@ use tokio::time::sleep;
 // libxdoのリンクを避けるため、直接ライブラリ依存をコメントアウト
 // use libxdo; 
 
-use crate::config::{Config, OutputMode};
-use crate::transcriber::TranscriptionResult;
-use crate::audio::show_notification;
+use crate::config::{Config, OutputMode, TypeTool};
+use crate::transcriber::{TranscriptionResult, CURL_TIMEOUT_EXIT_CODE, retry_delay, split_http_status};
+use crate::audio::{NotificationKind, notify};
+use crate::history::append_history;
+use crate::stats::SessionStats;
+use crate::subtitle::{SubtitleFormat, format_subtitles};
+use crate::utils::{apply_text_case, expand_escapes, normalize_japanese, run_command_with_timeout, truncate_chars};
+
+/// タイプ入力後にクリップボードを復元するまでの待機時間（ミリ秒）
+const CLIPBOARD_RESTORE_DELAY_MS: u64 = 300;
+/// クリップボードにコピーしてから貼り付けのキー操作を送るまでの待機時間（ミリ秒）
+const PASTE_DELAY_MS: u64 = 100;
+/// フォーカスされているウィンドウのタイトルの取得を待つ時間
+const WINDOW_TITLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 退避したクリップボードの内容
+enum SavedClipboard {
+    Text(String),
+    Image(ImageData<'static>),
+    /// 空、または復元できない形式だった
+    Empty,
+}
 
 /// 出力マネージャー
 pub struct OutputManager {
     config: Config,
     result_rx: mpsc::Receiver<TranscriptionResult>,
+    /// キー操作で開始した録音の世代（録音バッファと共有）
+    recording_epoch: Arc<AtomicU64>,
+    /// セッションの集計（出力した結果を数える）
+    stats: Option<Arc<SessionStats>>,
 }
 
 impl OutputManager {
     /// 新しいOutputManagerを作成
-    pub fn new(config: Config, result_rx: mpsc::Receiver<TranscriptionResult>) -> Self {
+    pub fn new(config: Config, result_rx: mpsc::Receiver<TranscriptionResult>, recording_epoch: Arc<AtomicU64>) -> Self {
         Self {
             config,
             result_rx,
+            recording_epoch,
+            stats: None,
         }
     }
+    
+    /// 出力した結果を数えるセッションの集計を設定
+    pub fn set_stats(&mut self, stats: Arc<SessionStats>) {
+        self.stats = Some(stats);
+    }
 
     /// 結果処理を実行
     pub async fn run(&mut self) -> Result<()> {
         info!("OutputManager: 結果処理を開始します");
         
+        let mut utterance_count = 0u32;
+        let mut output_count = 0u32;
+        
         while let Some(result) = self.result_rx.recv().await {
-            debug!("OutputManager: 結果を受信: {}", result.text);
+            debug!("OutputManager: 結果を受信 #{}: {}", result.sequence, result.text);
+            
+            // キー操作で次の録音を始めた後に届いた、前の録音の結果は破棄する
+            // （無音検知モードでは発話が重なっても全て出力する）
+            let current_epoch = self.recording_epoch.load(Ordering::SeqCst);
+            if let Some(epoch) = result.epoch.filter(|epoch| !self.config.continuous && *epoch < current_epoch) {
+                info!("次の録音を開始したため、前の録音の結果を破棄しました (#{}, 世代 {} < {})", result.sequence, epoch, current_epoch);
+                continue;
+            }
+            
+            // 途中経過はログに表示するだけで出力しない
+            if !result.is_final {
+                info!("途中経過: {}", result.text);
+                continue;
+            }
+            
+            // 無音に近い録音での幻覚（定型文など）は出力しない
+            if let Some(reason) = hallucination_reason(&result, &self.config) {
+                info!("文字起こし結果を破棄しました: {}", reason);
+                continue;
+            }
+            
+            let mut result = result;
+            polish_text(&mut result, &self.config);
+            
+            // --onceでは終了を待つ間に届いた2件目以降の結果は出力しない
+            if self.config.once && output_count > 0 {
+                info!("最初の結果を出力済みのため、結果を破棄しました");
+                continue;
+            }
+            output_count += 1;
             
-            // すべてクリップボードに出力
-            self.copy_to_clipboard(&result.text)?;
+            if let Some(stats) = &self.stats {
+                stats.record_output(&result);
+            }
+            
+            // 連続モードでは2件目以降の発話の前に区切りを出力
+            let mut boundary = String::new();
+            if self.config.continuous {
+                utterance_count += 1;
+                info!("──── 発話 #{} ────", utterance_count);
+                if utterance_count > 1 {
+                    boundary = expand_escapes(&self.config.utterance_separator);
+                }
+            }
+            
+            // ドライランではログと通知にのみ出し、履歴や出力先には送らない
+            if self.config.dry_run {
+                info!("[ドライラン] 文字起こし結果: {}", result.text);
+                let message = format!("ドライラン：{}", truncate_chars(&result.text, 30));
+                notify(self.config.notification_level(), NotificationKind::Info, "音声入力", &message);
+                continue;
+            }
+            
+            // 出力に失敗しても後から取り出せるよう、先に履歴へ記録
+            if self.config.save_history {
+                if let Err(e) = append_history(&result) {
+                    warn!("履歴の保存エラー: {}", e);
+                }
+            }
+            
+            // すべての出力先に送る（1つが失敗しても残りは続ける）
+            for mode in &self.config.output_modes {
+                // 前置・後置文字列を出力モードごとに付ける（履歴には付けない）
+                let (prefix, suffix) = self.config.affixes_for(mode);
+                let output = TranscriptionResult {
+                    text: format!("{}{}{}", expand_escapes(prefix), result.text, expand_escapes(suffix)),
+                    ..result.clone()
+                };
+                // クリップボードは毎回置き換わり、Webhook・JSONは結果ごとに分かれているため区切りは付けない
+                let boundary = match mode {
+                    OutputMode::Clipboard | OutputMode::Webhook { .. } | OutputMode::Stdout { json_lines: true } => "",
+                    _ => boundary.as_str(),
+                };
+                if let Err(e) = self.write_output(mode, &output, boundary).await {
+                    warn!("出力エラー ({}): {}", mode.describe(), e);
+                }
+            }
         }
         
         info!("OutputManager: 結果処理を終了します");
         Ok(())
     }
 
+    /// 1つの出力先に文字起こし結果を出力
+    ///
+    /// `boundary`は連続モードの発話の区切りで、タイプ入力・ファイル・標準出力では結果の前に出力する
+    async fn write_output(&self, mode: &OutputMode, result: &TranscriptionResult, boundary: &str) -> Result<()> {
+        match mode {
+            OutputMode::Clipboard => {
+                self.copy_to_clipboard(&result.text)?;
+            }
+            OutputMode::Type => {
+                if self.typing_allowed() {
+                    self.type_text(&format!("{}{}", boundary, result.text)).await?;
+                } else {
+                    self.copy_to_clipboard(&result.text)?;
+                }
+            }
+            OutputMode::Both if !self.typing_allowed() => {
+                self.copy_to_clipboard(&result.text)?;
+            }
+            OutputMode::Both => {
+                // タイプ入力後に元のクリップボードの内容を戻す
+                let saved = if self.config.restore_clipboard { self.save_clipboard() } else { None };
+                
+                self.copy_to_clipboard(&result.text)?;
+                if let Err(e) = self.type_text(&format!("{}{}", boundary, result.text)).await {
+                    warn!("タイプ入力エラー: {}", e);
+                }
+                
+                if let Some(saved) = saved {
+                    sleep(Duration::from_millis(CLIPBOARD_RESTORE_DELAY_MS)).await;
+                    if let Err(e) = self.restore_clipboard(saved) {
+                        warn!("クリップボード復元エラー: {}", e);
+                    }
+                }
+            }
+            OutputMode::File { path } => {
+                self.append_to_file(path, &result.text, boundary)?;
+            }
+            OutputMode::Stdout { json_lines } => {
+                write_stdout(result, *json_lines, boundary)?;
+            }
+            OutputMode::Webhook { url } => {
+                if let Err(e) = self.post_webhook(url, result).await {
+                    let message = truncate_chars(&e.to_string(), 100);
+                    notify(self.config.notification_level(), NotificationKind::Error, "voilip", &format!("Webhookの送信に失敗しました: {}", message));
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// クリップボードにテキストをコピー
     fn copy_to_clipboard(&self, text: &str) -> Result<()> {
+        set_clipboard_text(text)?;
+        
+        info!("クリップボードにコピーしました ({} 文字)", text.chars().count());
+        
+        // 通知を表示（日本語の文字の途中で切らないよう文字数で切り詰める）
+        let message = format!("クリップボードにコピーしました：{}", truncate_chars(text, 30));
+        notify(self.config.notification_level(), NotificationKind::Info, "音声入力", &message);
+        
+        Ok(())
+    }
+
+    /// 現在のクリップボードの内容を退避
+    fn save_clipboard(&self) -> Option<SavedClipboard> {
+        let mut clipboard = match Clipboard::new() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                warn!("クリップボード初期化エラー（復元しません）: {}", e);
+                return None;
+            }
+        };
+        
+        if let Ok(text) = clipboard.get_text() {
+            return Some(SavedClipboard::Text(text));
+        }
+        if let Ok(image) = clipboard.get_image() {
+            return Some(SavedClipboard::Image(image.to_owned_img()));
+        }
+        
+        debug!("クリップボードが空、または復元できない形式です");
+        Some(SavedClipboard::Empty)
+    }
+
+    /// 退避したクリップボードの内容を復元
+    fn restore_clipboard(&self, saved: SavedClipboard) -> Result<()> {
         let mut clipboard = Clipboard::new()
             .map_err(|e| anyhow!("クリップボード初期化エラー: {}", e))?;
         
-        clipboard.set_text(text)
-            .map_err(|e| anyhow!("クリップボードコピーエラー: {}", e))?;
+        match saved {
+            SavedClipboard::Text(text) => clipboard.set_text(text),
+            SavedClipboard::Image(image) => clipboard.set_image(image),
+            SavedClipboard::Empty => clipboard.clear(),
+        }.map_err(|e| anyhow!("クリップボード復元エラー: {}", e))?;
+        
+        debug!("クリップボードの内容を復元しました");
+        Ok(())
+    }
+
+    /// ファイルにテキストを追記（`boundary`はタイムスタンプより前に書き出す）
+    fn append_to_file(&self, path: &Path, text: &str, boundary: &str) -> Result<()> {
+        // 出力先ディレクトリがなければ作成
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
         
-        info!("クリップボードにコピーしました ({} 文字)", text.len());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
         
-        // 通知を表示
-        let short_text = if text.len() > 30 {
-            format!("{}...", &text[..30])
+        write!(file, "{}", boundary)?;
+        if self.config.output_timestamp {
+            let timestamp = Local::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+            writeln!(file, "[{}] {}", timestamp, text)?;
         } else {
-            text.to_string()
-        };
-        let message = format!("クリップボードにコピーしました：{}", short_text);
-        let _ = show_notification("音声入力", &message);
+            writeln!(file, "{}", text)?;
+        }
+        file.flush()?;
         
+        info!("ファイルに追記しました: {} ({} 文字)", path.display(), text.chars().count());
         Ok(())
     }
 
-    /// テキストをタイピング（将来のために保持）
-    #[allow(dead_code)]
-    async fn type_text(&self, text: &str) -> Result<()> {
-        info!("テキストをタイプします ({} 文字)", text.len());
-        
-        #[cfg(target_os = "macos")]
-        {
-            // AppleScriptを使用
-            let script = format!(
-                "tell application \"System Events\" to keystroke \"{}\"",
-                text.replace("\\", "\\\\").replace("\"", "\\\"")
-            );
+    /// 文字起こし結果をJSONでWebhookにPOST
+    ///
+    /// レート制限・サーバーエラー・タイムアウトは文字起こしAPIと同じ方針でリトライする
+    async fn post_webhook(&self, url: &str, result: &TranscriptionResult) -> Result<()> {
+        let body = serde_json::json!({
+            "text": result.text,
+            "language": result.language,
+            "duration_sec": result.duration_sec,
+            "timestamp": Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        });
+        
+        // 本文は引数に渡すとプロセス一覧から見えるため一時ファイル経由で送る
+        let mut body_file = tempfile::NamedTempFile::new()?;
+        body_file.write_all(body.to_string().as_bytes())?;
+        body_file.flush()?;
+        
+        let mut retry_count = 0;
+        loop {
+            let mut command = Command::new("curl");
+            command
+                .arg("-s")
+                .arg("--max-time").arg(self.config.request_timeout_sec.to_string())
+                .arg("-w").arg("\n%{http_code}")
+                .arg("-X").arg("POST")
+                .arg("-H").arg("Content-Type: application/json")
+                .arg("--data-binary").arg(format!("@{}", body_file.path().display()))
+                .arg(url);
+            if let Some(proxy) = self.config.proxy_url() {
+                command.arg("-x").arg(proxy);
+            }
+            let output = run_command_with_timeout(&mut command, Duration::from_secs(self.config.request_timeout_sec + 5))?;
             
-            let status = Command::new("osascript")
-                .args(["-e", &script])
-                .status();
+            let retryable = if output.status.success() {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let (response, http_status) = split_http_status(&stdout);
+                if (200..300).contains(&http_status) {
+                    info!("Webhookに送信しました: {} (HTTP {})", url, http_status);
+                    return Ok(());
+                }
+                if retry_count >= self.config.api_retry_max || !(http_status == 429 || http_status >= 500) {
+                    return Err(anyhow!("HTTP {}: {}", http_status, truncate_chars(response.trim(), 200)));
+                }
+                format!("HTTP {}", http_status)
+            } else {
+                let status = output.status.code().unwrap_or(-1);
+                let error_text = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                if retry_count >= self.config.api_retry_max || status != CURL_TIMEOUT_EXIT_CODE {
+                    return Err(anyhow!("curlエラー ({}): {}", status, error_text));
+                }
+                format!("{}秒でタイムアウト", self.config.request_timeout_sec)
+            };
             
-            match status {
+            retry_count += 1;
+            warn!("Webhook送信エラー ({}/{}): {}. リトライします...", retry_count, self.config.api_retry_max, retryable);
+            sleep(retry_delay(&self.config, retry_count)).await;
+        }
+    }
+
+    /// フォーカスされているウィンドウにタイプ入力してよいか（`type_allowlist`が空なら常に許可）
+    ///
+    /// ウィンドウタイトルを取得できない場合も誤入力を避けるため許可しない。許可しない場合は通知する
+    fn typing_allowed(&self) -> bool {
+        if self.config.type_allowlist.is_empty() {
+            return true;
+        }
+        
+        let title = focused_window_title();
+        if title.as_deref().is_some_and(|title| title_allowed(title, &self.config.type_allowlist)) {
+            return true;
+        }
+        
+        let title = title.unwrap_or_else(|| "(取得できません)".to_string());
+        info!("許可されていないウィンドウのため、タイプ入力の代わりにクリップボードにコピーします: {}", title);
+        let message = format!("「{}」にはタイプ入力しません", truncate_chars(&title, 30));
+        notify(self.config.notification_level(), NotificationKind::Info, "音声入力", &message);
+        false
+    }
+    
+    /// テキストをタイピング
+    ///
+    /// タイプ入力ツールが途中で失敗し入力できなかった文字が残った場合は、
+    /// 残りをクリップボード経由で貼り付ける
+    async fn type_text(&self, text: &str) -> Result<()> {
+        let expected = text.chars().count();
+        info!("テキストをタイプします ({} 文字)", expected);
+        
+        let typed = self.send_keystrokes(text).await?;
+        if typed >= expected {
+            return Ok(());
+        }
+        
+        warn!("タイプ入力が途中で失敗しました ({}/{} 文字)。残りをクリップボード経由で貼り付けます", typed, expected);
+        let rest: String = text.chars().skip(typed).collect();
+        self.paste_text(&rest).await
+    }
+    
+    /// タイプ入力ツールでテキストを入力し、入力できた文字数を返す
+    ///
+    /// `type_tool`の指定があればそのツールのみを使い、なければ環境に合うツールを順に試す。
+    /// 使えるツールが1つもない場合はエラーを返す
+    async fn send_keystrokes(&self, text: &str) -> Result<usize> {
+        if let Some(tool) = self.config.type_tool {
+            if !crate::utils::command_exists(tool.command()) {
+                return Err(anyhow!("指定されたタイプ入力ツールが見つかりません: {}", tool.command()));
+            }
+            debug!("指定されたタイプ入力ツールを使います: {}", tool.command());
+            return Ok(self.type_with(tool, text).await);
+        }
+        
+        let expected = text.chars().count();
+        let mut typed = 0;
+        let mut tool_found = false;
+        
+        // 途中で失敗した場合は、次のツールで残りを入力する
+        for tool in detect_type_tools() {
+            if !crate::utils::command_exists(tool.command()) {
+                continue;
+            }
+            tool_found = true;
+            let rest: String = text.chars().skip(typed).collect();
+            typed += self.type_with(tool, &rest).await;
+            if typed >= expected {
+                return Ok(typed);
+            }
+        }
+        
+        // どのツールもない場合はエラー
+        if !tool_found {
+            if cfg!(any(target_os = "macos", target_os = "linux")) {
+                return Err(anyhow!("テキスト入力ツールが見つかりません。wtype、ydotoolまたはxdotoolをインストールしてください。"));
+            }
+            // 非対応プラットフォーム
+            return Err(anyhow!("このプラットフォームはサポートされていません"));
+        }
+        Ok(typed)
+    }
+    
+    /// 1つのタイプ入力ツールでテキストを入力し、入力できた文字数を返す
+    async fn type_with(&self, tool: TypeTool, text: &str) -> usize {
+        let delay_ms = self.config.type_delay_ms;
+        let mut command = Command::new(tool.command());
+        match tool {
+            TypeTool::Wtype => return self.type_with_wtype(text).await,
+            TypeTool::Xdotool => {
+                command.args(xdotool_type_args(text, delay_ms));
+            }
+            TypeTool::Ydotool => {
+                // ydotoolはキーコードを送るため、日本語などASCII以外の文字は入力できない
+                if !text.is_ascii() {
+                    warn!("ydotoolはASCII以外の文字を入力できません");
+                    return 0;
+                }
+                command.args(ydotool_type_args(text, delay_ms));
+            }
+            TypeTool::Osascript => {
+                command.args(["-e", &keystroke_script(text, delay_ms)]);
+            }
+        }
+        
+        match command.status() {
+            Ok(status) if status.success() => {
+                debug!("{}でタイプ成功", tool.command());
+                text.chars().count()
+            }
+            Ok(status) => {
+                warn!("{}の実行失敗: {}", tool.command(), status);
+                0
+            }
+            Err(e) => {
+                warn!("{}の実行エラー: {}", tool.command(), e);
+                0
+            }
+        }
+    }
+    
+    /// wtypeでテキストを入力し、入力できた文字数を返す
+    ///
+    /// 文字間隔の指定がある場合は1文字ずつwtypeを呼び出し、途中で失敗した位置を分かるようにする
+    async fn type_with_wtype(&self, text: &str) -> usize {
+        if self.config.type_delay_ms == 0 {
+            return match Command::new("wtype").args(wtype_type_args(text)).status() {
                 Ok(status) if status.success() => {
-                    debug!("osascriptでタイプ成功");
-                    return Ok(());
+                    debug!("wtypeでタイプ成功");
+                    text.chars().count()
                 }
                 Ok(status) => {
-                    warn!("osascriptの実行失敗: {}", status);
-                    return Err(anyhow!("osascriptの実行に失敗しました"));
+                    warn!("wtypeの実行失敗: {}", status);
+                    0
                 }
                 Err(e) => {
-                    warn!("osascriptの実行エラー: {}", e);
-                    return Err(anyhow!("osascriptの実行に失敗しました: {}", e));
+                    warn!("wtypeの実行エラー: {}", e);
+                    0
                 }
-            }
+            };
         }
         
-        #[cfg(target_os = "linux")]
-        {
-            // LinuxでX11またはWaylandを検出
-            let is_wayland = std::env::var("WAYLAND_DISPLAY").is_ok();
-            let is_x11 = std::env::var("DISPLAY").is_ok();
-            
-            if is_wayland {
-                debug!("Wayland環境を検出しました");
-                
-                // wtype（Wayland用タイプツール）を試す
-                if let Ok(status) = Command::new("which")
-                    .arg("wtype")
-                    .output()
-                {
-                    if status.status.success() {
-                        let status = Command::new("wtype")
-                            .arg(text)
-                            .status();
-                            
-                        match status {
-                            Ok(status) if status.success() => {
-                                debug!("wtypeでタイプ成功");
-                                return Ok(());
-                            }
-                            Ok(status) => {
-                                warn!("wtypeの実行失敗: {}", status);
-                            }
-                            Err(e) => {
-                                warn!("wtypeの実行エラー: {}", e);
-                            }
-                        }
-                    }
-                }
+        let mut typed = 0;
+        let mut buf = [0u8; 4];
+        for c in text.chars() {
+            if typed > 0 {
+                sleep(Duration::from_millis(self.config.type_delay_ms as u64)).await;
             }
-            
-            if is_x11 {
-                debug!("X11環境を検出しました");
-                
-                // xdotool（X11用タイプツール）を試す
-                if let Ok(status) = Command::new("which")
-                    .arg("xdotool")
-                    .output()
-                {
-                    if status.status.success() {
-                        let status = Command::new("xdotool")
-                            .args(["type", "--clearmodifiers", text])
-                            .status();
-                            
-                        match status {
-                            Ok(status) if status.success() => {
-                                debug!("xdotoolでタイプ成功");
-                                return Ok(());
-                            }
-                            Ok(status) => {
-                                warn!("xdotoolの実行失敗: {}", status);
-                            }
-                            Err(e) => {
-                                warn!("xdotoolの実行エラー: {}", e);
-                            }
-                        }
-                    }
+            // 「-」などオプションとして解釈されないよう「--」の後に渡す
+            match Command::new("wtype").args(["--", c.encode_utf8(&mut buf)]).status() {
+                Ok(status) if status.success() => typed += 1,
+                Ok(status) => {
+                    warn!("wtypeの実行失敗: {}", status);
+                    break;
+                }
+                Err(e) => {
+                    warn!("wtypeの実行エラー: {}", e);
+                    break;
                 }
             }
-            
-            // どのツールもない場合はエラー
-            return Err(anyhow!("テキスト入力ツールが見つかりません。wtypeまたはxdotoolをインストールしてください。"));
         }
+        debug!("wtypeで1文字ずつタイプしました ({} 文字)", typed);
+        typed
+    }
+    
+    /// クリップボードにコピーし、貼り付けのキー操作を送る
+    ///
+    /// `restore_clipboard`が有効な場合は貼り付け後に元の内容を戻す
+    async fn paste_text(&self, text: &str) -> Result<()> {
+        let saved = if self.config.restore_clipboard { self.save_clipboard() } else { None };
         
-        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-        {
-            // 非対応プラットフォーム
-            return Err(anyhow!("このプラットフォームはサポートされていません"));
+        set_clipboard_text(text)?;
+        // クリップボードの内容が行き渡るまで少し待つ
+        sleep(Duration::from_millis(PASTE_DELAY_MS)).await;
+        let result = send_paste_key(self.config.type_tool);
+        
+        if let Some(saved) = saved {
+            sleep(Duration::from_millis(CLIPBOARD_RESTORE_DELAY_MS)).await;
+            if let Err(e) = self.restore_clipboard(saved) {
+                warn!("クリップボード復元エラー: {}", e);
+            }
+        }
+        
+        result?;
+        info!("クリップボード経由で貼り付けました ({} 文字)", text.chars().count());
+        Ok(())
+    }
+} 
+
+/// ウィンドウタイトルが許可リストのいずれかを含むか（大文字小文字は無視）
+fn title_allowed(title: &str, allowlist: &[String]) -> bool {
+    let title = title.to_lowercase();
+    allowlist.iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .any(|entry| title.contains(&entry.trim().to_lowercase()))
+}
+
+/// フォーカスされているウィンドウのタイトルを取得（取得できない場合は`None`）
+///
+/// macOSではアプリケーション名とウィンドウタイトルをつなげて返す
+#[cfg(target_os = "macos")]
+fn focused_window_title() -> Option<String> {
+    const SCRIPT: &str = r#"tell application "System Events"
+    set frontApp to first application process whose frontmost is true
+    set windowName to ""
+    try
+        set windowName to name of front window of frontApp
+    end try
+    return (name of frontApp) & " " & windowName
+end tell"#;
+    window_title_from(Command::new("osascript").args(["-e", SCRIPT]))
+}
+
+/// フォーカスされているウィンドウのタイトルを取得（取得できない場合は`None`）
+///
+/// xdotoolを使うため、WaylandではXWaylandのウィンドウのみ取得できる
+#[cfg(not(target_os = "macos"))]
+fn focused_window_title() -> Option<String> {
+    window_title_from(Command::new("xdotool").args(["getactivewindow", "getwindowname"]))
+}
+
+/// ウィンドウタイトルを出力するコマンドを実行
+fn window_title_from(command: &mut Command) -> Option<String> {
+    match run_command_with_timeout(command, WINDOW_TITLE_TIMEOUT) {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|title| !title.is_empty())
+        }
+        Ok(output) => {
+            debug!("ウィンドウタイトルを取得できません: {}", String::from_utf8_lossy(&output.stderr).trim());
+            None
+        }
+        Err(e) => {
+            debug!("ウィンドウタイトルを取得できません: {}", e);
+            None
+        }
+    }
+}
+
+/// 環境に合うタイプ入力ツールを優先順に返す
+fn detect_type_tools() -> Vec<TypeTool> {
+    let mut tools = Vec::new();
+    if cfg!(target_os = "macos") {
+        // AppleScriptを使用
+        tools.push(TypeTool::Osascript);
+    }
+    if cfg!(target_os = "linux") {
+        // LinuxでX11またはWaylandを検出（XWaylandでは両方を順に試す）
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            debug!("Wayland環境を検出しました");
+            // wtypeは仮想キーボードに対応していないコンポジタ（GNOMEなど）では使えないため、ydotoolも試す
+            tools.push(TypeTool::Wtype);
+            tools.push(TypeTool::Ydotool);
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            debug!("X11環境を検出しました");
+            tools.push(TypeTool::Xdotool);
+        }
+    }
+    tools
+}
+
+/// osascriptでテキストを入力するAppleScript（文字間隔の指定がある場合は1文字ずつ待つ）
+fn keystroke_script(text: &str, delay_ms: u32) -> String {
+    let escape = |s: &str| s.replace("\\", "\\\\").replace("\"", "\\\"");
+    if delay_ms == 0 {
+        return format!("tell application \"System Events\" to keystroke \"{}\"", escape(text));
+    }
+    
+    let mut script = String::from("tell application \"System Events\"\n");
+    for c in text.chars() {
+        script.push_str(&format!("keystroke \"{}\"\ndelay {}\n", escape(&c.to_string()), delay_ms as f32 / 1000.0));
+    }
+    script.push_str("end tell");
+    script
+}
+
+/// `xdotool type`の引数（文字間隔の指定がある場合は`--delay`を付ける）
+fn xdotool_type_args(text: &str, delay_ms: u32) -> Vec<String> {
+    let mut args = vec!["type".to_string(), "--clearmodifiers".to_string()];
+    if delay_ms > 0 {
+        args.push("--delay".to_string());
+        args.push(delay_ms.to_string());
+    }
+    // 「-」で始まるテキストがオプションとして解釈されないよう「--」の後に渡す
+    args.push("--".to_string());
+    args.push(text.to_string());
+    args
+}
+
+/// `ydotool type`の引数（文字間隔の指定がある場合は`--key-delay`を付ける）
+fn ydotool_type_args(text: &str, delay_ms: u32) -> Vec<String> {
+    let mut args = vec!["type".to_string()];
+    if delay_ms > 0 {
+        args.push("--key-delay".to_string());
+        args.push(delay_ms.to_string());
+    }
+    // 「-」で始まるテキストがオプションとして解釈されないよう「--」の後に渡す
+    args.push("--".to_string());
+    args.push(text.to_string());
+    args
+}
+
+/// `wtype`の引数
+fn wtype_type_args(text: &str) -> [&str; 2] {
+    ["--", text]
+}
+
+/// 貼り付けのキー操作（macOSはCmd+V、LinuxはCtrl+V）を送る
+///
+/// `tool`の指定がなければ、環境に合うツールを成功するまで順に試す
+fn send_paste_key(tool: Option<TypeTool>) -> Result<()> {
+    let tools = match tool {
+        Some(tool) => vec![tool],
+        None => detect_type_tools().into_iter().filter(|tool| crate::utils::command_exists(tool.command())).collect(),
+    };
+    
+    let mut last_error = anyhow!("貼り付けのキー操作を送るツールが見つかりません。wtype、ydotoolまたはxdotoolをインストールしてください。");
+    for tool in tools {
+        let mut command = Command::new(tool.command());
+        match tool {
+            TypeTool::Wtype => command.args(["-M", "ctrl", "v", "-m", "ctrl"]),
+            TypeTool::Xdotool => command.args(["key", "--clearmodifiers", "ctrl+v"]),
+            // 29: 左Ctrl、47: V（Linuxのキーコード、:1で押下、:0で解放）
+            TypeTool::Ydotool => command.args(["key", "29:1", "47:1", "47:0", "29:0"]),
+            TypeTool::Osascript => command.args(["-e", "tell application \"System Events\" to keystroke \"v\" using command down"]),
+        };
+        
+        match command.status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => last_error = anyhow!("貼り付けのキー操作の送信に失敗しました ({}): {}", tool.command(), status),
+            Err(e) => last_error = anyhow!("貼り付けのキー操作の送信エラー ({}): {}", tool.command(), e),
+        }
+        warn!("{}", last_error);
+    }
+    Err(last_error)
+}
+
+/// 文字起こし結果を標準出力に1行で書き出す（パイプ先がすぐ読めるよう毎回フラッシュする）
+///
+/// `boundary`は連続モードの発話の区切りで、結果の前に書き出す
+fn write_stdout(result: &TranscriptionResult, json_lines: bool, boundary: &str) -> Result<()> {
+    let line = if json_lines {
+        serde_json::to_string(result)?
+    } else {
+        result.text.clone()
+    };
+    
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}{}", boundary, line)?;
+    stdout.flush()?;
+    
+    debug!("標準出力に書き出しました ({} 文字)", result.text.chars().count());
+    Ok(())
+}
+
+/// `test --format`で標準出力に書き出す内容（text・json・srt・vtt）
+///
+/// jsonは文字起こし結果全体、srt・vttはセグメントの字幕で、セグメントがない場合はエラーにする
+pub fn format_test_result(result: &TranscriptionResult, format: &str) -> Result<String> {
+    let subtitle_format = match format {
+        "srt" => Some(SubtitleFormat::Srt),
+        "vtt" => Some(SubtitleFormat::Vtt),
+        _ => None,
+    };
+    match subtitle_format {
+        Some(_) if result.segments.is_empty() => Err(anyhow!(
+            "タイムスタンプ付きのセグメントが得られなかったため、字幕を出力できません（タイムスタンプに対応したモデルを使用してください）"
+        )),
+        Some(subtitle_format) => Ok(format_subtitles(&result.segments, subtitle_format)),
+        None if format == "json" => Ok(format!("{}\n", serde_json::to_string(result)?)),
+        None => Ok(format!("{}\n", result.text)),
+    }
+}
+
+/// クリップボードにテキストを設定（ログ・通知は出さない）
+pub fn set_clipboard_text(text: &str) -> Result<()> {
+    let mut result = Clipboard::new()
+        .map_err(|e| anyhow!("クリップボード初期化エラー: {}", e))
+        .and_then(|mut clipboard| {
+            clipboard.set_text(text).map_err(|e| anyhow!("クリップボードコピーエラー: {}", e))
+        });
+    
+    // Waylandのコンポジタによってはvoilipの終了とともに内容が消えるため、
+    // arboardでコピーできた場合もwl-copyに内容を引き継ぐ（失敗した場合は代わりに使う）
+    if wl_copy_available() {
+        match (&result, copy_with_wl_copy(text)) {
+            (Err(e), Ok(())) => {
+                debug!("arboardでコピーできなかったため、wl-copyでコピーしました: {}", e);
+                result = Ok(());
+            }
+            (Ok(()), Err(e)) => warn!("wl-copyでのコピーに失敗しました（終了後にクリップボードの内容が消える場合があります）: {}", e),
+            _ => {}
         }
     }
-} 
\ No newline at end of file
+    result
+}
+
+/// Wayland環境でwl-copyが使えるか
+fn wl_copy_available() -> bool {
+    cfg!(target_os = "linux")
+        && std::env::var_os("WAYLAND_DISPLAY").is_some()
+        && crate::utils::command_exists("wl-copy")
+}
+
+/// wl-copyでクリップボードにコピー
+///
+/// `--foreground`を付けないため、wl-copyは標準入力を読み終えるとバックグラウンドに移り、
+/// voilipの終了後も他のアプリケーションがクリップボードを取得するまで内容を保持する
+fn copy_with_wl_copy(text: &str) -> Result<()> {
+    let mut child = Command::new("wl-copy")
+        .args(["--type", "text/plain;charset=utf-8"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("wl-copyの実行エラー: {}", e))?;
+    
+    // 書き込み後に標準入力を閉じ、wl-copyに終端を知らせる
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("wl-copyの実行に失敗しました: {}", status));
+    }
+    Ok(())
+}
+
+/// 文字起こし結果を幻覚とみなして破棄すべき場合はその理由を返す
+///
+/// 空の結果、短すぎる録音、定型文との一致、セグメントの無音確率の平均が高い場合に破棄する
+fn hallucination_reason(result: &TranscriptionResult, config: &Config) -> Option<String> {
+    if result.text.trim().is_empty() {
+        return Some("結果が空です".to_string());
+    }
+    
+    if result.duration_sec < config.min_output_duration_sec {
+        return Some(format!("録音が短すぎます ({:.2}秒)", result.duration_sec));
+    }
+    
+    let normalized = normalize_for_blocklist(&result.text);
+    if config.hallucination_blocklist.iter().any(|entry| normalize_for_blocklist(entry) == normalized) {
+        return Some("定型文と一致しました".to_string());
+    }
+    
+    if let Some(average) = result.no_speech_prob.filter(|average| *average > config.max_no_speech_prob) {
+        return Some(format!("無音の可能性が高いです (no_speech_prob: {:.2})", average));
+    }
+    
+    None
+}
+
+/// 定型文との比較用に前後の空白・句読点を除き小文字にする
+fn normalize_for_blocklist(text: &str) -> String {
+    text.trim_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation() || "。、！？!?…".contains(c))
+        .to_lowercase()
+}
+
+/// 出力する前に結果の表記を整える（日本語の正規化と大文字・小文字）
+///
+/// 言語を自動検出した場合は検出した言語で日本語か判定する
+pub fn polish_text(result: &mut TranscriptionResult, config: &Config) {
+    if config.ja_normalize && result.language.starts_with("ja") {
+        result.text = normalize_japanese(&result.text);
+    }
+    result.text = apply_text_case(&result.text, config.text_case);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NotificationLevel;
+    
+    fn final_result(text: &str, sequence: u64, epoch: Option<u64>) -> TranscriptionResult {
+        TranscriptionResult {
+            text: text.to_string(),
+            language: "ja".to_string(),
+            duration_sec: 1.0,
+            is_final: true,
+            sequence,
+            segments: Vec::new(),
+            no_speech_prob: None,
+            diagnostics: None,
+            fallback_engine: None,
+            epoch,
+        }
+    }
+    
+    /// 出力先に送らず、出力した結果だけを集計するOutputManagerを作成
+    fn dry_run_manager(result_rx: mpsc::Receiver<TranscriptionResult>, epoch: Arc<AtomicU64>) -> (OutputManager, Arc<SessionStats>) {
+        let config = Config {
+            dry_run: true,
+            notifications: Some(NotificationLevel::Off),
+            ..Config::default()
+        };
+        let stats = Arc::new(SessionStats::default());
+        let mut manager = OutputManager::new(config, result_rx, epoch);
+        manager.set_stats(stats.clone());
+        (manager, stats)
+    }
+    
+    #[tokio::test]
+    async fn discards_result_of_previous_key_driven_recording() {
+        let (result_tx, result_rx) = mpsc::channel(8);
+        let epoch = Arc::new(AtomicU64::new(1));
+        let (mut manager, stats) = dry_run_manager(result_rx, epoch.clone());
+        
+        // 1件目の文字起こし中に次の録音を開始し、1件目の結果が後から届く
+        epoch.store(2, Ordering::SeqCst);
+        result_tx.send(final_result("古い", 1, Some(1))).await.unwrap();
+        result_tx.send(final_result("新しい結果", 2, Some(2))).await.unwrap();
+        drop(result_tx);
+        manager.run().await.unwrap();
+        
+        let summary = stats.summary();
+        assert_eq!(summary.utterances, 1);
+        assert_eq!(summary.characters, "新しい結果".chars().count() as u64);
+    }
+    
+    #[tokio::test]
+    async fn keeps_overlapping_voice_activity_results() {
+        let (result_tx, result_rx) = mpsc::channel(8);
+        let (mut manager, stats) = dry_run_manager(result_rx, Arc::new(AtomicU64::new(0)));
+        
+        // 2件目の発話の結果が先に届いても、1件目も出力する
+        result_tx.send(final_result("二件目", 2, None)).await.unwrap();
+        result_tx.send(final_result("一件目です", 1, None)).await.unwrap();
+        drop(result_tx);
+        manager.run().await.unwrap();
+        
+        let summary = stats.summary();
+        assert_eq!(summary.utterances, 2);
+        assert_eq!(summary.characters, ("二件目".chars().count() + "一件目です".chars().count()) as u64);
+    }
+    
+    #[tokio::test]
+    async fn continuous_mode_separates_utterances() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.txt");
+        let config = Config {
+            output_modes: vec![OutputMode::File { path: path.clone() }],
+            continuous: true,
+            utterance_separator: "---\\n".to_string(),
+            save_history: false,
+            notifications: Some(NotificationLevel::Off),
+            ..Config::default()
+        };
+        let (result_tx, result_rx) = mpsc::channel(8);
+        let mut manager = OutputManager::new(config, result_rx, Arc::new(AtomicU64::new(0)));
+        
+        for (sequence, text) in ["一件目", "二件目", "三件目"].into_iter().enumerate() {
+            result_tx.send(final_result(text, sequence as u64, None)).await.unwrap();
+        }
+        drop(result_tx);
+        manager.run().await.unwrap();
+        
+        assert_eq!(fs::read_to_string(&path).unwrap(), "一件目\n---\n二件目\n---\n三件目\n");
+    }
+    
+    #[tokio::test]
+    async fn drops_blocklisted_and_silent_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.txt");
+        let config = Config {
+            output_modes: vec![OutputMode::File { path: path.clone() }],
+            save_history: false,
+            notifications: Some(NotificationLevel::Off),
+            ..Config::default()
+        };
+        let (result_tx, result_rx) = mpsc::channel(8);
+        let mut manager = OutputManager::new(config, result_rx, Arc::new(AtomicU64::new(0)));
+        
+        result_tx.send(final_result("ご視聴ありがとうございました。", 1, None)).await.unwrap();
+        // タイムスタンプ取得が無効でもverbose_jsonの無音確率で判定する
+        let silent = TranscriptionResult { no_speech_prob: Some(0.95), ..final_result("えー", 2, None) };
+        result_tx.send(silent).await.unwrap();
+        result_tx.send(final_result("こんにちは", 3, None)).await.unwrap();
+        drop(result_tx);
+        manager.run().await.unwrap();
+        
+        assert_eq!(fs::read_to_string(&path).unwrap(), "こんにちは\n");
+    }
+    
+    #[test]
+    fn typing_backends_pass_text_after_double_dash() {
+        let text = "-1度です";
+        assert_eq!(xdotool_type_args(text, 0), ["type", "--clearmodifiers", "--", text]);
+        assert_eq!(xdotool_type_args(text, 15), ["type", "--clearmodifiers", "--delay", "15", "--", text]);
+        assert_eq!(ydotool_type_args("-5", 0), ["type", "--", "-5"]);
+        assert_eq!(ydotool_type_args("-5", 15), ["type", "--key-delay", "15", "--", "-5"]);
+        assert_eq!(wtype_type_args(text), ["--", text]);
+    }
+    
+    #[test]
+    fn japanese_normalization_only_applies_to_japanese_results() {
+        let config = Config { ja_normalize: true, ..Config::default() };
+        let mut result = final_result("１２ 件 です 。", 1, None);
+        polish_text(&mut result, &config);
+        assert_eq!(result.text, "12件です。");
+        
+        let mut result = TranscriptionResult { language: "en".to_string(), ..final_result("１２ 件 です 。", 1, None) };
+        polish_text(&mut result, &config);
+        assert_eq!(result.text, "１２ 件 です 。");
+        
+        let mut result = final_result("１２ 件", 1, None);
+        polish_text(&mut result, &Config::default());
+        assert_eq!(result.text, "１２ 件");
+    }
+    
+    #[test]
+    fn test_result_json_parses_back_into_result() {
+        let result = TranscriptionResult {
+            segments: vec![crate::transcriber::Segment { start: 0.0, end: 1.5, text: "こんにちは".to_string(), no_speech_prob: None }],
+            ..final_result("こんにちは", 3, None)
+        };
+        let json = format_test_result(&result, "json").unwrap();
+        assert_eq!(json.lines().count(), 1);
+        let parsed: TranscriptionResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.text, result.text);
+        assert_eq!(parsed.language, "ja");
+        assert_eq!(parsed.sequence, 3);
+        assert_eq!(parsed.segments.len(), 1);
+        
+        assert_eq!(format_test_result(&result, "text").unwrap(), "こんにちは\n");
+        assert!(format_test_result(&result, "srt").unwrap().contains("00:00:00,000 --> 00:00:01,500"));
+        assert!(format_test_result(&final_result("こんにちは", 3, None), "vtt").is_err());
+    }
+}
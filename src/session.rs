@@ -0,0 +1,226 @@
+use anyhow::Result;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::audio::{AudioBuffer, AudioCapture, AudioChunk, InputFormat, LevelMeter, NotificationKind, init_notifier, notify};
+use crate::config::{Config, NotificationLevel, RecordingMode, TranscriptionEngine};
+use crate::events::RecordingEvent;
+use crate::stats::SessionStats;
+use crate::transcriber::{Transcriber, TranscriptionResult};
+
+/// ストリーミング音声認識に送る音声のチャネルのバッファサイズ（再接続中の音声を溜めておく）
+const LIVE_CHANNEL_CAPACITY: usize = 1024;
+/// 終了時に録音中の音声と処理中の文字起こしの完了を待つ最大時間
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// 音声ストリームの作り直しを続けて試みる最大回数（超えたら諦める）
+const MAX_STREAM_RESTARTS: u32 = 5;
+/// 音声ストリームの作り直しの間隔の初期値（失敗するたびに倍にする）
+const STREAM_RESTART_DELAY: Duration = Duration::from_secs(1);
+/// 作り直した音声ストリームがこの時間エラーなく動いたら、失敗の回数を数え直す
+const STREAM_HEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+/// 音声キャプチャと文字起こしをまとめて実行するセッション
+///
+/// 録音の制御は設定の録音モード（無音検知・PTT・トグル・ハイブリッド）に従い、
+/// 文字起こし結果はクリップボードなどに出力せず呼び出し元に返す
+pub struct VoilipSession {
+    audio_capture: AudioCapture,
+    transcriber_task: JoinHandle<()>,
+    /// 文字起こしの終了の要求
+    shutdown: Arc<Notify>,
+    latest_sequence: Arc<AtomicU64>,
+    stats: Arc<SessionStats>,
+    notification_level: NotificationLevel,
+    /// 音声ストリームの作り直しを続けて試みた回数
+    stream_restarts: u32,
+    /// 最後に音声ストリームの作り直しを試みた時刻
+    last_stream_restart: Option<Instant>,
+}
+
+impl VoilipSession {
+    /// セッションを開始し、セッションと文字起こし結果の受信側を返す
+    ///
+    /// tokioのランタイム上で呼び出す必要がある
+    pub fn start(config: Config) -> Result<(Self, mpsc::Receiver<TranscriptionResult>)> {
+        Self::start_with_events(config, None)
+    }
+
+    /// 録音・文字起こしの状態の変化を`events`に通知しながらセッションを開始
+    ///
+    /// UIの表示の更新などに使う。受信側の処理が追いつかない場合、通知は捨てられる
+    pub fn start_with_events(
+        config: Config,
+        events: Option<mpsc::Sender<RecordingEvent>>,
+    ) -> Result<(Self, mpsc::Receiver<TranscriptionResult>)> {
+        if config.notification_level() != NotificationLevel::Off {
+            init_notifier();
+        }
+
+        // 設定ファイルで0にされた場合もチャネルを作れるようにする
+        let capacity = config.channel_capacity.max(1);
+        let (audio_tx, audio_rx) = mpsc::channel::<AudioChunk>(capacity);
+        let (result_tx, result_rx) = mpsc::channel::<TranscriptionResult>(capacity);
+
+        let mut audio_capture = AudioCapture::new(config.clone(), audio_tx);
+        let latest_sequence = Arc::new(AtomicU64::new(0));
+        let mut transcriber = Transcriber::new(config.clone(), audio_rx, result_tx, latest_sequence.clone());
+        if let Some(events) = events {
+            audio_capture.set_event_sender(events.clone());
+            transcriber.set_event_sender(events);
+        }
+        // ストリーミング音声認識には録音中の音声をそのまま送る
+        if config.transcription_engine == TranscriptionEngine::Deepgram {
+            let (live_tx, live_rx) = mpsc::channel(LIVE_CHANNEL_CAPACITY);
+            audio_capture.set_live_sender(live_tx);
+            transcriber.set_live_receiver(live_rx);
+        }
+
+        audio_capture.start()?;
+
+        // 録音制御モードの設定
+        match config.recording_mode {
+            RecordingMode::PushToTalk { .. } => audio_capture.setup_ptt_control()?,
+            RecordingMode::Toggle { .. } => audio_capture.setup_toggle_control()?,
+            RecordingMode::Hybrid { .. } => audio_capture.setup_hybrid_control()?,
+            RecordingMode::SeparateKeys { .. } => audio_capture.setup_separate_key_control()?,
+            RecordingMode::VoiceActivity { .. } => {}
+        }
+        audio_capture.setup_pause_control()?;
+        audio_capture.setup_language_control(transcriber.language_handle())?;
+        let shutdown = transcriber.shutdown_handle();
+        let stats = transcriber.stats_handle();
+
+        let transcriber_task = tokio::spawn(async move {
+            if let Err(e) = transcriber.run().await {
+                error!("音声認識エラー: {}", e);
+            }
+        });
+
+        Ok((
+            Self {
+                audio_capture,
+                transcriber_task,
+                shutdown,
+                latest_sequence,
+                stats,
+                notification_level: config.notification_level(),
+                stream_restarts: 0,
+                last_stream_restart: None,
+            },
+            result_rx,
+        ))
+    }
+
+    /// 録音を開始（PTT・トグルのキー操作の代わりに使える）
+    pub fn start_recording(&self) -> Result<()> {
+        self.audio_capture.start_recording()
+    }
+
+    /// 録音を停止して文字起こしに送る
+    pub fn stop_recording(&self) -> Result<()> {
+        self.audio_capture.stop_recording()
+    }
+
+    /// 現在録音中かどうか
+    pub fn is_recording(&self) -> bool {
+        self.audio_capture.is_recording()
+    }
+
+    /// 実際に使用している入力形式
+    pub fn input_format(&self) -> Option<InputFormat> {
+        self.audio_capture.input_format()
+    }
+
+    /// 録音バッファ（制御ソケットなど別のタスクから録音を開始・停止する場合に使う）
+    pub fn audio_buffer(&self) -> Arc<AudioBuffer> {
+        self.audio_capture.audio_buffer()
+    }
+
+    /// 入力レベルメーター（UIでの表示用）
+    pub fn level_meter(&self) -> &LevelMeter {
+        self.audio_capture.level_meter()
+    }
+
+    /// 音声ストリームが止まっていれば作り直す（メインループから定期的に呼び出す）
+    ///
+    /// 入力デバイスの取り外しなどでストリームがエラーになった場合、間隔を空けながら作り直しを試みる。
+    /// 続けて`MAX_STREAM_RESTARTS`回失敗した場合はエラーを返す
+    pub fn check_stream(&mut self) -> Result<()> {
+        if !self.audio_capture.stream_failed() {
+            if self.last_stream_restart.is_some_and(|t| t.elapsed() >= STREAM_HEALTHY_AFTER) {
+                self.stream_restarts = 0;
+                self.last_stream_restart = None;
+            }
+            return Ok(());
+        }
+
+        // 失敗するたびに間隔を倍にし、すぐに失敗を繰り返さないようにする
+        if let Some(last) = self.last_stream_restart {
+            let delay = STREAM_RESTART_DELAY * 2u32.pow(self.stream_restarts.saturating_sub(1).min(5));
+            if last.elapsed() < delay {
+                return Ok(());
+            }
+        }
+        if self.stream_restarts >= MAX_STREAM_RESTARTS {
+            notify(self.notification_level, NotificationKind::Error, "voilip", "音声入力が停止しました。入力デバイスを確認してください");
+            return Err(anyhow::anyhow!("音声入力が停止し、{}回作り直しても復旧しませんでした", MAX_STREAM_RESTARTS));
+        }
+        if self.stream_restarts == 0 {
+            notify(self.notification_level, NotificationKind::Error, "voilip", "音声入力が停止しました。再接続しています");
+        }
+
+        self.stream_restarts += 1;
+        self.last_stream_restart = Some(Instant::now());
+        warn!("音声ストリームを作り直します ({}/{}回目)", self.stream_restarts, MAX_STREAM_RESTARTS);
+        match self.audio_capture.restart() {
+            Ok(()) => info!("音声ストリームを作り直しました"),
+            Err(e) => warn!("音声ストリームの作り直しに失敗しました: {}", e),
+        }
+        Ok(())
+    }
+
+    /// 最終結果の通し番号（途中経過が最新の録音のものかの判定に使う）
+    pub fn latest_sequence(&self) -> Arc<AtomicU64> {
+        self.latest_sequence.clone()
+    }
+
+    /// キー操作で開始した録音の世代（`OutputManager::new`に渡して古い録音の結果を破棄する）
+    pub fn recording_epoch(&self) -> Arc<AtomicU64> {
+        self.audio_capture.audio_buffer().recording_epoch()
+    }
+
+    /// セッションの集計（出力した結果は`OutputManager::set_stats`で渡した先で数える）
+    pub fn stats(&self) -> Arc<SessionStats> {
+        self.stats.clone()
+    }
+
+    /// 音声キャプチャを停止し、処理中の文字起こしの完了を待って終了
+    ///
+    /// 録音中の場合は、それまでの音声を文字起こしに送ってから停止する
+    pub async fn stop(self) {
+        let Self { mut audio_capture, transcriber_task, shutdown, .. } = self;
+        if audio_capture.is_recording() {
+            info!("録音中の音声を文字起こししてから終了します");
+            if let Err(e) = audio_capture.stop_recording() {
+                warn!("録音の停止エラー: {}", e);
+            }
+        }
+        audio_capture.stop();
+        drop(audio_capture);
+
+        // 送信済みの音声と処理中の文字起こしを終えてからループを終わらせる
+        shutdown.notify_one();
+
+        // 文字起こしが長引いても終了できるよう、待ち時間に上限を設ける
+        let abort_handle = transcriber_task.abort_handle();
+        if tokio::time::timeout(STOP_TIMEOUT, transcriber_task).await.is_err() {
+            warn!("文字起こしの終了を待てなかったため中断します");
+            abort_handle.abort();
+        }
+        info!("セッションを終了しました");
+    }
+}
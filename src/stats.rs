@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::transcriber::TranscriptionResult;
+
+/// セッション中の文字起こしの集計（文字起こしと出力の別々のタスクから更新する）
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    utterances: AtomicU64,
+    audio_ms: AtomicU64,
+    characters: AtomicU64,
+    words: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl SessionStats {
+    /// 出力した文字起こし結果を1件加える
+    pub fn record_output(&self, result: &TranscriptionResult) {
+        self.utterances.fetch_add(1, Ordering::Relaxed);
+        self.audio_ms.fetch_add((result.duration_sec.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+        self.characters.fetch_add(result.text.chars().count() as u64, Ordering::Relaxed);
+        self.words.fetch_add(result.text.split_whitespace().count() as u64, Ordering::Relaxed);
+    }
+    
+    /// 文字起こしの失敗を1件加える
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// 現在までの集計
+    pub fn summary(&self) -> StatsSummary {
+        StatsSummary {
+            utterances: self.utterances.load(Ordering::Relaxed),
+            audio_sec: self.audio_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+            characters: self.characters.load(Ordering::Relaxed),
+            words: self.words.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// ある時点でのセッションの集計
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatsSummary {
+    /// 出力した発話の数
+    pub utterances: u64,
+    /// 出力した発話の音声の長さの合計（秒）
+    pub audio_sec: f64,
+    /// 出力した文字数（前置・後置文字列は含めない）
+    pub characters: u64,
+    /// 出力した単語数（空白区切り、日本語では目安にならない）
+    pub words: u64,
+    /// 文字起こしに失敗した回数
+    pub errors: u64,
+}
+
+impl StatsSummary {
+    /// 終了時に表示する1行の要約
+    pub fn describe(&self, elapsed: Duration) -> String {
+        let elapsed = elapsed.as_secs();
+        format!(
+            "セッションの集計: 発話 {}件 / 音声 {:.1}秒 / {}文字 ({}語) / エラー {}件 / 起動時間 {}分{}秒",
+            self.utterances, self.audio_sec, self.characters, self.words, self.errors, elapsed / 60, elapsed % 60
+        )
+    }
+}
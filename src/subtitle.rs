@@ -0,0 +1,94 @@
+use crate::transcriber::Segment;
+
+/// 字幕の形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    /// SubRip（.srt）
+    Srt,
+    /// WebVTT（.vtt）
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// 時刻のミリ秒の区切り文字（SRTはカンマ、WebVTTはピリオド）
+    fn millis_separator(&self) -> char {
+        match self {
+            SubtitleFormat::Srt => ',',
+            SubtitleFormat::Vtt => '.',
+        }
+    }
+}
+
+/// セグメントを字幕ファイルの内容に変換
+///
+/// 本文が空のセグメントは出力せず、番号は出力したものだけで振り直す。
+/// 開始位置が前のセグメントの終了位置より前の場合や、終了位置が開始位置より前の場合は
+/// 字幕の時刻が逆行しないよう補正する
+pub fn format_subtitles(segments: &[Segment], format: SubtitleFormat) -> String {
+    let mut output = String::new();
+    if format == SubtitleFormat::Vtt {
+        output.push_str("WEBVTT\n\n");
+    }
+    
+    let mut index = 0;
+    let mut last_end = 0.0f32;
+    for segment in segments {
+        let text = segment.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        
+        let start = segment.start.max(last_end).max(0.0);
+        let end = segment.end.max(start);
+        last_end = end;
+        index += 1;
+        
+        // WebVTTの番号（キューID）は省略できるが、SRTに揃えて出力する
+        output.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index,
+            format_timestamp(start, format),
+            format_timestamp(end, format),
+            text
+        ));
+    }
+    
+    output
+}
+
+/// 秒を`HH:MM:SS,mmm`（WebVTTは`HH:MM:SS.mmm`）の形式に変換
+fn format_timestamp(seconds: f32, format: SubtitleFormat) -> String {
+    let total_ms = (seconds.max(0.0) as f64 * 1000.0).round() as u64;
+    let hours = total_ms / 3_600_000;
+    let minutes = total_ms / 60_000 % 60;
+    let secs = total_ms / 1000 % 60;
+    let millis = total_ms % 1000;
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, secs, format.millis_separator(), millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn segment(start: f32, end: f32, text: &str) -> Segment {
+        Segment { start, end, text: text.to_string(), no_speech_prob: None }
+    }
+    
+    #[test]
+    fn formats_srt() {
+        let segments = [
+            segment(0.0, 2.5, "こんにちは"),
+            segment(3661.25, 3663.0, " 字幕のテスト "),
+        ];
+        assert_eq!(
+            format_subtitles(&segments, SubtitleFormat::Srt),
+            "1\n00:00:00,000 --> 00:00:02,500\nこんにちは\n\n2\n01:01:01,250 --> 01:01:03,000\n字幕のテスト\n\n",
+        );
+    }
+    
+    #[test]
+    fn formats_empty_segments_as_empty_srt() {
+        assert_eq!(format_subtitles(&[], SubtitleFormat::Srt), "");
+        assert_eq!(format_subtitles(&[segment(0.0, 1.0, "  ")], SubtitleFormat::Srt), "");
+    }
+}
@@ -1,19 +1,52 @@
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::io::Cursor;
 use anyhow::{Result, anyhow};
-use tracing::{info, warn, error, debug};
-use tokio::sync::mpsc;
+use tracing::{info, warn, error, debug, trace};
+use tokio::sync::{mpsc, Notify, OnceCell, Semaphore};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use hound::{WavSpec, WavWriter, SampleFormat};
+use std::env;
 use std::fs;
 use std::process::Command;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use tempfile::NamedTempFile;
+use chrono::Local;
 
-use crate::config::{Config, TranscriptionEngine};
+use crate::audio::{AudioChunk, LiveAudio, NotificationKind, extract_audio_data_from_wav, load_audio_file, notify, split_on_silence};
+use crate::config::{AUTO_LANGUAGE, AZURE_API_KEY_ENV, CliOverrides, Config, TranscriptionEngine, UploadBitDepth, validate_model};
+use crate::error::VoilipError;
+use crate::events::{RecordingEvent, emit};
+use crate::key_rotation;
+use crate::output::polish_text;
+use crate::stats::SessionStats;
+use crate::utils::{apply_replacements, format_text, run_command_with_stderr_lines, run_command_with_timeout, truncate_chars};
+use crate::whisper_server::{self, WhisperServer};
+use crate::{deepgram, local_whisper};
 
-const API_RETRY_MAX: u8 = 3;
-const API_RETRY_DELAY_MS: u64 = 1000;
+/// リトライ間隔に加えるゆらぎの割合（±25%）
+const API_RETRY_JITTER: f64 = 0.25;
+/// Groqの文字起こしAPIのURL
+const GROQ_TRANSCRIPTION_URL: &str = "https://api.groq.com/openai/v1/audio/transcriptions";
+/// Groqの翻訳APIのエンドポイント
+const GROQ_TRANSLATION_URL: &str = "https://api.groq.com/openai/v1/audio/translations";
+/// Azure OpenAIのREST APIのバージョン
+pub const AZURE_API_VERSION: &str = "2024-06-01";
+/// 通知に表示するエラーメッセージの最大文字数
+pub(crate) const NOTIFICATION_ERROR_MAX_CHARS: usize = 100;
+/// curlがタイムアウトした場合の終了コード
+pub const CURL_TIMEOUT_EXIT_CODE: i32 = 28;
+/// Retry-Afterで指定された待ち時間の上限（極端に長い指定で文字起こしが止まらないようにする）
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Whisper.cppの進捗をログに出す間隔（パーセント）
+const WHISPER_CPP_PROGRESS_LOG_STEP: u8 = 10;
+/// Whisper.cppの実行がこれより長引いた場合に、進捗を通知で知らせる
+const WHISPER_CPP_PROGRESS_NOTIFY_AFTER: Duration = Duration::from_secs(10);
+/// WAVヘッダーのサイズ（バイト）
+const WAV_HEADER_BYTES: usize = 44;
 
 /// 文字起こし結果
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,155 +54,413 @@ pub struct TranscriptionResult {
     pub text: String,
     pub language: String,
     pub duration_sec: f32,
+    /// 録音完了後の最終結果か（falseの場合は録音中の途中経過）
+    pub is_final: bool,
+    /// 録音の通し番号（古い録音の結果を破棄するために使用）
+    pub sequence: u64,
+    /// タイムスタンプ付きのセグメント（タイムスタンプ取得が有効な場合のみ）
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    /// セグメントの無音確率の平均（verbose_jsonで取得した場合のみ、タイムスタンプ取得の設定に関わらず求める）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
+    /// 文字起こしに送った音声の診断情報（録音から文字起こしした場合のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<AudioDiagnostics>,
+    /// エンジンが失敗し、フォールバックエンジンで認識した場合のエンジン名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_engine: Option<String>,
+    /// キー操作で開始した録音の世代（古い録音の結果を破棄するために使用、Noneは破棄しない）
+    #[serde(skip)]
+    pub epoch: Option<u64>,
+}
+
+/// 文字起こしに送った音声の診断情報
+///
+/// 結果が空だったりおかしかったりした場合に、原因が音声（短すぎる・小さすぎる）か
+/// モデルかを切り分けるために使う
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioDiagnostics {
+    /// 録音したサンプル数（前処理前）
+    pub recorded_samples: usize,
+    /// 無音除去後のサンプル数
+    pub voiced_samples: usize,
+    /// 録音のピーク振幅（前処理前）
+    pub peak: f32,
+    /// APIの上限に合わせて分割したWAVの数（1なら分割なし）
+    pub chunks: usize,
+}
+
+impl AudioDiagnostics {
+    /// 前処理をしていない音声の診断情報
+    pub fn unprocessed(samples: &[f32]) -> Self {
+        Self {
+            recorded_samples: samples.len(),
+            voiced_samples: samples.len(),
+            peak: samples.iter().fold(0.0f32, |max, s| max.max(s.abs())),
+            chunks: 1,
+        }
+    }
+}
+
+/// タイムスタンプ付きのセグメント
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    /// 開始位置（秒）
+    pub start: f32,
+    /// 終了位置（秒）
+    pub end: f32,
+    pub text: String,
+    /// 無音である確率（verbose_jsonのみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
 }
 
 /// 音声認識エンジン
 pub struct Transcriber {
     config: Config,
-    audio_rx: mpsc::Receiver<Vec<f32>>,
+    audio_rx: mpsc::Receiver<AudioChunk>,
     result_tx: mpsc::Sender<TranscriptionResult>,
+    /// 最後に受け付けた録音の通し番号（OutputManagerと共有）
+    latest_sequence: Arc<AtomicU64>,
+    /// 同時に実行する文字起こしの数を制限する
+    semaphore: Arc<Semaphore>,
+    /// 文字起こしの開始・完了の通知先
+    events: Option<mpsc::Sender<RecordingEvent>>,
+    /// 文字起こしの言語（実行中に言語切り替えキーで変更される）
+    language: SharedLanguage,
+    /// 終了の要求
+    shutdown: Arc<Notify>,
+    /// ストリーミング音声認識に送る録音中の音声（Deepgramのみ）
+    live_rx: Option<mpsc::Receiver<LiveAudio>>,
+    /// セッションの集計（文字起こしの失敗を数える）
+    stats: Arc<SessionStats>,
+    /// 起動したwhisper-server（whisper-serverエンジンのみ、Transcriberのdrop時に終了する）
+    whisper_server: OnceCell<WhisperServer>,
 }
 
+/// 実行中に切り替えられる文字起こしの言語
+pub type SharedLanguage = Arc<RwLock<String>>;
+
 impl Transcriber {
     /// 新しいTranscriberを作成
     pub fn new(
         config: Config,
-        audio_rx: mpsc::Receiver<Vec<f32>>,
+        audio_rx: mpsc::Receiver<AudioChunk>,
         result_tx: mpsc::Sender<TranscriptionResult>,
+        latest_sequence: Arc<AtomicU64>,
     ) -> Self {
+        let semaphore = Arc::new(Semaphore::new(config.max_concurrent_transcriptions.max(1)));
+        let language = Arc::new(RwLock::new(config.language.clone()));
         Self {
             config,
             audio_rx,
             result_tx,
+            latest_sequence,
+            semaphore,
+            events: None,
+            language,
+            shutdown: Arc::new(Notify::new()),
+            live_rx: None,
+            stats: Arc::new(SessionStats::default()),
+            whisper_server: OnceCell::new(),
         }
     }
+    
+    /// 文字起こしの言語の共有ハンドル（書き換えると次の録音から反映される）
+    pub fn language_handle(&self) -> SharedLanguage {
+        self.language.clone()
+    }
+
+    /// 文字起こしの開始・完了の通知先を設定
+    pub fn set_event_sender(&mut self, events: mpsc::Sender<RecordingEvent>) {
+        self.events = Some(events);
+    }
+
+    /// ストリーミング音声認識に送る録音中の音声の受信側を設定
+    pub fn set_live_receiver(&mut self, live_rx: mpsc::Receiver<LiveAudio>) {
+        self.live_rx = Some(live_rx);
+    }
 
     /// 音声認識処理を実行
     pub async fn run(&mut self) -> Result<()> {
         info!("Transcriber: 音声認識処理を開始します");
         
-        while let Some(audio_data) = self.audio_rx.recv().await {
-            if audio_data.is_empty() {
-                continue;
+        // 最初の録音を待たせないよう、whisper-serverは開始時に起動しておく
+        self.ensure_whisper_server().await?;
+        
+        // ストリーミング音声認識は録音中の音声を直接受け取るため、別のタスクで実行する
+        let stream_shutdown = Arc::new(Notify::new());
+        let stream_task = self.live_rx.take()
+            .filter(|_| self.config.transcription_engine == TranscriptionEngine::Deepgram)
+            .map(|live_rx| {
+                tokio::spawn(deepgram::run_stream(
+                    self.config.clone(),
+                    live_rx,
+                    self.result_tx.clone(),
+                    self.latest_sequence.clone(),
+                    self.events.clone(),
+                    self.language.clone(),
+                    stream_shutdown.clone(),
+                ))
+            });
+        
+        loop {
+            let chunk = tokio::select! {
+                chunk = self.audio_rx.recv() => match chunk {
+                    Some(chunk) => chunk,
+                    None => break,
+                },
+                _ = self.shutdown.notified() => {
+                    // 終了の要求までに届いていた音声は文字起こししてから終わる
+                    while let Ok(chunk) = self.audio_rx.try_recv() {
+                        self.process_chunk(chunk).await?;
+                    }
+                    break;
+                }
+            };
+            self.process_chunk(chunk).await?;
+        }
+        
+        // 処理中の文字起こしが完了するまで待つ（すべてのpermitが返却されるのを待つ）
+        let permits = self.config.max_concurrent_transcriptions.max(1) as u32;
+        let _ = self.semaphore.acquire_many(permits).await;
+        
+        if let Some(stream_task) = stream_task {
+            stream_shutdown.notify_one();
+            match stream_task.await {
+                Ok(Err(e)) => error!("ストリーミング音声認識エラー: {}", e),
+                Err(e) => error!("ストリーミング音声認識のタスクエラー: {}", e),
+                Ok(Ok(())) => {}
             }
-            
-            debug!("Transcriber: 音声データを受信 ({} サンプル)", audio_data.len());
-            
-            // WAVファイルにエンコード
-            let wav_data = self.encode_wav(&audio_data)?;
-            
-            // 音声認識を実行
-            match &self.config.transcription_engine {
-                TranscriptionEngine::GPT4o => {
-                    let config_clone = self.config.clone();
-                    let wav_data_clone = wav_data.clone();
-                    let result_tx = self.result_tx.clone();
-                    
-                    // ブロッキングAPIを別スレッドで実行
-                    tokio::task::spawn_blocking(move || {
-                        match transcribe_with_openai(&config_clone, "gpt-4o", &wav_data_clone) {
-                            Ok(result) => {
-                                tokio::spawn(async move {
-                                    if let Err(e) = result_tx.send(result).await {
-                                        error!("結果送信エラー: {}", e);
-                                    }
-                                });
-                            }
-                            Err(e) => error!("GPT-4o 音声認識エラー: {}", e),
+        }
+        
+        info!("Transcriber: 音声認識処理を終了します");
+        Ok(())
+    }
+    
+    /// セッションの集計の共有ハンドル（出力した結果はOutputManagerが数える）
+    pub fn stats_handle(&self) -> Arc<SessionStats> {
+        self.stats.clone()
+    }
+    
+    /// 文字起こしを終了させるハンドル（`notify_one`で、届いている音声を処理してから`run`を終える）
+    pub fn shutdown_handle(&self) -> Arc<Notify> {
+        self.shutdown.clone()
+    }
+    
+    /// whisper-serverエンジンの場合、サーバーが起動していなければ起動して応答を待つ
+    async fn ensure_whisper_server(&self) -> Result<()> {
+        if let TranscriptionEngine::WhisperCppServer { path, model, port } = &self.config.transcription_engine {
+            let (config, path, model, port) = (self.config.clone(), path.clone(), model.clone(), *port);
+            self.whisper_server.get_or_try_init(|| async move {
+                tokio::task::spawn_blocking(move || WhisperServer::start(&config, &path, &model, port)).await?
+            }).await?;
+        }
+        Ok(())
+    }
+    
+    /// 受信した音声を1つ文字起こしに回す
+    async fn process_chunk(&mut self, chunk: AudioChunk) -> Result<()> {
+        let AudioChunk { samples: audio_data, is_final, diagnostics, recording, epoch } = chunk;
+        // ストリーミング音声認識では録音中に送った音声から結果を作るため、録音後の音声は使わない
+        if audio_data.is_empty() || self.config.transcription_engine == TranscriptionEngine::Deepgram {
+            return Ok(());
+        }
+        
+        // 最終結果なら通し番号を進める（途中経過は録音中の番号を使う）
+        let sequence = if is_final {
+            self.latest_sequence.fetch_add(1, Ordering::SeqCst) + 1
+        } else {
+            self.latest_sequence.load(Ordering::SeqCst) + 1
+        };
+        
+        debug!("Transcriber: 音声データを受信 #{} ({} サンプル, {})", sequence, audio_data.len(), if is_final { "最終" } else { "途中経過" });
+        
+        // WAVファイルにエンコード（APIの上限を超える場合は無音部分で分割）
+        // whisper-rsはサンプルをそのまま受け取るためエンコードしない
+        let wav_chunks = if matches!(self.config.transcription_engine, TranscriptionEngine::WhisperRs { .. }) {
+            Vec::new()
+        } else {
+            encode_wav_chunks(&self.config, &audio_data, self.config.output_channels())?
+        };
+        let diagnostics = AudioDiagnostics { chunks: wav_chunks.len().max(1), ..diagnostics };
+        if is_final {
+            debug!("Transcriber: 音声の診断情報 #{} (録音: {} サンプル, 無音除去後: {} サンプル, ピーク: {:.3}, 分割数: {})",
+                sequence, diagnostics.recorded_samples, diagnostics.voiced_samples, diagnostics.peak, diagnostics.chunks);
+        }
+        
+        // デバッグや再文字起こし用に確定した録音を保存
+        let recording_path = match (&self.config.save_recordings, is_final) {
+            (Some(dir), true) => match self.encode_wav(recording.as_deref().unwrap_or(&audio_data), self.config.output_channels()).and_then(|wav| save_recording(dir, sequence, &wav)) {
+                Ok(path) => {
+                    if let Some(max) = self.config.max_saved_recordings {
+                        if let Err(e) = prune_recordings(dir, max) {
+                            warn!("古い録音の削除エラー: {}", e);
                         }
-                    });
+                    }
+                    Some(path)
+                },
+                Err(e) => {
+                    warn!("録音の保存エラー: {}", e);
+                    None
                 }
-                TranscriptionEngine::Whisper1 => {
-                    let config_clone = self.config.clone();
-                    let wav_data_clone = wav_data.clone();
-                    let result_tx = self.result_tx.clone();
-                    
-                    // ブロッキングAPIを別スレッドで実行
-                    tokio::task::spawn_blocking(move || {
-                        match transcribe_with_openai(&config_clone, "whisper-1", &wav_data_clone) {
-                            Ok(result) => {
-                                tokio::spawn(async move {
-                                    if let Err(e) = result_tx.send(result).await {
-                                        error!("結果送信エラー: {}", e);
-                                    }
-                                });
-                            }
-                            Err(e) => error!("Whisper-1 音声認識エラー: {}", e),
+            },
+            _ => None,
+        };
+        
+        // 同時実行数の上限に達している場合は空きを待つ（超えた分はチャネルで順番待ちになる）
+        let permit = match self.semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                debug!("Transcriber: 同時文字起こし数の上限 ({}) に達したため待機します #{}",
+                    self.config.max_concurrent_transcriptions, sequence);
+                self.semaphore.clone().acquire_owned().await?
+            }
+        };
+        
+        // 音声認識を実行（言語は録音ごとに最新の値を使う）
+        let mut config_clone = self.config.clone();
+        if let Ok(language) = self.language.read() {
+            config_clone.language = language.clone();
+        }
+        let result_tx = self.result_tx.clone();
+        // 途中経過は頻繁に届くため、状態の変化は確定した録音についてのみ通知する
+        let events = self.events.clone().filter(|_| is_final);
+        let stats = self.stats.clone();
+        emit(events.as_ref(), RecordingEvent::Transcribing);
+        
+        // ブロッキングAPIを別スレッドで実行（完了時にpermitを解放）
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            match transcribe_with_fallback(&config_clone, &audio_data, config_clone.output_channels(), &wav_chunks) {
+                Ok(mut result) => {
+                    result.is_final = is_final;
+                    result.sequence = sequence;
+                    result.epoch = epoch;
+                    result.diagnostics = Some(diagnostics);
+                    result.text = apply_replacements(&format_text(&result.text), &config_clone.replacements);
+                    // 保存した録音の横に文字起こし結果を書き出す（修正用データセットの作成向け）
+                    if let Some(path) = &recording_path {
+                        if let Err(e) = fs::write(path.with_extension("txt"), &result.text) {
+                            warn!("文字起こし結果の保存エラー: {}", e);
                         }
-                    });
+                    }
+                    emit(events.as_ref(), RecordingEvent::Done(result.clone()));
+                    // ブロッキング用のスレッドなので、ランタイムのハンドルに頼らずその場で送信する
+                    if let Err(e) = result_tx.blocking_send(result) {
+                        error!("結果送信エラー: {}", e);
+                    }
                 }
-                TranscriptionEngine::WhisperCpp { path, model } => {
-                    let config_clone = self.config.clone();
-                    let path_clone = path.clone();
-                    let model_clone = model.clone();
-                    let wav_data_clone = wav_data.clone();
-                    let result_tx = self.result_tx.clone();
-                    
-                    // ブロッキングAPIを別スレッドで実行
-                    tokio::task::spawn_blocking(move || {
-                        match transcribe_with_whisper_cpp(&config_clone, &path_clone, &model_clone, &wav_data_clone) {
-                            Ok(result) => {
-                                tokio::spawn(async move {
-                                    if let Err(e) = result_tx.send(result).await {
-                                        error!("結果送信エラー: {}", e);
-                                    }
-                                });
-                            }
-                            Err(e) => error!("Whisper.cpp 音声認識エラー: {}", e),
-                        }
-                    });
+                Err(e) => {
+                    error!("{} 音声認識エラー: {}", config_clone.transcription_engine.name(), e);
+                    emit(events.as_ref(), RecordingEvent::Error(e.to_string()));
+                    stats.record_error();
+                    // ターミナルを見ていなくても失敗に気付けるよう通知する
+                    let message = format!("文字起こしに失敗しました: {}", truncate_chars(&e.to_string(), NOTIFICATION_ERROR_MAX_CHARS));
+                    notify(config_clone.notification_level(), NotificationKind::Error, "voilip", &message);
                 }
             }
-        }
+        });
         
-        info!("Transcriber: 音声認識処理を終了します");
         Ok(())
     }
 
     /// 音声データをWAVファイルにエンコード
-    fn encode_wav(&self, audio_data: &[f32]) -> Result<Vec<u8>> {
-        let spec = WavSpec {
-            channels: self.config.channels,
-            sample_rate: self.config.sample_rate,
-            bits_per_sample: 16,
-            sample_format: SampleFormat::Int,
-        };
-        
-        let mut buffer = Vec::new();
-        let mut writer = WavWriter::new(Cursor::new(&mut buffer), spec)?;
-        
-        // f32サンプルをi16に変換して書き込み
-        for &sample in audio_data {
-            let sample_i16 = (sample * 32767.0) as i16;
-            writer.write_sample(sample_i16)?;
+    fn encode_wav(&self, audio_data: &[f32], channels: u16) -> Result<Vec<u8>> {
+        encode_wav(&self.config, audio_data, channels)
+    }
+
+    /// メモリ上の音声を設定のエンジンで文字起こし
+    ///
+    /// `samples`は設定のサンプルレートのモノラル音声。録音ループ（`run`）とは独立して呼び出せ、
+    /// アップロード上限に合わせた分割と、結果の整形・置換ルールの適用も行う
+    pub async fn transcribe_samples(&self, samples: &[f32]) -> Result<TranscriptionResult> {
+        self.ensure_whisper_server().await?;
+        let mut config = self.config.clone();
+        if let Ok(language) = self.language.read() {
+            config.language = language.clone();
         }
+        let wav_chunks = if matches!(config.transcription_engine, TranscriptionEngine::WhisperRs { .. }) {
+            Vec::new()
+        } else {
+            encode_wav_chunks(&config, samples, 1)?
+        };
+        let samples = samples.to_vec();
         
-        writer.finalize()?;
-        Ok(buffer)
+        tokio::task::spawn_blocking(move || {
+            let mut result = transcribe_with_fallback(&config, &samples, 1, &wav_chunks)?;
+            result.text = apply_replacements(&format_text(&result.text), &config.replacements);
+            Ok(result)
+        }).await?
     }
 
-    /// テストモード: 音声ファイルから文字起こし
+    /// 音声ファイルを指定したモデル（またはエンジン名）で文字起こし（ベンチマーク・再文字起こし用）
     pub async fn transcribe_file(
         &self,
-        file_path: &PathBuf,
+        file_path: &Path,
         model: &str,
     ) -> Result<TranscriptionResult> {
-        // ファイルを読み込み
-        let wav_data = std::fs::read(file_path)?;
+        // 任意の形式のファイルをデコードし、設定のレートのモノラルWAVに変換
+        let samples = load_audio_file(file_path, self.config.sample_rate)?;
+        let wav_data = encode_wav_samples(&samples, 1, self.config.sample_rate, self.config.upload_bit_depth, self.config.upload_dither)?;
         let config_clone = self.config.clone();
         
-        // モデルに応じた文字起こし
+        // モデルに応じた文字起こし（モデル名の場合はそのままAPIに送る）
         match model {
-            "gpt-4o" => {
+            m if m == "whisper-1" || m.starts_with("gpt-4o") => {
+                let mut config_clone = config_clone;
+                config_clone.model = m.to_string();
+                tokio::task::spawn_blocking(move || {
+                    Ok(transcribe_with_openai(&config_clone, &wav_data)?)
+                }).await?
+            }
+            m if is_groq_model(m) => {
+                let mut config_clone = config_clone;
+                config_clone.model = m.to_string();
                 tokio::task::spawn_blocking(move || {
-                    transcribe_with_openai(&config_clone, "gpt-4o", &wav_data)
+                    transcribe_with_groq(&config_clone, &wav_data)
                 }).await?
             }
-            "whisper-1" => {
+            "groq" => {
+                // 設定のモデルがGroqのモデルでなければGroqのデフォルトモデルで比較する
+                let mut config_clone = config_clone;
+                if !is_groq_model(&config_clone.model) {
+                    config_clone.model = TranscriptionEngine::Groq.default_model().unwrap_or_default().to_string();
+                }
                 tokio::task::spawn_blocking(move || {
-                    transcribe_with_openai(&config_clone, "whisper-1", &wav_data)
+                    transcribe_with_groq(&config_clone, &wav_data)
                 }).await?
             }
+            "azure" => {
+                if let TranscriptionEngine::Azure { endpoint, deployment } = self.config.transcription_engine.clone() {
+                    tokio::task::spawn_blocking(move || {
+                        transcribe_with_azure(&config_clone, &endpoint, &deployment, &wav_data)
+                    }).await?
+                } else {
+                    Err(anyhow!("Azure OpenAIを使用するには、エンドポイントとデプロイ名が設定されている必要があります"))
+                }
+            }
+            "whisper-rs" => {
+                if let TranscriptionEngine::WhisperRs { model } = self.config.transcription_engine.clone() {
+                    let sample_rate = self.config.sample_rate;
+                    tokio::task::spawn_blocking(move || {
+                        local_whisper::transcribe(&config_clone, &model, &samples, 1, sample_rate)
+                    }).await?
+                } else {
+                    Err(anyhow!("whisper-rsを使用するには、モデルが設定されている必要があります"))
+                }
+            }
+            "whisper-server" => {
+                if let TranscriptionEngine::WhisperCppServer { port, .. } = self.config.transcription_engine {
+                    self.ensure_whisper_server().await?;
+                    tokio::task::spawn_blocking(move || {
+                        whisper_server::transcribe(&config_clone, port, &wav_data)
+                    }).await?
+                } else {
+                    Err(anyhow!("whisper-serverを使用するには、パスとモデルが設定されている必要があります"))
+                }
+            }
             "whisper.cpp" | "whisper-cpp" => {
                 if let TranscriptionEngine::WhisperCpp { path, model } = &self.config.transcription_engine {
                     let path_clone = path.clone();
@@ -189,71 +480,486 @@ impl Transcriber {
     }
 }
 
+/// f32の音声データを指定したサンプル形式のWAVにエンコード
+///
+/// `dither`の場合、整数PCMでは丸める前に三角分布のディザを加える
+fn encode_wav_samples(audio_data: &[f32], channels: u16, sample_rate: u32, bit_depth: UploadBitDepth, dither: bool) -> Result<Vec<u8>> {
+    let (bits_per_sample, sample_format) = match bit_depth {
+        UploadBitDepth::Int16 => (16, SampleFormat::Int),
+        UploadBitDepth::Int24 => (24, SampleFormat::Int),
+        UploadBitDepth::Float32 => (32, SampleFormat::Float),
+    };
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    };
+    
+    let mut buffer = Vec::new();
+    let mut writer = WavWriter::new(Cursor::new(&mut buffer), spec)?;
+    
+    // 整数PCMは範囲外の値が最大値に張り付くよう[-1.0, 1.0]に収めてから丸めて変換
+    for &sample in audio_data {
+        match bit_depth {
+            UploadBitDepth::Int16 => writer.write_sample(quantize(sample, 32767.0, dither) as i16)?,
+            UploadBitDepth::Int24 => writer.write_sample(quantize(sample, 8388607.0, dither) as i32)?,
+            UploadBitDepth::Float32 => writer.write_sample(sample)?,
+        }
+    }
+    
+    writer.finalize()?;
+    Ok(buffer)
+}
+
+/// サンプルを[-1.0, 1.0]に収めて`max`倍し、整数の値に丸める
+///
+/// `dither`の場合は丸める前に±1LSBの三角分布のノイズを加える。
+/// 完全な無音（0）はディザで雑音にならないよう、そのまま0にする
+fn quantize(sample: f32, max: f32, dither: bool) -> f32 {
+    let scaled = sample.clamp(-1.0, 1.0) * max;
+    let noise = if dither && sample != 0.0 { fastrand::f32() - fastrand::f32() } else { 0.0 };
+    (scaled + noise).round().clamp(-max - 1.0, max)
+}
+
+/// 音声データをWAVファイルにエンコードし、必要に応じてアップロード上限・長さの上限以下に分割
+fn encode_wav_chunks(config: &Config, audio_data: &[f32], channels: u16) -> Result<Vec<Vec<u8>>> {
+    // ローカルのWhisper.cppにはサイズ・長さの制限がない
+    if matches!(config.transcription_engine, TranscriptionEngine::WhisperCpp { .. } | TranscriptionEngine::WhisperCppServer { .. }) {
+        return Ok(vec![encode_wav(config, audio_data, channels)?]);
+    }
+    
+    // ヘッダー分を差し引き、サンプル形式に応じたサンプル数に換算
+    let interleaved = channels as usize;
+    let bytes_per_sample = config.upload_bit_depth.bytes_per_sample() * interleaved;
+    let max_upload_samples = config.max_upload_bytes.saturating_sub(WAV_HEADER_BYTES) / bytes_per_sample * interleaved;
+    // APIはサイズとは別に音声の長さも制限するため、短い方に合わせる
+    let max_duration_samples = config.max_api_duration_sec
+        .map_or(usize::MAX, |sec| sec as usize * config.sample_rate as usize * interleaved);
+    let max_samples = max_upload_samples.min(max_duration_samples);
+    
+    if audio_data.len() <= max_samples {
+        return Ok(vec![encode_wav(config, audio_data, channels)?]);
+    }
+    
+    let chunks = split_on_silence(audio_data, interleaved, config.sample_rate, max_samples);
+    let reason = if max_duration_samples < max_upload_samples { "APIに送れる長さ" } else { "アップロード上限" };
+    info!("録音が{}を超えるため{}個に分割します", reason, chunks.len());
+    let samples_per_sec = (config.sample_rate as usize * interleaved) as f32;
+    for (i, chunk) in chunks.iter().enumerate() {
+        debug!("分割した音声 {}/{}: {:.1}秒", i + 1, chunks.len(), chunk.len() as f32 / samples_per_sec);
+    }
+    
+    chunks.into_iter().map(|chunk| encode_wav(config, chunk, channels)).collect()
+}
+
+/// 音声データを設定のサンプルレート・量子化ビット数でWAVファイルにエンコード
+pub(crate) fn encode_wav(config: &Config, audio_data: &[f32], channels: u16) -> Result<Vec<u8>> {
+    encode_wav_samples(audio_data, channels, config.sample_rate, config.upload_bit_depth, config.upload_dither)
+}
+
+/// 録音を設定のエンジンで認識（`wav_chunks`は`encode_wav_chunks`でエンコードした音声、whisper-rsでは使わない）
+fn transcribe_audio(config: &Config, samples: &[f32], channels: u16, wav_chunks: &[Vec<u8>]) -> Result<TranscriptionResult> {
+    match &config.transcription_engine {
+        TranscriptionEngine::WhisperRs { model } => local_whisper::transcribe(config, model, samples, channels, config.sample_rate),
+        _ => transcribe_chunks(config, wav_chunks),
+    }
+}
+
+/// 録音を設定のエンジンで認識し、失敗した場合はフォールバックエンジンで同じ録音を認識し直す
+///
+/// フォールバックエンジンも失敗した場合は、両方のエラーを含めて返す
+fn transcribe_with_fallback(config: &Config, samples: &[f32], channels: u16, wav_chunks: &[Vec<u8>]) -> Result<TranscriptionResult> {
+    let error = match transcribe_audio(config, samples, channels, wav_chunks) {
+        Ok(result) => return Ok(result),
+        Err(e) => e,
+    };
+    let Some(fallback) = config.fallback_config() else {
+        return Err(error);
+    };
+    let engine = fallback.transcription_engine.name();
+    warn!("{} 音声認識エラーのため、{}で認識し直します: {}", config.transcription_engine.name(), engine, error);
+    
+    // アップロード上限はエンジンごとに異なるため、フォールバックエンジンに合わせてエンコードし直す
+    let result = if matches!(fallback.transcription_engine, TranscriptionEngine::WhisperRs { .. }) {
+        transcribe_audio(&fallback, samples, channels, &[])
+    } else {
+        encode_wav_chunks(&fallback, samples, channels)
+            .and_then(|wav_chunks| transcribe_audio(&fallback, samples, channels, &wav_chunks))
+    };
+    match result {
+        Ok(mut result) => {
+            info!("{}で認識しました（フォールバック）", engine);
+            result.fallback_engine = Some(engine.to_string());
+            Ok(result)
+        }
+        Err(e) => Err(anyhow!("{} (フォールバックの{}も失敗: {})", error, engine, e)),
+    }
+}
+
+/// 分割された音声を順に認識して1つの結果にまとめる
+fn transcribe_chunks(config: &Config, wav_chunks: &[Vec<u8>]) -> Result<TranscriptionResult> {
+    if let [wav_data] = wav_chunks {
+        return transcribe_with_engine(config, wav_data);
+    }
+    
+    let mut texts = Vec::with_capacity(wav_chunks.len());
+    let mut language = None;
+    let mut duration_sec = 0.0;
+    let mut segments = Vec::new();
+    let mut no_speech_probs = Vec::new();
+    
+    for (i, wav_data) in wav_chunks.iter().enumerate() {
+        debug!("分割音声を認識中 ({}/{})", i + 1, wav_chunks.len());
+        let result = transcribe_with_engine(config, wav_data)?;
+        texts.push(result.text);
+        language.get_or_insert(result.language);
+        no_speech_probs.extend(result.no_speech_prob);
+        // セグメントの位置は分割前の音声の先頭からの位置に揃える
+        segments.extend(result.segments.into_iter().map(|segment| Segment {
+            start: segment.start + duration_sec,
+            end: segment.end + duration_sec,
+            ..segment
+        }));
+        duration_sec += result.duration_sec;
+    }
+    
+    Ok(TranscriptionResult {
+        text: texts.join(" "),
+        language: language.unwrap_or_else(|| config.language.clone()),
+        duration_sec,
+        is_final: true,
+        sequence: 0,
+        segments,
+        no_speech_prob: (!no_speech_probs.is_empty())
+            .then(|| no_speech_probs.iter().sum::<f32>() / no_speech_probs.len() as f32),
+        diagnostics: None,
+        fallback_engine: None,
+        epoch: None,
+    })
+}
+
+/// 設定されたエンジンで音声認識
+fn transcribe_with_engine(config: &Config, wav_data: &[u8]) -> Result<TranscriptionResult> {
+    match &config.transcription_engine {
+        TranscriptionEngine::GPT4o | TranscriptionEngine::Whisper1 => Ok(transcribe_with_openai(config, wav_data)?),
+        TranscriptionEngine::Groq => transcribe_with_groq(config, wav_data),
+        TranscriptionEngine::WhisperCpp { path, model } => {
+            transcribe_with_whisper_cpp(config, path, model, wav_data)
+        }
+        TranscriptionEngine::WhisperCppServer { port, .. } => whisper_server::transcribe(config, *port, wav_data),
+        TranscriptionEngine::Azure { endpoint, deployment } => {
+            transcribe_with_azure(config, endpoint, deployment, wav_data)
+        }
+        // 通常はWAVを経由せずにサンプルを渡すが、WAVしかない場合はデコードして渡す
+        TranscriptionEngine::WhisperRs { model } => {
+            let (samples, spec) = extract_audio_data_from_wav(wav_data)?;
+            local_whisper::transcribe(config, model, &samples, spec.channels, spec.sample_rate)
+        }
+        TranscriptionEngine::Deepgram => Err(anyhow!("Deepgramはストリーミング専用のため、録音済みの音声は文字起こしできません")),
+    }
+}
+
+/// multipart APIの認証方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiAuth<'a> {
+    /// 認証なし（ローカルサーバーなど）
+    None,
+    /// `Authorization: Bearer`ヘッダー（OpenAI・Groq）
+    Bearer(&'a str),
+    /// リクエストごとに順に使う複数のキーの`Authorization: Bearer`ヘッダー（OpenAI）
+    BearerKeys(&'a [String]),
+    /// `api-key`ヘッダー（Azure OpenAI）
+    ApiKey(&'a str),
+}
+
+impl<'a> ApiAuth<'a> {
+    /// APIキーからBearer認証を作成（キーが空なら認証なし）
+    fn bearer(api_key: &'a str) -> Self {
+        if api_key.is_empty() { ApiAuth::None } else { ApiAuth::Bearer(api_key) }
+    }
+    
+    /// curlの`-H`に渡すヘッダー
+    pub fn header(&self) -> Option<String> {
+        match self {
+            ApiAuth::None => None,
+            ApiAuth::Bearer(key) => Some(format!("Authorization: Bearer {}", key)),
+            // 通常はリクエストごとに選んだキーの`Bearer`に置き換えてから使う
+            ApiAuth::BearerKeys(keys) => keys.first().map(|key| format!("Authorization: Bearer {}", key)),
+            ApiAuth::ApiKey(key) => Some(format!("api-key: {}", key)),
+        }
+    }
+    
+    /// ログ出力用のヘッダー（キーは伏せる）
+    fn masked_header(&self) -> Option<&'static str> {
+        match self {
+            ApiAuth::None => None,
+            ApiAuth::Bearer(_) | ApiAuth::BearerKeys(_) => Some("Authorization: Bearer ***"),
+            ApiAuth::ApiKey(_) => Some("api-key: ***"),
+        }
+    }
+}
+
+/// Azure OpenAIの文字起こし（翻訳モードでは翻訳）APIのURL
+pub fn azure_transcription_url(endpoint: &str, deployment: &str, translate: bool) -> String {
+    let operation = if translate { "translations" } else { "transcriptions" };
+    format!(
+        "{}/openai/deployments/{}/audio/{}?api-version={}",
+        endpoint.trim_end_matches('/'), deployment, operation, AZURE_API_VERSION
+    )
+}
+
 /// OpenAI APIで音声認識
-fn transcribe_with_openai(config: &Config, model: &str, wav_data: &[u8]) -> Result<TranscriptionResult> {
-    let api_key = config.openai_api_key.clone();
+///
+/// 送るモデル名は常に設定のモデル（`--model`で上書き可能）で、エンジンは接続先のみを決める
+fn transcribe_with_openai(config: &Config, wav_data: &[u8]) -> Result<TranscriptionResult, VoilipError> {
+    let api_keys = config.openai_api_keys();
+    if api_keys.is_empty() && config.api_base_url.is_none() {
+        return Err(VoilipError::MissingApiKey { provider: "OpenAI" });
+    }
+    // 複数のキーがある場合はレート制限を分散するため、リクエストごとに順に使う
+    let auth = match api_keys.as_slice() {
+        [] => ApiAuth::None,
+        [api_key] => ApiAuth::Bearer(api_key),
+        keys => ApiAuth::BearerKeys(keys),
+    };
+    
+    let model = request_model(config)?;
+    let url = if config.translate { config.translation_url() } else { config.transcription_url() };
+    Ok(transcribe_with_multipart_api(config, &url, auth, model, wav_data)?)
+}
+
+/// Groq APIで音声認識
+fn transcribe_with_groq(config: &Config, wav_data: &[u8]) -> Result<TranscriptionResult> {
+    if config.groq_api_key.is_empty() {
+        return Err(VoilipError::MissingApiKey { provider: "Groq" }.into());
+    }
+    
+    let model = request_model(config)?;
+    let url = if config.translate { GROQ_TRANSLATION_URL } else { GROQ_TRANSCRIPTION_URL };
+    transcribe_with_multipart_api(config, url, ApiAuth::bearer(&config.groq_api_key), model, wav_data)
+}
+
+/// Groqで使用できるモデル名か
+fn is_groq_model(model: &str) -> bool {
+    model.starts_with("whisper-large") || model.starts_with("distil-whisper")
+}
+
+/// Azure OpenAIで音声認識
+fn transcribe_with_azure(config: &Config, endpoint: &str, deployment: &str, wav_data: &[u8]) -> Result<TranscriptionResult> {
+    let api_key = env::var(AZURE_API_KEY_ENV).unwrap_or_default();
     if api_key.is_empty() {
-        return Err(anyhow!("OpenAI APIキーが設定されていません"));
+        return Err(VoilipError::MissingApiKey { provider: "Azure OpenAI" }.into());
     }
     
-    // モデルを決定する - 引数で指定されたものがあればそれを使用、なければ設定ファイルのモデルを使用
-    let transcription_model = match model {
-        "gpt-4o" => "gpt-4o-transcribe", // 後方互換性のため
-        _ => &config.model,
+    let url = azure_transcription_url(endpoint, deployment, config.translate);
+    transcribe_with_multipart_api(config, &url, ApiAuth::ApiKey(&api_key), request_model(config)?, wav_data)
+}
+
+/// multipart APIの`model`フィールドに送るモデル名
+///
+/// エンジンは接続先を決めるだけで、モデル名は設定（または`--model`）の値を読み替えずにそのまま送る。
+/// ただしAzure OpenAIはモデルがデプロイで決まるため、デプロイ名を送る
+fn request_model(config: &Config) -> Result<&str, VoilipError> {
+    let engine_error = |detail: String| VoilipError::EngineConfig {
+        engine: config.transcription_engine.name().to_string(),
+        detail,
     };
+    if let TranscriptionEngine::Azure { deployment, .. } = &config.transcription_engine {
+        return Ok(deployment);
+    }
     
-    let url = "https://api.openai.com/v1/audio/transcriptions";
-    
+    validate_model(&config.model).map_err(|e| engine_error(e.to_string()))?;
+    // OpenAIの翻訳エンドポイントはwhisper-1のみ対応
+    if config.translate && config.model.starts_with("gpt-4o") {
+        return Err(engine_error(format!(
+            "モデル{}は翻訳（--translate）に対応していません。--model whisper-1を指定してください",
+            config.model
+        )));
+    }
+    Ok(&config.model)
+}
+
+/// OpenAI互換のmultipart APIで音声認識
+fn transcribe_with_multipart_api(
+    config: &Config,
+    url: &str,
+    auth: ApiAuth,
+    transcription_model: &str,
+    wav_data: &[u8],
+) -> Result<TranscriptionResult> {
     // 処理された音声データの情報をログに出力
     let wav_duration = audio_duration_sec(wav_data)?;
     debug!("音声データの処理: 長さ {:.2}秒, サイズ {} バイト", wav_duration, wav_data.len());
     debug!("使用するモデル: {}", transcription_model);
     
+    // 言語を自動検出する場合は指定せず、サーバーが検出した言語をverbose_jsonで受け取る
+    let language = config.language_hint();
+    let detect_language = language.is_none() && !config.translate;
+    
+    // タイムスタンプか検出した言語が必要な場合のみverbose_jsonを要求（gpt-4o系はjsonのみ対応）
+    let response_format = if !config.timestamps && !detect_language {
+        "json"
+    } else if transcription_model.starts_with("gpt-4o") {
+        if config.timestamps {
+            warn!("{}はタイムスタンプに対応していないため、セグメントは取得しません", transcription_model);
+        }
+        "json"
+    } else {
+        "verbose_json"
+    };
+    
+    // APIはファイル名の拡張子で形式を判定するため、.wavの一時ファイルに書き出す
+    // （リトライ時も使い回し、成功・失敗に関わらずdrop時に削除される）
+    let mut temp_file = tempfile::Builder::new().prefix("voilip-").suffix(".wav").tempfile()?;
+    std::io::copy(&mut Cursor::new(wav_data), &mut temp_file)?;
+    let temp_path = temp_file.path().to_str().ok_or_else(|| anyhow!("一時ファイルパスの変換エラー"))?;
+    // レート制限時のRetry-Afterを読むため、応答ヘッダーをファイルに書き出させる
+    let header_file = NamedTempFile::new()?;
+    
+    let original_auth = auth;
     let mut retry_count = 0;
     loop {
-        // WAVファイルを一時ファイルに書き出す
-        let mut temp_file = NamedTempFile::new()?;
-        std::io::copy(&mut Cursor::new(wav_data), &mut temp_file)?;
-        let temp_path = temp_file.path().to_str().ok_or_else(|| anyhow!("一時ファイルパスの変換エラー"))?;
+        // 複数のキーを順に使う場合は、リトライも含めてリクエストごとにキーを選び直す
+        let rotated_key = match auth {
+            ApiAuth::BearerKeys(keys) => key_rotation::select_key(keys),
+            _ => None,
+        };
+        let auth = rotated_key.as_deref().map_or(auth, ApiAuth::Bearer);
         
-        // 拡張子を明示的に指定
-        let temp_path_with_ext = format!("{}.wav", temp_path);
-        std::fs::copy(temp_path, &temp_path_with_ext)?;
-
         // curlコマンドをデバッグ出力
-        debug!("実行するcurlコマンド: curl -s -X POST -H \"Authorization: Bearer ***\" -H \"Content-Type: multipart/form-data\" -F \"model={}\" -F \"language={}\" -F \"response_format=json\" -F \"file=@{}\" {}", 
-              transcription_model, config.language, temp_path_with_ext, url);
+        debug!("実行するcurlコマンド: curl -s -X POST -H \"{}\" -H \"Content-Type: multipart/form-data\" -F \"model={}\" -F \"language={}\" -F \"response_format={}\" -F \"file=@{}\" {}", 
+              auth.masked_header().unwrap_or("(認証なし)"), transcription_model, config.language, response_format, temp_path, url);
+        if !config.extra_headers.is_empty() {
+            debug!("追加のHTTPヘッダー: {}", config.extra_headers.iter().map(|(name, _)| format!("{}: ***", name)).collect::<Vec<_>>().join(", "));
+        }
         
         // curlコマンドを使用してリクエスト
-        let output = Command::new("curl")
+        let mut command = Command::new("curl");
+        command
             .arg("-s")
-            .arg("-X").arg("POST")
-            .arg("-H").arg(format!("Authorization: Bearer {}", api_key))
+            .arg("--max-time").arg(config.request_timeout_sec.to_string())
+            .arg("-w").arg("\n%{http_code}")
+            .arg("-D").arg(header_file.path())
+            .arg("-X").arg("POST");
+        // 設定または環境変数のプロキシを明示的に渡す（curlは大文字のHTTP_PROXYなどを一部無視するため）
+        if let Some(proxy) = config.proxy_url() {
+            command.arg("-x").arg(proxy);
+        }
+        // ローカルサーバーなどAPIキー不要の場合と、追加のヘッダーで認証を置き換える場合はヘッダーを省略
+        if let Some(header) = auth.header().filter(|_| !config.overrides_auth_header()) {
+            command.arg("-H").arg(header);
+        }
+        for (name, value) in &config.extra_headers {
+            command.arg("-H").arg(format!("{}: {}", name, value));
+        }
+        command
             .arg("-H").arg("Content-Type: multipart/form-data")
-            .arg("-F").arg(format!("model={}", transcription_model))
-            .arg("-F").arg(format!("language={}", config.language))
-            .arg("-F").arg("response_format=json")
-            .arg("-F").arg(format!("file=@{}", temp_path_with_ext))
-            .arg(url)
-            .output()?;
-        
-        // 一時ファイルを削除
-        let _ = std::fs::remove_file(temp_path_with_ext);
+            .arg("-F").arg(format!("model={}", transcription_model));
+        // 翻訳エンドポイントは常に英語を出力するためlanguageは送らない
+        if let Some(language) = language.filter(|_| !config.translate) {
+            command.arg("-F").arg(format!("language={}", language));
+        }
+        command.arg("-F").arg(format!("response_format={}", response_format));
+        // プロンプトは@や<で始まるとファイル参照と解釈されるため--form-stringを使う
+        if let Some(prompt) = config.effective_prompt() {
+            command.arg("--form-string").arg(format!("prompt={}", prompt));
+        }
+        if let Some(temperature) = config.temperature {
+            command.arg("-F").arg(format!("temperature={}", temperature));
+        }
+        command
+            .arg("-F").arg(format!("file=@{}", temp_path))
+            .arg(url);
+        let output = run_command_with_timeout(&mut command, Duration::from_secs(config.transcription_timeout_sec))?;
         
         if output.status.success() {
-            let response = String::from_utf8(output.stdout)?;
-            debug!("API応答: {}", response);
-            let json: Value = serde_json::from_str(&response)?;
+            let stdout = String::from_utf8(output.stdout)?;
+            let (response, http_status) = split_http_status(&stdout);
+            // レスポンスには文字起こし結果が含まれるため、本文はtraceレベルでのみ出力
+            debug!("API応答: HTTP {} ({} バイト)", http_status, response.len());
+            trace!("API応答本文: {}", response);
+            
+            if http_status >= 400 {
+                if retry_count < config.api_retry_max && (http_status == 429 || http_status >= 500) {
+                    // レート制限または一時的なサーバーエラーの場合はリトライ
+                    // （サーバーがRetry-Afterで待ち時間を指定していればそれに従う）
+                    retry_count += 1;
+                    let retry_after = if matches!(http_status, 429 | 503) {
+                        fs::read_to_string(header_file.path()).ok()
+                            .and_then(|headers| retry_after_from_headers(&headers, chrono::Utc::now()))
+                    } else {
+                        None
+                    };
+                    // レート制限を受けたキーは後回しにし、制限を受けていないキーがあればすぐにリトライする
+                    if let (Some(key), ApiAuth::BearerKeys(keys), 429) = (&rotated_key, original_auth, http_status) {
+                        key_rotation::mark_rate_limited(key, retry_after.unwrap_or(key_rotation::RATE_LIMIT_COOLDOWN));
+                        if key_rotation::has_available_key(keys) {
+                            warn!("API呼び出しエラー ({}/{}): HTTP 429. 別のAPIキーでリトライします...", retry_count, config.api_retry_max);
+                            continue;
+                        }
+                    }
+                    let delay = retry_after.unwrap_or_else(|| retry_delay(config, retry_count));
+                    warn!("API呼び出しエラー ({}/{}): HTTP {}. {:.1}秒後にリトライします{}...", 
+                        retry_count, config.api_retry_max, http_status, delay.as_secs_f32(),
+                        if retry_after.is_some() { " (Retry-Afterの指定)" } else { "" });
+                    
+                    std::thread::sleep(delay);
+                    continue;
+                }
+                
+                return Err(VoilipError::ApiError { status: http_status, body: response.to_string() }.into());
+            }
+            
+            let json = match parse_api_response(response) {
+                Ok(json) => json,
+                Err(e) => {
+                    warn!("APIの応答を解釈できません: {} (本文: {})", e.message, truncate_chars(response.trim(), 200));
+                    if e.transient && retry_count < config.api_retry_max {
+                        retry_count += 1;
+                        warn!("API呼び出しエラー ({}/{}): 不正な応答. リトライします...",
+                            retry_count, config.api_retry_max);
+                        
+                        std::thread::sleep(retry_delay(config, retry_count));
+                        continue;
+                    }
+                    
+                    return Err(anyhow!(e.message));
+                }
+            };
             
             if let Some(text) = json.get("text").and_then(|t| t.as_str()) {
                 let duration = audio_duration_sec(wav_data)?;
                 
-                info!("文字起こし完了: {} ({:.2}秒)", text, duration);
+                info!("文字起こし完了 ({:.2}秒, {} 文字)", duration, text.chars().count());
+                debug!("文字起こし結果: {}", text);
+                
+                let language = if config.translate {
+                    "en".to_string()
+                } else if let Some(language) = language {
+                    language.to_string()
+                } else {
+                    json.get("language")
+                        .and_then(|l| l.as_str())
+                        .map(language_code)
+                        .unwrap_or_else(|| AUTO_LANGUAGE.to_string())
+                };
+                
+                // 無音確率は幻覚の判定に使うため、セグメントを返さない場合も求めておく
+                let segments = parse_verbose_json_segments(&json);
+                let no_speech_prob = average_no_speech_prob(&segments);
                 
                 return Ok(TranscriptionResult {
                     text: text.to_string(),
-                    language: config.language.clone(),
+                    language,
                     duration_sec: duration,
+                    is_final: true,
+                    sequence: 0,
+                    segments: if config.timestamps { segments } else { Vec::new() },
+                    no_speech_prob,
+                    diagnostics: None,
+                    fallback_engine: None,
+                    epoch: None,
                 });
             } else {
                 return Err(anyhow!("APIレスポンスにテキストがありません: {}", response));
@@ -263,15 +969,26 @@ fn transcribe_with_openai(config: &Config, model: &str, wav_data: &[u8]) -> Resu
             let status = output.status.code().unwrap_or(500);
             let stdout_text = String::from_utf8(output.stdout)?;
             
-            if retry_count < API_RETRY_MAX && (status == 429 || status >= 500) {
+            // curlの終了コード28はタイムアウト（リトライ回数に含める）
+            if status == CURL_TIMEOUT_EXIT_CODE {
+                if retry_count < config.api_retry_max {
+                    retry_count += 1;
+                    warn!("APIリクエストが{}秒でタイムアウトしました ({}/{}). リトライします...",
+                        config.request_timeout_sec, retry_count, config.api_retry_max);
+                    continue;
+                }
+                
+                return Err(anyhow!("APIリクエストがタイムアウトしました ({}秒, {}回リトライ)",
+                    config.request_timeout_sec, config.api_retry_max));
+            }
+            
+            if retry_count < config.api_retry_max && (status == 429 || status >= 500) {
                 // レート制限または一時的なサーバーエラーの場合はリトライ
                 retry_count += 1;
                 warn!("API呼び出しエラー ({}/{}): {} - {}. リトライします...", 
-                    retry_count, API_RETRY_MAX, status, error_text);
+                    retry_count, config.api_retry_max, status, error_text);
                 
-                std::thread::sleep(std::time::Duration::from_millis(
-                    API_RETRY_DELAY_MS * 2u64.pow(retry_count as u32 - 1)
-                ));
+                std::thread::sleep(retry_delay(config, retry_count));
                 continue;
             }
             
@@ -282,49 +999,437 @@ fn transcribe_with_openai(config: &Config, model: &str, wav_data: &[u8]) -> Resu
 
 /// Whisper.cppを使用した音声認識
 fn transcribe_with_whisper_cpp(
-    _config: &Config,
+    config: &Config,
     whisper_path: &PathBuf,
-    model_path: &PathBuf,
+    model_path: &Path,
     wav_data: &[u8],
 ) -> Result<TranscriptionResult> {
+    // OSの「No such file」では何が見つからないのか分からないため、起動前に確認する
+    check_whisper_cpp_paths(whisper_path, model_path)?;
+    
     // 一時ファイルに保存
     let mut temp_file = NamedTempFile::new()?;
     std::io::copy(&mut Cursor::new(wav_data), &mut temp_file)?;
     let temp_path = temp_file.path();
     
+    // 出力ファイル名 (whisper.cppは入力ファイル名に拡張子を付けて出力する)
+    let output_file = format!("{}.json", temp_path.to_string_lossy());
+    
     // Whisper.cppのコマンドを構築
-    let output = Command::new(whisper_path)
-        .arg("-m").arg(model_path)
-        .arg("-f").arg(temp_path)
-        .arg("-otxt")
-        .output()?;
+    let mut command = Command::new(whisper_path);
+    command.args(whisper_cpp_command_args(config, model_path, temp_path));
     
-    if output.status.success() {
-        // 出力ファイル名を取得
-        let output_file = format!("{}.txt", temp_path.to_string_lossy());
-        
-        // 結果を読み込み
-        let text = fs::read_to_string(&output_file)?;
-        let duration = audio_duration_sec(wav_data)?;
-        
-        // 一時ファイルを削除
-        let _ = fs::remove_file(&output_file);
-        
-        info!("Whisper.cppによる文字起こし完了 ({:.2}秒)", duration);
+    let mut retried = false;
+    let result = loop {
+        let on_progress = whisper_cpp_progress_reporter(config.notification_level());
+        let output = match run_command_with_stderr_lines(&mut command, Duration::from_secs(config.transcription_timeout_sec), on_progress) {
+            Ok(output) => output,
+            Err(e) => break Err(e),
+        };
+        if output.status.success() {
+            break fs::read_to_string(&output_file).map_err(anyhow::Error::from).and_then(|json| parse_whisper_cpp_json(&json));
+        }
         
-        Ok(TranscriptionResult {
-            text,
-            language: "auto".to_string(), // Whisper.cppは自動的に言語を検出
-            duration_sec: duration,
-        })
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        // 進捗の行はエラーの原因の手がかりにならないため、エラーメッセージには含めない
+        let stderr = String::from_utf8_lossy(&output.stderr).lines()
+            .filter(|line| parse_whisper_cpp_progress(line).is_none())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string();
+        // 何も出力せずに失敗した場合は、シグナルなどによる一時的な失敗とみなして1回だけやり直す
+        if !retried && stdout.is_empty() && stderr.is_empty() {
+            retried = true;
+            warn!("Whisper.cppが何も出力せずに終了しました ({})。リトライします...", output.status);
+            continue;
+        }
+        break Err(anyhow!("Whisper.cpp実行エラー ({}): 標準エラー出力: {} / 標準出力: {}",
+            output.status, or_none(&stderr), or_none(&stdout)));
+    };
+    
+    // 成功・失敗に関わらず出力ファイルを削除（WAVの一時ファイルはdrop時に削除される）
+    let _ = fs::remove_file(&output_file);
+    
+    let parsed = result?;
+    let duration = audio_duration_sec(wav_data)?;
+    
+    // セグメントのオフセットから求めた長さと照合
+    if let Some(segment_end) = parsed.segment_end_sec {
+        if (segment_end - duration).abs() > 1.0 {
+            debug!("Whisper.cppのセグメント終端 ({:.2}秒) と音声の長さ ({:.2}秒) が一致しません", segment_end, duration);
+        }
+    }
+    
+    info!("Whisper.cppによる文字起こし完了 ({:.2}秒)", duration);
+    
+    Ok(TranscriptionResult {
+        text: parsed.text,
+        language: if config.translate {
+            "en".to_string()
+        } else {
+            parsed.language.unwrap_or_else(|| "auto".to_string())
+        },
+        duration_sec: duration,
+        is_final: true,
+        sequence: 0,
+        segments: if config.timestamps { parsed.segments } else { Vec::new() },
+        no_speech_prob: None,
+        diagnostics: None,
+        fallback_engine: None,
+        epoch: None,
+    })
+}
+
+/// Whisper.cppの標準エラー出力の進捗（`--print-progress`）をログと通知で知らせる処理を作成
+///
+/// 大きなモデルで長い録音を文字起こしすると数十秒かかるため、一定の間隔でログに出し、
+/// 長引いた場合は一度だけ通知する
+fn whisper_cpp_progress_reporter(notification_level: crate::config::NotificationLevel) -> impl FnMut(&str) + Send + 'static {
+    let start = Instant::now();
+    let mut last_logged = 0;
+    let mut notified = false;
+    move |line| {
+        let Some(progress) = parse_whisper_cpp_progress(line) else {
+            trace!("Whisper.cpp: {}", line);
+            return;
+        };
+        if progress >= last_logged + WHISPER_CPP_PROGRESS_LOG_STEP {
+            last_logged = progress - progress % WHISPER_CPP_PROGRESS_LOG_STEP;
+            info!("Whisper.cppで文字起こし中... {}% ({:.1}秒経過)", progress, start.elapsed().as_secs_f32());
+        }
+        if !notified && progress < 100 && start.elapsed() >= WHISPER_CPP_PROGRESS_NOTIFY_AFTER {
+            notified = true;
+            notify(notification_level, NotificationKind::Info, "voilip", &format!("Whisper.cppで文字起こし中... {}%", progress));
+        }
+    }
+}
+
+/// Whisper.cppの進捗の行（`whisper_print_progress_callback: progress =  45%`）から進捗（パーセント）を取り出す
+pub(crate) fn parse_whisper_cpp_progress(line: &str) -> Option<u8> {
+    let (_, rest) = line.split_once("progress =")?;
+    let percent = rest.trim().strip_suffix('%')?.trim();
+    percent.parse::<u8>().ok().filter(|p| *p <= 100)
+}
+
+/// Whisper.cppの実行ファイルとモデルファイルが存在するか確認し、見つからないものを示すエラーを返す
+///
+/// 実行ファイルはパス区切りを含まない場合、PATHから探す
+pub(crate) fn check_whisper_cpp_paths(whisper_path: &Path, model_path: &Path) -> Result<()> {
+    let found = if whisper_path.components().count() > 1 {
+        whisper_path.is_file()
+    } else {
+        crate::utils::command_exists(&whisper_path.to_string_lossy())
+    };
+    if !found {
+        return Err(anyhow!("Whisper.cppの実行ファイルが見つかりません: {}", whisper_path.display()));
+    }
+    if !model_path.is_file() {
+        return Err(anyhow!("Whisper.cppのモデルファイルが見つかりません: {}", model_path.display()));
+    }
+    Ok(())
+}
+
+/// エラーメッセージ用に、空の出力を「(なし)」と表示する
+fn or_none(output: &str) -> &str {
+    if output.is_empty() { "(なし)" } else { output }
+}
+
+/// n回目（1始まり）のリトライまでの待ち時間
+///
+/// 初期値からリトライごとに倍にし、複数の録音が同時に失敗した場合に
+/// リトライが一斉に集中しないようゆらぎを加える
+pub fn retry_delay(config: &Config, retry_count: u8) -> Duration {
+    let base = config.api_retry_delay_ms as f64 * 2f64.powi(retry_count.saturating_sub(1) as i32);
+    let jitter = 1.0 + API_RETRY_JITTER * (fastrand::f64() * 2.0 - 1.0);
+    Duration::from_millis((base * jitter) as u64)
+}
+
+/// 応答ヘッダーのRetry-Afterから待ち時間を求める（秒数とHTTP日付の両方に対応）
+///
+/// リダイレクトなどでヘッダーが複数ある場合は最後の値を使う。`now`はHTTP日付との差を求める基準の時刻で、
+/// 過去の日付は待たずにリトライし、`MAX_RETRY_AFTER`より長い指定はそこで打ち切る
+fn retry_after_from_headers(headers: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = headers.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("retry-after"))
+        .map(|(_, value)| value.trim())
+        .next_back()?;
+    
+    let delay = if let Ok(sec) = value.parse::<u64>() {
+        Duration::from_secs(sec)
     } else {
-        let error = String::from_utf8(output.stderr)?;
-        Err(anyhow!("Whisper.cpp実行エラー: {}", error))
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        (date.with_timezone(&chrono::Utc) - now).to_std().unwrap_or(Duration::ZERO)
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+/// Whisper.cppに渡す引数を構築
+///
+/// voilipが必要とするオプションの後に、設定された追加の引数を順に付ける
+fn whisper_cpp_command_args(config: &Config, model_path: &Path, input_path: &Path) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![
+        "-m".into(), model_path.into(),
+        "-f".into(), input_path.into(),
+        "-oj".into(),
+        // 進捗を標準エラー出力に出させ、長い録音の文字起こし中に知らせる
+        "--print-progress".into(),
+        "-l".into(), config.language_hint().unwrap_or(AUTO_LANGUAGE).into(),
+    ];
+    if let Some(threads) = config.whisper_cpp_threads {
+        args.push("-t".into());
+        args.push(threads.to_string().into());
+    }
+    if config.translate {
+        args.push("--translate".into());
+    }
+    args.extend(config.whisper_cpp_args.iter().map(OsString::from));
+    args
+}
+
+/// 録音をタイムスタンプ付きのWAVファイルとして保存し、そのパスを返す
+fn save_recording(dir: &Path, sequence: u64, wav_data: &[u8]) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let file_name = format!("{}-{:04}.wav", Local::now().format("%Y%m%d-%H%M%S"), sequence);
+    let path = dir.join(file_name);
+    fs::write(&path, wav_data)?;
+    debug!("録音を保存しました: {}", path.display());
+    Ok(path)
+}
+
+/// 保存先ディレクトリ直下の録音（.wav）を保存順（名前順）に取得
+fn list_recordings(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut recordings: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wav"))
+        .collect();
+    recordings.sort();
+    Ok(recordings)
+}
+
+/// 保存した録音のうち最新のもののパスを取得（再文字起こし用、録音がなければ`None`）
+pub fn latest_recording(dir: &Path) -> Result<Option<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    Ok(list_recordings(dir)?.pop())
+}
+
+/// 保存した録音を、設定のエンジンで文字起こしし直す
+///
+/// `overrides`のエンジン・モデルの指定は`config`の複製にだけ適用し、指定がなければ設定のエンジンをそのまま使う。
+/// 結果には録音中と同じ整形・置換ルール・表記の整えを適用する
+pub async fn retranscribe_recording(config: &Config, recording: &Path, overrides: &CliOverrides) -> Result<TranscriptionResult> {
+    let mut config = config.clone();
+    config.apply_engine_overrides(overrides)?;
+    info!("録音を文字起こしし直します: {} ({}, モデル: {})", recording.display(), config.transcription_engine.name(), config.model);
+    
+    let samples = load_audio_file(recording, config.sample_rate)?;
+    let (_audio_tx, audio_rx) = mpsc::channel::<AudioChunk>(1);
+    let (result_tx, _) = mpsc::channel::<TranscriptionResult>(1);
+    let transcriber = Transcriber::new(config.clone(), audio_rx, result_tx, Arc::new(AtomicU64::new(0)));
+    let mut result = transcriber.transcribe_samples(&samples).await?;
+    polish_text(&mut result, &config);
+    Ok(result)
+}
+
+/// 保存した録音が`max`件を超えている場合、古いものから削除する
+///
+/// ファイル名が日時で始まるため名前順を保存順とみなす。対象は保存先ディレクトリ直下の.wavファイルだけで、
+/// 同じ名前の文字起こし結果（.txt）があれば一緒に削除する
+fn prune_recordings(dir: &Path, max: usize) -> Result<()> {
+    let recordings = list_recordings(dir)?;
+    if recordings.len() <= max {
+        return Ok(());
+    }
+    
+    let excess = recordings.len() - max;
+    for path in &recordings[..excess] {
+        fs::remove_file(path)?;
+        let transcript = path.with_extension("txt");
+        if transcript.is_file() {
+            fs::remove_file(&transcript)?;
+        }
+        debug!("古い録音を削除しました: {}", path.display());
     }
+    Ok(())
+}
+
+/// curlの`-w "\n%{http_code}"`で末尾に付けたHTTPステータスをレスポンス本文から切り離す
+pub fn split_http_status(stdout: &str) -> (&str, u16) {
+    match stdout.rsplit_once('\n') {
+        Some((body, status)) => (body, status.trim().parse().unwrap_or(0)),
+        None => (stdout, stdout.trim().parse().unwrap_or(0)),
+    }
+}
+
+/// APIのエラーレスポンスからメッセージを取得（JSONでなければ本文をそのまま使う）
+pub(crate) fn api_error_message(response: &str) -> String {
+    serde_json::from_str::<Value>(response)
+        .ok()
+        .and_then(|json| json.pointer("/error/message").and_then(|m| m.as_str()).map(|m| m.to_string()))
+        .unwrap_or_else(|| response.trim().to_string())
+}
+
+/// 成功ステータスで返ってきたAPI応答本文を解釈できなかった理由
+#[derive(Debug)]
+struct InvalidApiResponse {
+    message: String,
+    /// リトライで解消する可能性がある（途中で切れた応答やサーバー側の一時的なエラー）
+    transient: bool,
+}
+
+/// API応答本文をJSONとして解釈する
+///
+/// JSONでない本文（途中で切れた応答やプロキシのHTMLエラーページなど）と
+/// `{"error": {...}}`形式のエラーは、内容が分かるメッセージのエラーにする
+fn parse_api_response(response: &str) -> Result<Value, InvalidApiResponse> {
+    let json: Value = match serde_json::from_str(response) {
+        Ok(json) => json,
+        Err(e) => {
+            let kind = if response.trim().is_empty() {
+                "応答が空です".to_string()
+            } else if response.trim_start().starts_with('<') {
+                "応答がJSONではなくHTMLです（プロキシなどのエラーページの可能性があります）".to_string()
+            } else if e.is_eof() {
+                "応答のJSONが途中で切れています".to_string()
+            } else {
+                format!("応答がJSONではありません ({})", e)
+            };
+            return Err(InvalidApiResponse {
+                message: format!("{}: {}", kind, truncate_chars(response.trim(), 100)),
+                transient: true,
+            });
+        }
+    };
+    
+    if let Some(error) = json.get("error").filter(|error| !error.is_null()) {
+        let message = error.get("message").and_then(|m| m.as_str())
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| truncate_chars(&error.to_string(), 100));
+        // サーバー側の一時的なエラーとレート制限はリトライする
+        let transient = ["type", "code"].iter()
+            .filter_map(|key| error.get(*key).and_then(|v| v.as_str()))
+            .any(|kind| matches!(kind, "server_error" | "rate_limit_exceeded" | "timeout"));
+        return Err(InvalidApiResponse {
+            message: format!("APIがエラーを返しました: {}", message),
+            transient,
+        });
+    }
+    
+    Ok(json)
+}
+
+/// セグメントの無音確率の平均（無音確率を持つセグメントがなければNone）
+pub(crate) fn average_no_speech_prob(segments: &[Segment]) -> Option<f32> {
+    let probs: Vec<f32> = segments.iter().filter_map(|segment| segment.no_speech_prob).collect();
+    (!probs.is_empty()).then(|| probs.iter().sum::<f32>() / probs.len() as f32)
+}
+
+/// verbose_json形式のレスポンスからセグメントを取得
+pub(crate) fn parse_verbose_json_segments(json: &Value) -> Vec<Segment> {
+    json.get("segments")
+        .and_then(|s| s.as_array())
+        .map(|segments| {
+            segments.iter()
+                .filter_map(|segment| {
+                    Some(Segment {
+                        start: segment.get("start")?.as_f64()? as f32,
+                        end: segment.get("end")?.as_f64()? as f32,
+                        text: segment.get("text")?.as_str()?.trim().to_string(),
+                        no_speech_prob: segment.get("no_speech_prob").and_then(|p| p.as_f64()).map(|p| p as f32),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whisper.cppのJSON出力の解析結果
+struct WhisperCppOutput {
+    text: String,
+    language: Option<String>,
+    /// 最後のセグメントの終了位置（秒）
+    segment_end_sec: Option<f32>,
+    segments: Vec<Segment>,
+}
+
+/// verbose_jsonの言語名（例: japanese）を言語コードに変換（不明な言語はそのまま返す）
+pub(crate) fn language_code(name: &str) -> String {
+    const LANGUAGE_CODES: &[(&str, &str)] = &[
+        ("english", "en"), ("japanese", "ja"), ("chinese", "zh"), ("korean", "ko"),
+        ("german", "de"), ("french", "fr"), ("spanish", "es"), ("italian", "it"),
+        ("portuguese", "pt"), ("russian", "ru"), ("dutch", "nl"), ("polish", "pl"),
+        ("turkish", "tr"), ("ukrainian", "uk"), ("arabic", "ar"), ("hindi", "hi"),
+        ("thai", "th"), ("vietnamese", "vi"), ("indonesian", "id"),
+    ];
+    let name = name.trim().to_lowercase();
+    LANGUAGE_CODES.iter()
+        .find(|(language, _)| *language == name)
+        .map(|(_, code)| code.to_string())
+        .unwrap_or(name)
+}
+
+/// セグメントを連結する際の区切り文字（単語を空白で区切らない言語ではそのまま連結）
+pub(crate) fn segment_separator(language: Option<&str>) -> &'static str {
+    match language {
+        Some("ja") | Some("zh") | Some("ko") | Some("th") => "",
+        _ => " ",
+    }
+}
+
+/// Whisper.cppのJSON出力（-oj）を解析
+fn parse_whisper_cpp_json(json: &str) -> Result<WhisperCppOutput> {
+    let value: Value = serde_json::from_str(json)?;
+    
+    let segments = value.get("transcription")
+        .and_then(|t| t.as_array())
+        .ok_or_else(|| anyhow!("Whisper.cppの出力にtranscriptionがありません"))?;
+    
+    // 検出された言語 (result.language、なければparams.language)
+    let language = value.pointer("/result/language")
+        .or_else(|| value.pointer("/params/language"))
+        .and_then(|l| l.as_str())
+        .map(|l| l.to_string());
+    
+    let text = segments.iter()
+        .filter_map(|segment| segment.get("text").and_then(|t| t.as_str()))
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(segment_separator(language.as_deref()));
+    
+    let segment_end_sec = segments.last()
+        .and_then(|segment| segment.pointer("/offsets/to"))
+        .and_then(|to| to.as_f64())
+        .map(|ms| (ms / 1000.0) as f32);
+    
+    // オフセットはミリ秒単位
+    let timed_segments = segments.iter()
+        .filter_map(|segment| {
+            Some(Segment {
+                start: (segment.pointer("/offsets/from")?.as_f64()? / 1000.0) as f32,
+                end: (segment.pointer("/offsets/to")?.as_f64()? / 1000.0) as f32,
+                text: segment.get("text")?.as_str()?.trim().to_string(),
+                no_speech_prob: None,
+            })
+        })
+        .filter(|segment| !segment.text.is_empty())
+        .collect();
+    
+    Ok(WhisperCppOutput {
+        text,
+        language,
+        segment_end_sec,
+        segments: timed_segments,
+    })
 }
 
 /// 音声ファイルの長さ（秒）を取得
-fn audio_duration_sec(wav_data: &[u8]) -> Result<f32> {
+pub(crate) fn audio_duration_sec(wav_data: &[u8]) -> Result<f32> {
     let reader = hound::WavReader::new(Cursor::new(wav_data))?;
     let spec = reader.spec();
     
@@ -332,38 +1437,298 @@ fn audio_duration_sec(wav_data: &[u8]) -> Result<f32> {
     Ok(duration)
 }
 
-/// WAVファイルから音声データを抽出
-fn extract_audio_data_from_wav(wav_data: &[u8]) -> Result<Vec<f32>> {
-    let mut reader = hound::WavReader::new(Cursor::new(wav_data))?;
-    let spec = reader.spec();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
     
-    let mut samples = Vec::new();
+    /// OpenAI互換APIのテスト用設定（APIキーなし・リトライなし）
+    fn openai_config(api_base_url: Option<String>) -> Config {
+        Config {
+            openai_api_key: String::new(),
+            extra_openai_api_keys: Vec::new(),
+            api_base_url,
+            api_retry_max: 0,
+            ..Default::default()
+        }
+    }
     
-    match (spec.bits_per_sample, spec.sample_format) {
-        (16, SampleFormat::Int) => {
-            for sample in reader.samples::<i16>() {
-                samples.push(sample? as f32 / 32768.0);
-            }
-        },
-        (24, SampleFormat::Int) => {
-            for sample in reader.samples::<i32>() {
-                samples.push(sample? as f32 / 8388608.0);
+    /// 文字起こしAPIの代わりに`count`件のリクエストを受け付け、送られたフォームを返すサーバー
+    ///
+    /// 応答のテキストは送られたモデル名をそのまま返す
+    fn serve_transcriptions(count: usize) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            (0..count).map(|_| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let header_end = loop {
+                    if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                };
+                let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+                let content_length: usize = headers.lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |value| value.trim().parse().unwrap());
+                if headers.contains("expect: 100-continue") {
+                    stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+                }
+                while request.len() < header_end + content_length {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let form = String::from_utf8_lossy(&request[header_end..]).to_string();
+                let model = form_field(&form, "model").unwrap_or_default();
+                let body = serde_json::json!({ "text": format!("{}の結果", model) }).to_string();
+                write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+                form
+            }).collect()
+        });
+        (port, server)
+    }
+    
+    /// multipartのフォームから項目の値を取り出す
+    fn form_field(form: &str, name: &str) -> Option<String> {
+        let start = form.find(&format!("name=\"{}\"", name))?;
+        let value = form[start..].split("\r\n\r\n").nth(1)?;
+        Some(value.split("\r\n").next()?.to_string())
+    }
+    
+    #[tokio::test]
+    async fn retry_with_another_model_transcribes_the_same_recording() {
+        let (port, server) = serve_transcriptions(2);
+        let config = Config {
+            model: "whisper-1".to_string(),
+            language: "en".to_string(),
+            replacements: vec![crate::config::Replacement { from: "の結果".to_string(), to: " result".to_string(), ignore_case: false }],
+            notifications: Some(crate::config::NotificationLevel::Off),
+            ..openai_config(Some(format!("http://127.0.0.1:{}/v1", port)))
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let samples: Vec<f32> = (0..16000).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+        let recording = save_recording(dir.path(), 1, &encode_wav(&config, &samples, 1).unwrap()).unwrap();
+        
+        let first = retranscribe_recording(&config, &recording, &CliOverrides::default()).await.unwrap();
+        let overrides = CliOverrides { model: Some("gpt-4o-transcribe".to_string()), ..Default::default() };
+        let retried = retranscribe_recording(&config, &recording, &overrides).await.unwrap();
+        let forms = server.join().unwrap();
+        
+        // 設定のエンジン（OpenAI互換）のまま、モデルだけを上書きして同じ録音を送る
+        assert_eq!(first.text, "whisper-1 result");
+        assert_eq!(retried.text, "gpt-4o-transcribe result");
+        assert_eq!(form_field(&forms[0], "model").as_deref(), Some("whisper-1"));
+        assert_eq!(form_field(&forms[1], "model").as_deref(), Some("gpt-4o-transcribe"));
+        let audio = |form: &str| form[form.find("RIFF").unwrap()..].len();
+        assert_eq!(audio(&forms[0]), audio(&forms[1]));
+        assert_eq!(config.model, "whisper-1");
+    }
+    
+    #[test]
+    fn each_engine_sends_the_configured_model_unchanged() {
+        let engines = [
+            (TranscriptionEngine::GPT4o, "gpt-4o-mini-transcribe", "gpt-4o-mini-transcribe"),
+            (TranscriptionEngine::Whisper1, "whisper-1", "whisper-1"),
+            // Groqでも使えないモデル名をデフォルトに読み替えない
+            (TranscriptionEngine::Groq, "gpt-4o-transcribe", "gpt-4o-transcribe"),
+            (TranscriptionEngine::Azure { endpoint: "https://example.openai.azure.com".to_string(), deployment: "my-whisper".to_string() }, "whisper-1", "my-whisper"),
+        ];
+        let (port, server) = serve_transcriptions(engines.len());
+        let url = format!("http://127.0.0.1:{}/v1/audio/transcriptions", port);
+        let samples: Vec<f32> = (0..1600).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+        
+        for (engine, model, _) in &engines {
+            let config = Config { transcription_engine: engine.clone(), model: model.to_string(), ..openai_config(None) };
+            let wav = encode_wav(&config, &samples, 1).unwrap();
+            transcribe_with_multipart_api(&config, &url, ApiAuth::None, request_model(&config).unwrap(), &wav).unwrap();
+        }
+        let forms = server.join().unwrap();
+        
+        for ((engine, _, expected), form) in engines.iter().zip(&forms) {
+            assert_eq!(form_field(form, "model").as_deref(), Some(*expected), "{}", engine.name());
+        }
+    }
+    
+    #[test]
+    fn model_that_cannot_be_sent_is_an_engine_config_error() {
+        let empty = Config { model: " ".to_string(), ..openai_config(None) };
+        assert!(matches!(request_model(&empty), Err(VoilipError::EngineConfig { .. })));
+        
+        // 翻訳でGPT-4o系のモデルをwhisper-1に読み替えない
+        let translate = Config { model: "gpt-4o-transcribe".to_string(), translate: true, ..openai_config(None) };
+        assert!(matches!(request_model(&translate), Err(VoilipError::EngineConfig { .. })));
+        let translate = Config { model: "whisper-1".to_string(), ..translate };
+        assert_eq!(request_model(&translate).unwrap(), "whisper-1");
+    }
+    
+    #[tokio::test]
+    async fn transcribe_samples_sends_in_memory_audio_to_configured_engine() {
+        let (port, server) = serve_transcriptions(1);
+        let config = Config {
+            model: "whisper-1".to_string(),
+            replacements: vec![crate::config::Replacement { from: "の結果".to_string(), to: "で文字起こし".to_string(), ignore_case: false }],
+            ..openai_config(Some(format!("http://127.0.0.1:{}/v1", port)))
+        };
+        let sample_rate = config.sample_rate;
+        let (_audio_tx, audio_rx) = mpsc::channel(1);
+        let (result_tx, _result_rx) = mpsc::channel(1);
+        let transcriber = Transcriber::new(config, audio_rx, result_tx, Arc::new(AtomicU64::new(0)));
+        let samples: Vec<f32> = (0..sample_rate / 2).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+        
+        let result = transcriber.transcribe_samples(&samples).await.unwrap();
+        let forms = server.join().unwrap();
+        
+        assert_eq!(result.text, "whisper-1で文字起こし");
+        assert_eq!(form_field(&forms[0], "model").as_deref(), Some("whisper-1"));
+        // ファイルを経由せず、渡したサンプルがWAVとして送られる
+        let wav = &forms[0][forms[0].find("RIFF").unwrap()..];
+        assert!(wav.len() >= samples.len() * 2);
+    }
+    
+    #[tokio::test(flavor = "multi_thread")]
+    async fn one_recording_cycle_emits_events_in_order() {
+        let (port, server) = serve_transcriptions(1);
+        let config = Config {
+            recording_mode: crate::config::RecordingMode::Toggle { key: "F9".to_string() },
+            model: "whisper-1".to_string(),
+            language: "en".to_string(),
+            notifications: Some(crate::config::NotificationLevel::Off),
+            ..openai_config(Some(format!("http://127.0.0.1:{}/v1", port)))
+        };
+        let (events_tx, mut events_rx) = mpsc::channel(16);
+        let (audio_tx, audio_rx) = mpsc::channel(8);
+        let (result_tx, mut result_rx) = mpsc::channel(8);
+        let buffer = crate::audio::AudioBuffer::new(config.sample_rate as usize, audio_tx, &config);
+        buffer.set_event_sender(events_tx.clone());
+        let mut transcriber = Transcriber::new(config.clone(), audio_rx, result_tx, Arc::new(AtomicU64::new(0)));
+        transcriber.set_event_sender(events_tx);
+        let transcriber = tokio::spawn(async move { transcriber.run().await });
+        
+        // 録音の開始から停止、文字起こしの完了までの1サイクル
+        buffer.start_recording().unwrap();
+        let samples: Vec<f32> = (0..config.sample_rate).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+        buffer.push_samples(&samples, &config).unwrap();
+        buffer.stop_recording().unwrap();
+        let result = result_rx.recv().await.unwrap();
+        drop(buffer);
+        transcriber.await.unwrap().unwrap();
+        server.join().unwrap();
+        
+        let mut events = Vec::new();
+        while let Ok(event) = events_rx.try_recv() {
+            events.push(event);
+        }
+        let names: Vec<&str> = events.iter().map(|event| match event {
+            RecordingEvent::Started => "Started",
+            RecordingEvent::Stopped => "Stopped",
+            RecordingEvent::Transcribing => "Transcribing",
+            RecordingEvent::Done(_) => "Done",
+            RecordingEvent::Error(_) => "Error",
+        }).collect();
+        assert_eq!(names, vec!["Started", "Stopped", "Transcribing", "Done"]);
+        match &events[3] {
+            RecordingEvent::Done(done) => assert_eq!(done.text, result.text),
+            _ => unreachable!(),
+        }
+    }
+    
+    #[test]
+    fn encode_wav_round_trips_int16_samples() {
+        let wav = encode_wav_samples(&[1.2, 0.0, -1.2, 0.5], 1, 16000, UploadBitDepth::Int16, false).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec().bits_per_sample, 16);
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, vec![i16::MAX, 0, -i16::MAX, 16384]);
+    }
+    
+    #[test]
+    fn encode_wav_round_trips_each_bit_depth() {
+        // 読み込み時は2^(bits-1)で割るため、誤差は丸めの分と合わせて2LSB以内
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.01).sin() * 0.9).collect();
+        for (bit_depth, bits, tolerance) in [
+            (UploadBitDepth::Int16, 16, 2.0 / 32767.0),
+            (UploadBitDepth::Int24, 24, 2.0 / 8388607.0),
+            (UploadBitDepth::Float32, 32, 0.0),
+        ] {
+            let wav = encode_wav_samples(&samples, 1, 16000, bit_depth, false).unwrap();
+            let (decoded, spec) = extract_audio_data_from_wav(&wav).unwrap();
+            assert_eq!(spec.bits_per_sample, bits);
+            assert_eq!(decoded.len(), samples.len());
+            for (a, b) in samples.iter().zip(&decoded) {
+                assert!((a - b).abs() <= tolerance, "{:?}: {} != {}", bit_depth, a, b);
             }
-        },
-        (32, SampleFormat::Int) => {
-            for sample in reader.samples::<i32>() {
-                samples.push(sample? as f32 / 2147483648.0);
+        }
+        
+        // 範囲外の値は折り返さずに最大値に張り付く
+        let wav = encode_wav_samples(&[1.5, -1.5], 1, 16000, UploadBitDepth::Int24, false).unwrap();
+        let (decoded, _) = extract_audio_data_from_wav(&wav).unwrap();
+        assert!(decoded[0] > 0.999 && decoded[1] < -0.999);
+    }
+    
+    #[test]
+    fn parses_progress_from_whisper_cpp_stderr() {
+        let stderr = "\
+whisper_init_from_file_with_params_no_state: loading model from 'models/ggml-large-v3.bin'
+whisper_model_load: n_vocab       = 51866
+system_info: n_threads = 4 / 8 | AVX = 1 | AVX2 = 1 |
+main: processing 'recording.wav' (4800000 samples, 300.0 sec), 4 threads, 1 processors, 5 beams + best of 5, lang = ja, task = transcribe, timestamps = 1 ...
+whisper_print_progress_callback: progress =   5%
+[00:00:00.000 --> 00:00:04.000]   今日は会議の議事録を取ります。
+whisper_print_progress_callback: progress =  45%
+whisper_print_progress_callback: progress = 100%
+whisper_print_timings:     load time =   812.34 ms
+";
+        let progress: Vec<u8> = stderr.lines().filter_map(parse_whisper_cpp_progress).collect();
+        assert_eq!(progress, [5, 45, 100]);
+        
+        assert_eq!(parse_whisper_cpp_progress("progress = 150%"), None);
+        assert_eq!(parse_whisper_cpp_progress("progress = abc%"), None);
+        assert_eq!(parse_whisper_cpp_progress("whisper_print_progress_callback: progress = 30"), None);
+    }
+    
+    #[test]
+    fn openai_without_api_key_is_missing_api_key() {
+        let config = openai_config(None);
+        let wav = encode_wav(&config, &[0.0; 1600], 1).unwrap();
+        let error = transcribe_with_openai(&config, &wav).unwrap_err();
+        assert!(matches!(error, VoilipError::MissingApiKey { provider: "OpenAI" }));
+    }
+    
+    #[test]
+    fn openai_error_status_is_api_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // リクエストヘッダーを読んだら本文を待たずに応答する
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
             }
-        },
-        (32, SampleFormat::Float) => {
-            for sample in reader.samples::<f32>() {
-                samples.push(sample?);
+            let body = r#"{"error":{"message":"Invalid API key"}}"#;
+            write!(stream, "HTTP/1.1 401 Unauthorized\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        });
+        
+        let config = openai_config(Some(format!("http://127.0.0.1:{}/v1", port)));
+        let wav = encode_wav(&config, &[0.0; 1600], 1).unwrap();
+        let error = transcribe_with_openai(&config, &wav).unwrap_err();
+        server.join().unwrap();
+        match error {
+            VoilipError::ApiError { status, body } => {
+                assert_eq!(status, 401);
+                assert!(body.contains("Invalid API key"));
             }
-        },
-        (bits, _) => {
-            return Err(anyhow!("非対応のビット幅: {}", bits));
+            other => panic!("ApiErrorではありません: {:?}", other),
         }
     }
-    
-    Ok(samples)
-} 
+}
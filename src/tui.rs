@@ -0,0 +1,197 @@
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::events::RecordingEvent;
+use crate::session::VoilipSession;
+use crate::utils::AppState;
+
+#[cfg(feature = "tui")]
+pub use self::imp::run_dashboard;
+
+/// tuiフィーチャーを有効にしてビルドしたかどうか
+pub const AVAILABLE: bool = cfg!(feature = "tui");
+
+/// tuiフィーチャーなしでビルドした場合はエラーを返す
+#[cfg(not(feature = "tui"))]
+pub async fn run_dashboard(
+    _config: &Config,
+    _session: &mut VoilipSession,
+    _events: mpsc::Receiver<RecordingEvent>,
+    _app_state: &AppState,
+) -> Result<()> {
+    Err(anyhow::anyhow!("TUIダッシュボードを使うには、tuiフィーチャーを有効にしてビルドする必要があります (cargo build --features tui)"))
+}
+
+/// ratatuiを使ったダッシュボード
+#[cfg(feature = "tui")]
+mod imp {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+    use ratatui::{DefaultTerminal, Frame};
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::style::{Color, Style};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{Block, Gauge, Paragraph, Wrap};
+    
+    use crate::audio::InputLevel;
+    use crate::config::RecordingMode;
+    use crate::utils::{amplitude_to_db, level_meter_ratio};
+    
+    /// 画面の更新間隔
+    const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+    /// 表示する最近の文字起こし結果の件数
+    const RECENT_TRANSCRIPTS: usize = 5;
+    
+    /// ダッシュボードに表示する状態（状態の変化の通知から組み立てる）
+    #[derive(Default)]
+    struct Dashboard {
+        /// 文字起こし中の録音の数
+        transcribing: usize,
+        /// 最近の文字起こし結果（新しいものが末尾）
+        transcripts: VecDeque<String>,
+        /// 文字起こしが完了した録音の数
+        completed: usize,
+        /// 文字起こしに失敗した録音の数
+        errors: usize,
+        /// 最後のエラーの内容
+        last_error: Option<String>,
+        /// 直近の更新間隔の入力レベル
+        level: InputLevel,
+    }
+    
+    impl Dashboard {
+        /// 状態の変化を反映
+        fn apply(&mut self, event: RecordingEvent) {
+            match event {
+                RecordingEvent::Started | RecordingEvent::Stopped => {}
+                RecordingEvent::Transcribing => self.transcribing += 1,
+                RecordingEvent::Done(result) => {
+                    self.transcribing = self.transcribing.saturating_sub(1);
+                    self.completed += 1;
+                    let text = result.text.trim();
+                    if !text.is_empty() {
+                        if self.transcripts.len() == RECENT_TRANSCRIPTS {
+                            self.transcripts.pop_front();
+                        }
+                        self.transcripts.push_back(text.to_string());
+                    }
+                }
+                RecordingEvent::Error(message) => {
+                    self.transcribing = self.transcribing.saturating_sub(1);
+                    self.errors += 1;
+                    self.last_error = Some(message);
+                }
+            }
+        }
+    }
+    
+    /// 録音・文字起こしの状態をダッシュボードに表示し、終了が要求されるまで更新を続ける
+    ///
+    /// 端末を生モードにするため、Ctrl+Cはシグナルではなくキー入力として受け取り、
+    /// `app_state`の実行中フラグを下ろす。終了時は端末を元の状態に戻す
+    pub async fn run_dashboard(
+        config: &Config,
+        session: &mut VoilipSession,
+        mut events: mpsc::Receiver<RecordingEvent>,
+        app_state: &AppState,
+    ) -> Result<()> {
+        let mut terminal = ratatui::try_init()?;
+        let result = dashboard_loop(&mut terminal, config, session, &mut events, app_state).await;
+        ratatui::restore();
+        result
+    }
+    
+    /// 状態の反映・描画・キー入力の処理を繰り返す
+    async fn dashboard_loop(
+        terminal: &mut DefaultTerminal,
+        config: &Config,
+        session: &mut VoilipSession,
+        events: &mut mpsc::Receiver<RecordingEvent>,
+        app_state: &AppState,
+    ) -> Result<()> {
+        let mut dashboard = Dashboard::default();
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        
+        while app_state.is_running() {
+            interval.tick().await;
+            
+            while let Ok(event) = events.try_recv() {
+                dashboard.apply(event);
+            }
+            // 入力デバイスが外れた場合などは音声ストリームを作り直し、復旧しなければ終了する
+            session.check_stream()?;
+            dashboard.level = session.level_meter().take();
+            let recording = session.is_recording();
+            terminal.draw(|frame| draw(frame, config, &dashboard, recording))?;
+            
+            while event::poll(Duration::ZERO)? {
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)) {
+                    app_state.set_running(false);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// ダッシュボードを描画
+    fn draw(frame: &mut Frame, config: &Config, dashboard: &Dashboard, recording: bool) {
+        let [status_area, level_area, transcripts_area, help_area] = Layout::vertical([
+            Constraint::Length(6),
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        
+        let hotkey = match &config.recording_mode {
+            RecordingMode::VoiceActivity { .. } => "なし".to_string(),
+            RecordingMode::PushToTalk { key } | RecordingMode::Toggle { key } | RecordingMode::Hybrid { key, .. } => key.clone(),
+            RecordingMode::SeparateKeys { start_key, stop_key } => format!("{} / {}", start_key, stop_key),
+        };
+        let (state, color) = if recording {
+            ("● 録音中", Color::Red)
+        } else if dashboard.transcribing > 0 {
+            ("… 文字起こし中", Color::Yellow)
+        } else {
+            ("待機中", Color::Green)
+        };
+        let errors = match &dashboard.last_error {
+            Some(message) => format!("{}件 (最後: {})", dashboard.errors, message),
+            None => format!("{}件", dashboard.errors),
+        };
+        
+        let status = Paragraph::new(vec![
+            Line::from(format!("録音モード: {}  ホットキー: {}", config.recording_mode.name(), hotkey)),
+            Line::from(format!("エンジン: {}  モデル: {}  言語: {}", config.transcription_engine.name(), config.model, config.language)),
+            Line::from(vec![Span::raw("状態: "), Span::styled(state, Style::default().fg(color))]),
+            Line::from(format!("完了: {}件  エラー: {}", dashboard.completed, errors)),
+        ])
+        .block(Block::bordered().title(" voilip "));
+        frame.render_widget(status, status_area);
+        
+        let level = dashboard.level;
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(" 入力レベル "))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(level_meter_ratio(level.rms).clamp(0.0, 1.0) as f64)
+            .label(format!("RMS {:.1}dB  ピーク {:.1}dB", amplitude_to_db(level.rms), amplitude_to_db(level.peak)));
+        frame.render_widget(gauge, level_area);
+        
+        // 新しいものを上に表示
+        let transcripts: Vec<Line> = dashboard.transcripts.iter().rev().map(|t| Line::from(t.as_str())).collect();
+        let transcripts = Paragraph::new(transcripts)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(" 最近の文字起こし "));
+        frame.render_widget(transcripts, transcripts_area);
+        
+        frame.render_widget(Paragraph::new("q / Esc / Ctrl+C で終了"), help_area);
+    }
+}
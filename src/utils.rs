@@ -1,9 +1,16 @@
 use std::time::{Duration, Instant};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use anyhow::Result;
+use std::sync::{Arc, OnceLock};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use anyhow::{Result, anyhow};
+use regex::{NoExpand, Regex};
+#[cfg(unix)]
 use tokio::signal::unix::{signal, SignalKind};
-use tracing::info;
+use tracing::{info, warn, Level};
+
+use crate::config::{Replacement, TextCase};
 
 /// アプリケーションの状態管理
 pub struct AppState {
@@ -48,6 +55,7 @@ impl Default for AppState {
 }
 
 /// SIGINTシグナル（Ctrl+C）のハンドラセットアップ
+#[cfg(unix)]
 pub async fn setup_signal_handler(app_state: Arc<AppState>) -> Result<()> {
     // SIGINT (Ctrl+C) のハンドラを設定
     let mut sigint = signal(SignalKind::interrupt())?;
@@ -62,6 +70,85 @@ pub async fn setup_signal_handler(app_state: Arc<AppState>) -> Result<()> {
     Ok(())
 }
 
+/// Ctrl+Cのハンドラセットアップ (Windows)
+#[cfg(windows)]
+pub async fn setup_signal_handler(app_state: Arc<AppState>) -> Result<()> {
+    let state = app_state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Ctrl+Cハンドラのエラー: {}", e);
+            return;
+        }
+        info!("Ctrl+Cを受信しました。終了します...");
+        state.set_running(false);
+    });
+    
+    Ok(())
+}
+
+/// 外部コマンドをタイムアウト付きで実行
+///
+/// タイムアウトした場合は子プロセスをkillしてエラーを返す
+pub fn run_command_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output> {
+    run_command_with_stderr_lines(command, timeout, |_| {})
+}
+
+/// 外部コマンドをタイムアウト付きで実行し、標準エラー出力を1行ずつ`on_stderr_line`に渡す
+///
+/// 長時間かかるコマンドの進捗表示を実行中に読み取るために使う（渡した行も結果の標準エラー出力に含める）
+pub fn run_command_with_stderr_lines<F>(command: &mut Command, timeout: Duration, mut on_stderr_line: F) -> Result<Output>
+where
+    F: FnMut(&str) + Send + 'static,
+{
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    
+    // パイプが詰まらないよう、標準出力/標準エラーは別スレッドで読み続ける
+    let mut stdout = child.stdout.take().ok_or_else(|| anyhow!("標準出力の取得に失敗しました"))?;
+    let mut stderr = child.stderr.take().ok_or_else(|| anyhow!("標準エラーの取得に失敗しました"))?;
+    let stdout_thread = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        buf
+    });
+    let stderr_thread = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let mut reader = BufReader::new(&mut stderr);
+        let mut line = Vec::new();
+        while let Ok(n) = reader.read_until(b'\n', &mut line) {
+            if n == 0 {
+                break;
+            }
+            on_stderr_line(String::from_utf8_lossy(&line).trim_end());
+            buf.append(&mut line);
+        }
+        buf
+    });
+    
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("コマンドがタイムアウトしました ({}秒)", timeout.as_secs()));
+        }
+        
+        thread::sleep(Duration::from_millis(50));
+    };
+    
+    let stdout = stdout_thread.join().unwrap_or_default();
+    let stderr = stderr_thread.join().unwrap_or_default();
+    
+    Ok(Output { status, stdout, stderr })
+}
+
 /// 環境変数から設定値を取得（デフォルト値付き）
 pub fn get_env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
     std::env::var(key)
@@ -70,6 +157,16 @@ pub fn get_env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
         .unwrap_or(default)
 }
 
+/// 外部コマンドがPATH上にあるか確認
+pub fn command_exists(name: &str) -> bool {
+    let finder = if cfg!(target_os = "windows") { "where" } else { "which" };
+    Command::new(finder)
+        .arg(name)
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
 /// システム情報を取得してログに出力
 pub fn log_system_info() {
     info!("voilip バージョン: {}", env!("CARGO_PKG_VERSION"));
@@ -87,13 +184,346 @@ pub fn log_system_info() {
     }
 }
 
+/// Whisper.cppのタイムスタンプ (例: `[00:00.000 --> 00:02.000]`) に一致する正規表現
+fn timestamp_regex() -> &'static Regex {
+    static TIMESTAMP_RE: OnceLock<Regex> = OnceLock::new();
+    TIMESTAMP_RE.get_or_init(|| {
+        Regex::new(r"^\s*\[(\d{2}:)?\d{2}:\d{2}[.,]\d{3}\s*-->\s*(\d{2}:)?\d{2}:\d{2}[.,]\d{3}\]\s*")
+            .expect("タイムスタンプの正規表現が不正です")
+    })
+}
+
+/// ログレベルを決定
+///
+/// 優先順位は `--log-level` > `-v`/`-q` > 環境変数`RUST_LOG` > INFO
+pub fn resolve_log_level(log_level: Option<&str>, verbose: u8, quiet: bool) -> Result<Level> {
+    if let Some(level) = log_level {
+        return parse_log_level(level).ok_or_else(|| anyhow!("不明なログレベル: {}", level));
+    }
+    
+    match (verbose, quiet) {
+        (0, true) => return Ok(Level::WARN),
+        (1, _) => return Ok(Level::DEBUG),
+        (2.., _) => return Ok(Level::TRACE),
+        _ => {}
+    }
+    
+    // RUST_LOGは単純なレベル指定のみ対応（不正な値は無視）
+    Ok(std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|level| parse_log_level(&level))
+        .unwrap_or(Level::INFO))
+}
+
+/// ログレベルの文字列を解析
+fn parse_log_level(level: &str) -> Option<Level> {
+    match level.trim().to_lowercase().as_str() {
+        "error" => Some(Level::ERROR),
+        "warn" | "warning" => Some(Level::WARN),
+        "info" => Some(Level::INFO),
+        "debug" => Some(Level::DEBUG),
+        "trace" => Some(Level::TRACE),
+        _ => None,
+    }
+}
+
+/// レベルメーターで表示する最小レベル（dB）
+const LEVEL_METER_MIN_DB: f32 = -60.0;
+
+/// 振幅をdBに変換（0は-∞の代わりにLEVEL_METER_MIN_DBとする）
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        (20.0 * amplitude.log10()).max(LEVEL_METER_MIN_DB)
+    } else {
+        LEVEL_METER_MIN_DB
+    }
+}
+
+/// 振幅をレベルメーター上の位置（-60dBを0.0、0dBを1.0とする割合）に変換
+pub fn level_meter_ratio(amplitude: f32) -> f32 {
+    (amplitude_to_db(amplitude) - LEVEL_METER_MIN_DB) / -LEVEL_METER_MIN_DB
+}
+
+/// 入力レベルをバー表示の文字列に変換
+///
+/// RMSを`█`、ピークまでを`░`で描き、無音判定の閾値の位置に`|`を表示する。
+/// 小さな音も見えるよう、-60dB〜0dBの対数スケールで描画する
+pub fn render_level_meter(rms: f32, peak: f32, threshold: f32, width: usize) -> String {
+    let position = |amplitude: f32| {
+        ((level_meter_ratio(amplitude) * width as f32).round() as usize).min(width)
+    };
+    let rms_pos = position(rms);
+    let peak_pos = position(peak);
+    let threshold_pos = position(threshold).min(width.saturating_sub(1));
+    
+    let bar: String = (0..width)
+        .map(|i| {
+            if i == threshold_pos {
+                '|'
+            } else if i < rms_pos {
+                '█'
+            } else if i < peak_pos {
+                '░'
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    
+    let status = if peak > threshold { "音声" } else { "無音" };
+    format!("[{}] RMS {:>6.1}dB  ピーク {:>6.1}dB  {}", bar, amplitude_to_db(rms), amplitude_to_db(peak), status)
+}
+
+/// 文字数（バイト数ではない）で切り詰め、切り詰めた場合は末尾に"..."を付ける
+pub fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+/// `\n`、`\t`、`\\`のエスケープを展開（それ以外の`\`はそのまま残す）
+pub fn expand_escapes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    
+    result
+}
+
+/// 文の終わりとみなす記号
+const SENTENCE_TERMINATORS: &[char] = &['.', '!', '?', '。', '！', '？'];
+
+/// 文字起こし結果の大文字・小文字を整える
+///
+/// 文頭を大文字にする場合は、先頭と文末の記号の後の最初の文字だけを変える。
+/// 大文字・小文字のない日本語などはそのまま
+pub fn apply_text_case(text: &str, text_case: TextCase) -> String {
+    match text_case {
+        TextCase::None => text.to_string(),
+        TextCase::Lower => text.to_lowercase(),
+        TextCase::SentenceCase => {
+            let mut result = String::with_capacity(text.len());
+            let mut sentence_start = true;
+            for c in text.chars() {
+                if SENTENCE_TERMINATORS.contains(&c) {
+                    result.push(c);
+                    sentence_start = true;
+                } else if sentence_start && !c.is_whitespace() {
+                    result.extend(c.to_uppercase());
+                    sentence_start = false;
+                } else {
+                    result.push(c);
+                }
+            }
+            result
+        }
+    }
+}
+
+/// 日本語の文字起こし結果の表記を整える
+///
+/// 全角数字を半角にし、句読点（、。！？）の前後と日本語の文字どうし（数字を含む）の間の空白を取り除く。
+/// 英単語の間など、それ以外の空白はそのまま残す
+pub fn normalize_japanese(text: &str) -> String {
+    let chars: Vec<char> = text.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            _ => c,
+        })
+        .collect();
+    let is_space = |c: char| c == ' ' || c == '\u{3000}';
+    let is_punctuation = |c: char| "、。！？".contains(c);
+    
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_space(chars[i]) {
+            result.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        
+        // 連続する空白をまとめて、前後の文字で残すかどうかを決める
+        let end = chars[i..].iter().position(|&c| !is_space(c)).map_or(chars.len(), |n| i + n);
+        let prev = result.chars().next_back();
+        let next = chars.get(end).copied();
+        // 数字と日本語の間（「3 件」など）も日本語の文字どうしと同じく詰める
+        let joinable = |c: char| is_cjk(c) || c.is_ascii_digit();
+        let drop = prev.is_some_and(is_punctuation)
+            || next.is_some_and(is_punctuation)
+            || (prev.is_some_and(joinable) && next.is_some_and(joinable)
+                && (prev.is_some_and(is_cjk) || next.is_some_and(is_cjk)));
+        if !drop {
+            result.extend(&chars[i..end]);
+        }
+        i = end;
+    }
+    
+    result
+}
+
+/// 日本語・中国語の文字（かな・漢字・全角の記号）か
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'   // 句読点・括弧などの記号
+        | '\u{3040}'..='\u{30FF}' // ひらがな・カタカナ
+        | '\u{3400}'..='\u{4DBF}' // 漢字（拡張A）
+        | '\u{4E00}'..='\u{9FFF}' // 漢字
+        | '\u{F900}'..='\u{FAFF}' // 互換漢字
+        | '\u{FF01}'..='\u{FF9F}' // 全角英数・記号、半角カタカナ
+    )
+}
+
+/// 置換ルールを順に適用
+///
+/// 置換対象の端が英数字の場合は単語境界でのみ一致させる（"air"が"airplane"に一致しない）。
+/// 日本語などの非ASCII文字は境界として扱うため、日本語の文中でも英単語を置換できる
+pub fn apply_replacements(text: &str, replacements: &[Replacement]) -> String {
+    let mut result = text.to_string();
+    
+    for replacement in replacements.iter().filter(|r| !r.from.is_empty()) {
+        let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let mut pattern = String::new();
+        if replacement.ignore_case {
+            pattern.push_str("(?i)");
+        }
+        if replacement.from.starts_with(is_word_char) {
+            pattern.push_str(r"(?-u:\b)");
+        }
+        pattern.push_str(&regex::escape(&replacement.from));
+        if replacement.from.ends_with(is_word_char) {
+            pattern.push_str(r"(?-u:\b)");
+        }
+        
+        match Regex::new(&pattern) {
+            Ok(re) => result = re.replace_all(&result, NoExpand(&replacement.to)).into_owned(),
+            Err(e) => warn!("置換ルールの正規表現エラー ({}): {}", replacement.from, e),
+        }
+    }
+    
+    result
+}
+
 /// テキストのシンプルな整形
 pub fn format_text(text: &str) -> String {
+    // 行頭のタイムスタンプを除去し、先頭の空行を読み飛ばす
+    let lines: Vec<String> = text.lines()
+        .map(|line| timestamp_regex().replace(line, "").into_owned())
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    
     // 空白文字の連続を1つに置換
-    let mut result = text.trim().to_string();
+    let mut result = lines.join("\n").trim().to_string();
     while result.contains("  ") {
         result = result.replace("  ", " ");
     }
     
     result
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn run_command_with_timeout_kills_slow_command() {
+        let start = Instant::now();
+        let error = run_command_with_timeout(Command::new("sleep").arg("60"), Duration::from_secs(1)).unwrap_err();
+        assert!(error.to_string().contains("タイムアウト"), "{}", error);
+        assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+    }
+    
+    #[test]
+    fn truncate_chars_keeps_multibyte_characters_whole() {
+        // 30バイト目が「ね」の途中になる
+        let text = "a今日はいい天気ですね。これから音声入力のテストをしています。よろしくお願いします。";
+        assert!(!text.is_char_boundary(30));
+        
+        let truncated = truncate_chars(text, 30);
+        assert_eq!(truncated, "a今日はいい天気ですね。これから音声入力のテストをしています...");
+        assert_eq!(truncate_chars(text, 10), "a今日はいい天気です...");
+        assert_eq!(truncate_chars("短い", 30), "短い");
+    }
+    
+    #[test]
+    fn format_text_strips_whisper_cpp_timestamps() {
+        let raw = "\n  \n[00:00.000 --> 00:02.000]  こんにちは\n[00:00:02,000 --> 00:00:04,500] 今日は  いい天気です\n";
+        assert_eq!(format_text(raw), "こんにちは\n今日は いい天気です");
+        // 本文中の角括弧はタイムスタンプとして扱わない
+        assert_eq!(format_text("[笑] それは  いい"), "[笑] それは いい");
+    }
+    
+    #[test]
+    fn format_text_collapses_repeated_spaces() {
+        assert_eq!(format_text("  hello    world  "), "hello world");
+        assert_eq!(format_text("a  b   c"), "a b c");
+    }
+    
+    #[test]
+    fn replacements_apply_in_order_on_word_boundaries() {
+        let rule = |from: &str, to: &str, ignore_case: bool| Replacement { from: from.to_string(), to: to.to_string(), ignore_case };
+        
+        // 前のルールの結果に後のルールが適用される
+        let rules = [rule("cube cuttle", "kubectl", false), rule("kubectl", "kubectl(k8s)", false)];
+        assert_eq!(apply_replacements("cube cuttle get pods", &rules), "kubectl(k8s) get pods");
+        
+        // 英単語の一部には一致しないが、日本語に隣接していれば一致する
+        let rules = [rule("air", "エア", false)];
+        assert_eq!(apply_replacements("airplane air hair", &rules), "airplane エア hair");
+        assert_eq!(apply_replacements("今日はairの話", &rules), "今日はエアの話");
+        
+        // 大文字小文字の区別はルールごとに指定する
+        assert_eq!(apply_replacements("Voip and voip", &[rule("voip", "voilip", false)]), "Voip and voilip");
+        assert_eq!(apply_replacements("Voip and VOIP", &[rule("voip", "voilip", true)]), "voilip and voilip");
+        
+        // 置換後の文字列の$はキャプチャ参照として扱わない
+        assert_eq!(apply_replacements("price", &[rule("price", "$1", false)]), "$1");
+    }
+    
+    #[test]
+    fn normalize_japanese_converts_full_width_digits() {
+        assert_eq!(normalize_japanese("２０２４年１２月"), "2024年12月");
+        // 漢数字はそのまま
+        assert_eq!(normalize_japanese("三つ目"), "三つ目");
+    }
+    
+    #[test]
+    fn normalize_japanese_strips_spaces_around_punctuation_and_between_cjk() {
+        assert_eq!(normalize_japanese("はい 、 そうです 。"), "はい、そうです。");
+        assert_eq!(normalize_japanese("本当 ？　 すごい ！"), "本当？すごい！");
+        assert_eq!(normalize_japanese("今日は  いい 天気"), "今日はいい天気");
+        assert_eq!(normalize_japanese("３ 件 あります"), "3件あります");
+        // 英単語の間や英単語と日本語の間の空白は残す
+        assert_eq!(normalize_japanese("Rust のコード and more"), "Rust のコード and more");
+        assert_eq!(normalize_japanese("version 2 です"), "version 2です");
+    }
+    
+    #[test]
+    fn text_case_modes() {
+        let text = "hello world. the API is ready! is it?yes";
+        assert_eq!(apply_text_case(text, TextCase::None), text);
+        assert_eq!(apply_text_case("Hello NASA World", TextCase::Lower), "hello nasa world");
+        // 文中の略語は変えず、文頭だけを大文字にする
+        assert_eq!(apply_text_case(text, TextCase::SentenceCase), "Hello world. The API is ready! Is it?Yes");
+        
+        let japanese = "こんにちは。今日はいい天気ですね！";
+        assert_eq!(apply_text_case(japanese, TextCase::SentenceCase), japanese);
+        assert_eq!(apply_text_case("はい。ok です", TextCase::SentenceCase), "はい。Ok です");
+    }
+}
@@ -0,0 +1,201 @@
+use webrtc_vad::{SampleRate, VadMode};
+
+use crate::audio::LinearResampler;
+
+/// 判定に使うフレーム長（ミリ秒、WebRTC VADは10/20/30msのみ対応）
+const VAD_FRAME_MS: usize = 20;
+/// WebRTC VADが対応していないサンプルレートの場合にリサンプリングする先のレート
+const FALLBACK_RATE: u32 = 16000;
+
+/// 積極度ごとの「発話開始とみなすのに必要な連続音声フレーム数」
+const ONSET_FRAMES: [u32; 4] = [1, 2, 2, 3];
+
+/// libfvadのハンドル
+///
+/// `webrtc_vad::Vad`は生ポインタを保持するため`Send`にならないが、
+/// 状態はインスタンスごとのヒープ領域のみでスレッドに依存しない。
+/// 音声コールバックのスレッドからは`Mutex`越しにしか触らないため、スレッド間の移動を許可する
+struct FvadHandle(webrtc_vad::Vad);
+
+unsafe impl Send for FvadHandle {}
+
+/// フレーム単位の音声区間検出（VAD）
+///
+/// 入力をモノラルの20msフレームに区切り、WebRTC VAD（libfvad）で音声かどうかを判定する。
+/// `min_level`未満の小さな音は常に無音とみなし、数フレーム連続した場合のみ発話とするため、
+/// 単発のポップノイズでは反応しない
+pub struct Vad {
+    /// WebRTC VAD本体
+    fvad: FvadHandle,
+    /// 積極度（0〜3、大きいほど雑音を音声と判定しにくい）
+    aggressiveness: usize,
+    /// 入力のチャンネル数（インターリーブ済みのサンプルを平均してモノラルにする）
+    channels: usize,
+    /// 入力レートがVADの対応レートでない場合のリサンプラー
+    resampler: Option<LinearResampler>,
+    /// 1フレームのサンプル数
+    frame_len: usize,
+    /// 絶対的な最小エネルギー（RMS）
+    min_level: f32,
+    /// チャンネルをまたいで途中まで届いた入力フレーム
+    partial_frame: Vec<f32>,
+    /// 前回の呼び出しで余ったモノラルサンプル
+    pending: Vec<f32>,
+    /// 連続した音声フレーム数
+    speech_run: u32,
+}
+
+impl Vad {
+    /// 新しいVADを作成
+    ///
+    /// `min_level`はこれ未満のRMSを常に無音とみなす下限値
+    pub fn new(sample_rate: u32, channels: u16, aggressiveness: u8, min_level: f32) -> Self {
+        let aggressiveness = (aggressiveness as usize).min(3);
+        let (vad_rate, frame_rate, resampler) = match vad_sample_rate(sample_rate) {
+            Some(rate) => (rate, sample_rate, None),
+            None => (SampleRate::Rate16kHz, FALLBACK_RATE, Some(LinearResampler::new(sample_rate, FALLBACK_RATE, 1))),
+        };
+        let mode = match aggressiveness {
+            0 => VadMode::Quality,
+            1 => VadMode::LowBitrate,
+            2 => VadMode::Aggressive,
+            _ => VadMode::VeryAggressive,
+        };
+        let frame_len = frame_rate as usize * VAD_FRAME_MS / 1000;
+
+        Self {
+            fvad: FvadHandle(webrtc_vad::Vad::new_with_rate_and_mode(vad_rate, mode)),
+            aggressiveness,
+            channels: channels.max(1) as usize,
+            resampler,
+            frame_len,
+            min_level,
+            partial_frame: Vec::with_capacity(channels.max(1) as usize),
+            pending: Vec::with_capacity(frame_len),
+            speech_run: 0,
+        }
+    }
+
+    /// サンプルを追加し、完成したフレームのいずれかが発話と判定されたかを返す
+    pub fn process<I: IntoIterator<Item = f32>>(&mut self, samples: I) -> bool {
+        let mut mono = Vec::new();
+        for sample in samples {
+            self.partial_frame.push(sample);
+            if self.partial_frame.len() == self.channels {
+                mono.push(self.partial_frame.iter().sum::<f32>() / self.channels as f32);
+                self.partial_frame.clear();
+            }
+        }
+        if let Some(resampler) = &mut self.resampler {
+            mono = resampler.process(&mono);
+        }
+
+        let mut detected = false;
+        for sample in mono {
+            self.pending.push(sample);
+            if self.pending.len() == self.frame_len {
+                if self.process_frame() {
+                    detected = true;
+                }
+                self.pending.clear();
+            }
+        }
+
+        detected
+    }
+
+    /// 溜まった1フレームを判定
+    fn process_frame(&mut self) -> bool {
+        let frame = &self.pending;
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        let pcm: Vec<i16> = frame.iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+
+        // フレーム長は対応レートから計算しているため、判定に失敗することはない
+        let voiced = self.fvad.0.is_voice_segment(&pcm).unwrap_or(false);
+
+        if voiced && rms >= self.min_level {
+            self.speech_run += 1;
+        } else {
+            self.speech_run = 0;
+        }
+
+        self.speech_run >= ONSET_FRAMES[self.aggressiveness]
+    }
+}
+
+/// WebRTC VADがそのまま扱えるサンプルレートかどうか
+fn vad_sample_rate(sample_rate: u32) -> Option<SampleRate> {
+    match sample_rate {
+        8000 => Some(SampleRate::Rate8kHz),
+        16000 => Some(SampleRate::Rate16kHz),
+        32000 => Some(SampleRate::Rate32kHz),
+        48000 => Some(SampleRate::Rate48kHz),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 16000;
+
+    /// 基本周波数と倍音を重ね、音節ごとに振幅を揺らした有声音に近い信号
+    fn voiced(rate: u32, seconds: f32) -> Vec<f32> {
+        let len = (rate as f32 * seconds) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / rate as f32;
+                let syllable = 0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin();
+                let tone: f32 = (1..=10)
+                    .map(|h| (2.0 * PI * 140.0 * h as f32 * t).sin() / h as f32)
+                    .sum();
+                0.15 * syllable * tone
+            })
+            .collect()
+    }
+
+    /// 再現可能な白色雑音
+    fn hiss(rate: u32, seconds: f32, amplitude: f32) -> Vec<f32> {
+        let mut rng = fastrand::Rng::with_seed(7);
+        (0..(rate as f32 * seconds) as usize)
+            .map(|_| (rng.f32() * 2.0 - 1.0) * amplitude)
+            .collect()
+    }
+
+    #[test]
+    fn speech_is_detected_and_silence_is_not() {
+        let mut vad = Vad::new(RATE, 1, 2, 0.01);
+        assert!(!vad.process(vec![0.0; RATE as usize]));
+        assert!(vad.process(voiced(RATE, 1.0)));
+        assert!(!vad.process(vec![0.0; RATE as usize]));
+    }
+
+    #[test]
+    fn steady_hiss_and_single_pop_are_not_speech() {
+        let mut vad = Vad::new(RATE, 1, 3, 0.01);
+        // 定常的な雑音は最初の数秒で雑音モデルに取り込まれる
+        vad.process(hiss(RATE, 3.0, 0.03));
+        assert!(!vad.process(hiss(RATE, 1.0, 0.03)));
+        let noisy_speech: Vec<f32> = voiced(RATE, 1.0).iter().zip(hiss(RATE, 1.0, 0.03)).map(|(v, n)| v + n).collect();
+        assert!(vad.process(noisy_speech));
+
+        // 1フレームだけの大きなクリック音
+        let mut pop = vec![0.0; RATE as usize / 2];
+        pop.extend(voiced(RATE, 0.02).iter().map(|s| s * 4.0));
+        pop.extend(vec![0.0; RATE as usize / 2]);
+        assert!(!vad.process(pop));
+    }
+
+    #[test]
+    fn unsupported_rate_and_stereo_input_are_converted_before_detection() {
+        let mut vad = Vad::new(44100, 2, 2, 0.01);
+        let stereo = |mono: Vec<f32>| mono.into_iter().flat_map(|s| [s, s]).collect::<Vec<_>>();
+
+        assert!(!vad.process(stereo(vec![0.0; 44100])));
+        assert!(vad.process(stereo(voiced(44100, 1.0))));
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::env;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::config::{AZURE_API_KEY_ENV, DEFAULT_API_BASE_URL, Config, TranscriptionEngine};
+use crate::transcriber::{AZURE_API_VERSION, ApiAuth, CURL_TIMEOUT_EXIT_CODE, api_error_message, split_http_status};
+use crate::utils::run_command_with_timeout;
+
+/// Groqのモデル一覧APIのURL
+const GROQ_MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
+/// curlがホスト名を解決できなかった場合の終了コード
+const CURL_RESOLVE_EXIT_CODE: i32 = 6;
+/// curlがサーバーに接続できなかった場合の終了コード
+const CURL_CONNECT_EXIT_CODE: i32 = 7;
+
+/// 設定のエンジンのモデル一覧APIを呼び出し、APIキーが有効か確認する
+///
+/// 文字起こしよりも軽い認証付きのGETで確かめる。`list_models`の場合は、
+/// 返ってきたモデルのうち文字起こしに使えるものを表示する
+pub fn run_verify(config: &Config, list_models: bool) -> Result<()> {
+    let groq_api_key = if config.groq_api_key.is_empty() {
+        env::var("GROQ_API_KEY").unwrap_or_default()
+    } else {
+        config.groq_api_key.clone()
+    };
+    let azure_api_key = env::var(AZURE_API_KEY_ENV).unwrap_or_default();
+    
+    let (service, url, auth) = match &config.transcription_engine {
+        TranscriptionEngine::GPT4o | TranscriptionEngine::Whisper1 => {
+            let base = config.api_base_url.as_deref().unwrap_or(DEFAULT_API_BASE_URL);
+            let auth = if config.openai_api_key.is_empty() { ApiAuth::None } else { ApiAuth::Bearer(&config.openai_api_key) };
+            ("OpenAI", format!("{}/models", base.trim_end_matches('/')), auth)
+        }
+        TranscriptionEngine::Groq => ("Groq", GROQ_MODELS_URL.to_string(), ApiAuth::Bearer(&groq_api_key)),
+        TranscriptionEngine::Azure { endpoint, .. } => {
+            let url = format!("{}/openai/models?api-version={}", endpoint.trim_end_matches('/'), AZURE_API_VERSION);
+            ("Azure OpenAI", url, ApiAuth::ApiKey(&azure_api_key))
+        }
+        engine => {
+            return Err(anyhow!("{}エンジンではモデル一覧APIで確認できません（voilip doctorで設定を確認してください）", engine.name()));
+        }
+    };
+    if matches!(auth, ApiAuth::Bearer("") | ApiAuth::ApiKey("")) && !config.overrides_auth_header() {
+        return Err(anyhow!("{}のAPIキーが設定されていません", service));
+    }
+    
+    println!("{}のAPIキーを確認しています: {}", service, url);
+    let mut command = Command::new("curl");
+    command
+        .arg("-s")
+        .arg("--max-time").arg(config.request_timeout_sec.to_string())
+        .arg("-w").arg("\n%{http_code}");
+    if let Some(proxy) = config.proxy_url() {
+        command.arg("-x").arg(proxy);
+    }
+    if let Some(header) = auth.header().filter(|_| !config.overrides_auth_header()) {
+        command.arg("-H").arg(header);
+    }
+    // ゲートウェイが要求するヘッダーは文字起こしと同じく付ける
+    for (name, value) in &config.extra_headers {
+        command.arg("-H").arg(format!("{}: {}", name, value));
+    }
+    command.arg(&url);
+    let output = run_command_with_timeout(&mut command, Duration::from_secs(config.request_timeout_sec + 5))?;
+    
+    // curl自体の失敗は認証ではなく接続の問題
+    if !output.status.success() {
+        let code = output.status.code().unwrap_or(-1);
+        let reason = match code {
+            CURL_RESOLVE_EXIT_CODE => "ホスト名を解決できません".to_string(),
+            CURL_CONNECT_EXIT_CODE => "サーバーに接続できません".to_string(),
+            CURL_TIMEOUT_EXIT_CODE => format!("{}秒でタイムアウトしました", config.request_timeout_sec),
+            _ => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        };
+        return Err(anyhow!("APIに接続できません (curl終了コード {}): {}。ネットワークやプロキシの設定を確認してください", code, reason));
+    }
+    
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = split_http_status(&stdout);
+    match status {
+        200..=299 => {}
+        401 => return Err(anyhow!("APIキーが無効です (HTTP 401): {}", api_error_message(body))),
+        403 => return Err(anyhow!("APIキーにアクセス権限がありません (HTTP 403): {}", api_error_message(body))),
+        _ => return Err(anyhow!("API呼び出しエラー (HTTP {}): {}", status, api_error_message(body))),
+    }
+    println!("APIキーは有効です (HTTP {})", status);
+    
+    if list_models {
+        let models = transcription_models(body);
+        if models.is_empty() {
+            println!("文字起こしに使えるモデルは見つかりませんでした");
+        } else {
+            println!("文字起こしに使えるモデル:");
+            for model in models {
+                println!("  {}", model);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// モデル一覧APIの応答から文字起こしに使えるモデルのIDを取り出す（名前順）
+fn transcription_models(body: &str) -> Vec<String> {
+    let mut models: Vec<String> = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|json| json["data"].as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|model| model["id"].as_str())
+        .filter(|id| id.contains("whisper") || id.contains("transcribe"))
+        .map(|id| id.to_string())
+        .collect();
+    models.sort();
+    models
+}
@@ -0,0 +1,224 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tracing::{debug, info, trace, warn};
+
+use crate::config::{AUTO_LANGUAGE, Config};
+use crate::error::VoilipError;
+use crate::transcriber::{
+    TranscriptionResult, audio_duration_sec, average_no_speech_prob, check_whisper_cpp_paths, language_code,
+    parse_verbose_json_segments, split_http_status,
+};
+use crate::utils::{run_command_with_timeout, truncate_chars};
+
+/// whisper-serverを待ち受けさせるアドレス（他のマシンからは接続させない）
+const SERVER_HOST: &str = "127.0.0.1";
+/// 起動してからモデルを読み込み終わるまで待つ時間の上限
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+/// 起動を確認する間隔
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// 1回の起動確認の待ち時間の上限（秒）
+const HEALTH_CHECK_TIMEOUT_SEC: u64 = 2;
+/// 起動に失敗した場合に表示するサーバーのログの最大文字数
+const LOG_TAIL_MAX_CHARS: usize = 500;
+
+/// セッションの間起動しておくwhisper.cppのサーバー（whisper-server）
+///
+/// モデルを読み込んだまま待ち受けるため、録音ごとにモデルを読み直すWhisper.cppの
+/// 実行ファイルより待ち時間が短い。dropするとサーバーを終了する
+pub struct WhisperServer {
+    child: Child,
+    port: u16,
+    /// サーバーの標準出力・標準エラー出力（パイプは読まないと詰まるためファイルに書かせる）
+    log: NamedTempFile,
+}
+
+impl WhisperServer {
+    /// サーバーを起動し、モデルを読み込んで応答するようになるまで待つ（ブロッキング）
+    pub fn start(config: &Config, server_path: &Path, model_path: &Path, port: u16) -> Result<Self> {
+        check_whisper_cpp_paths(server_path, model_path)?;
+        // 別のサーバーが応答すると、そちらに録音を送ってしまう
+        if health_status(port).is_some() {
+            return Err(anyhow!("ポート{}はすでに使用されています。--whisper-server-portで別のポートを指定してください", port));
+        }
+        
+        let log = NamedTempFile::new()?;
+        let mut command = Command::new(server_path);
+        command
+            .args(server_command_args(config, model_path, port))
+            .stdin(Stdio::null())
+            .stdout(log.reopen()?)
+            .stderr(log.reopen()?);
+        debug!("whisper-serverを起動します: {:?}", command);
+        let child = command.spawn()
+            .map_err(|e| anyhow!("whisper-serverを起動できません: {}: {}", server_path.display(), e))?;
+        let mut server = Self { child, port, log };
+        
+        info!("whisper-serverでモデルを読み込んでいます: {} (ポート{})", model_path.display(), port);
+        let started = Instant::now();
+        loop {
+            if let Some(status) = server.child.try_wait()? {
+                return Err(anyhow!("whisper-serverが終了しました ({}): {}", status, server.log_tail()));
+            }
+            // 読み込み中は503を返し、/healthのない古いサーバーは読み込み後に待ち受けを始める
+            match health_status(port) {
+                Some(503) | None => {}
+                Some(status) => {
+                    debug!("whisper-serverの起動確認: HTTP {}", status);
+                    break;
+                }
+            }
+            if started.elapsed() >= STARTUP_TIMEOUT {
+                return Err(anyhow!("whisper-serverが{}秒以内に起動しませんでした: {}", STARTUP_TIMEOUT.as_secs(), server.log_tail()));
+            }
+            std::thread::sleep(HEALTH_CHECK_INTERVAL);
+        }
+        info!("whisper-serverを起動しました ({:.1}秒, ポート{})", started.elapsed().as_secs_f32(), port);
+        
+        Ok(server)
+    }
+    
+    /// 待ち受けているポート
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    
+    /// サーバーのログの末尾（起動に失敗した理由の表示用）
+    fn log_tail(&self) -> String {
+        let log = fs::read_to_string(self.log.path()).unwrap_or_default();
+        let log = log.trim();
+        if log.is_empty() {
+            return "(ログなし)".to_string();
+        }
+        let skip = log.chars().count().saturating_sub(LOG_TAIL_MAX_CHARS);
+        log.chars().skip(skip).collect()
+    }
+}
+
+impl Drop for WhisperServer {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            info!("whisper-serverを終了します");
+            if let Err(e) = self.child.kill() {
+                warn!("whisper-serverの終了エラー: {}", e);
+            }
+        }
+        let _ = self.child.wait();
+    }
+}
+
+/// whisper-serverに渡す引数を構築
+///
+/// 言語と翻訳は録音ごとに変わりうるため、起動時ではなくリクエストごとに送る。
+/// 設定された追加の引数はWhisper.cppの実行ファイルと共通で、最後に付ける
+pub fn server_command_args(config: &Config, model_path: &Path, port: u16) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![
+        "-m".into(), model_path.into(),
+        "--host".into(), SERVER_HOST.into(),
+        "--port".into(), port.to_string().into(),
+    ];
+    if let Some(threads) = config.whisper_cpp_threads {
+        args.push("-t".into());
+        args.push(threads.to_string().into());
+    }
+    args.extend(config.whisper_cpp_args.iter().map(OsString::from));
+    args
+}
+
+/// サーバーの起動確認（/health）のHTTPステータス（接続できない場合は`None`）
+fn health_status(port: u16) -> Option<u16> {
+    let output = Command::new("curl")
+        .arg("-s")
+        .arg("-o").arg(if cfg!(target_os = "windows") { "NUL" } else { "/dev/null" })
+        .arg("-w").arg("%{http_code}")
+        .arg("--noproxy").arg("*")
+        .arg("--max-time").arg(HEALTH_CHECK_TIMEOUT_SEC.to_string())
+        .arg(format!("http://{}:{}/health", SERVER_HOST, port))
+        .output()
+        .ok()?;
+    // 接続できなかった場合、curlは000を出力する
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok().filter(|status| *status != 0)
+}
+
+/// 起動済みのwhisper-serverに音声を送って認識
+pub fn transcribe(config: &Config, port: u16, wav_data: &[u8]) -> Result<TranscriptionResult> {
+    let mut temp_file = tempfile::Builder::new().prefix("voilip-").suffix(".wav").tempfile()?;
+    std::io::copy(&mut Cursor::new(wav_data), &mut temp_file)?;
+    
+    let language = config.language_hint();
+    let mut command = Command::new("curl");
+    command
+        .arg("-s")
+        .arg("--noproxy").arg("*")
+        .arg("--max-time").arg(config.request_timeout_sec.to_string())
+        .arg("-w").arg("\n%{http_code}")
+        .arg("-F").arg(format!("file=@{}", temp_file.path().display()))
+        .arg("-F").arg("response_format=verbose_json")
+        .arg("-F").arg(format!("language={}", language.unwrap_or(AUTO_LANGUAGE)));
+    if config.translate {
+        command.arg("-F").arg("translate=true");
+    }
+    if let Some(prompt) = config.effective_prompt() {
+        command.arg("--form-string").arg(format!("prompt={}", prompt));
+    }
+    if let Some(temperature) = config.temperature {
+        command.arg("-F").arg(format!("temperature={}", temperature));
+    }
+    command.arg(format!("http://{}:{}/inference", SERVER_HOST, port));
+    let output = run_command_with_timeout(&mut command, Duration::from_secs(config.transcription_timeout_sec))?;
+    
+    if !output.status.success() {
+        return Err(anyhow!("whisper-serverに接続できません (curl終了コード {}): {}",
+            output.status.code().unwrap_or(-1), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let (response, status) = split_http_status(&stdout);
+    debug!("whisper-serverの応答: HTTP {} ({} バイト)", status, response.len());
+    trace!("whisper-serverの応答本文: {}", response);
+    if status >= 400 {
+        return Err(VoilipError::ApiError { status, body: response.to_string() }.into());
+    }
+    
+    let json: Value = serde_json::from_str(response)
+        .map_err(|e| anyhow!("whisper-serverの応答を解釈できません ({}): {}", e, truncate_chars(response.trim(), 100)))?;
+    // whisper-serverはエラーも成功のステータスで返すことがある
+    if let Some(error) = json.get("error").and_then(|e| e.as_str()) {
+        return Err(anyhow!("whisper-serverがエラーを返しました: {}", error));
+    }
+    let text = json.get("text").and_then(|t| t.as_str())
+        .ok_or_else(|| anyhow!("whisper-serverの応答にテキストがありません: {}", truncate_chars(response.trim(), 100)))?;
+    
+    let duration = audio_duration_sec(wav_data)?;
+    info!("whisper-serverによる文字起こし完了 ({:.2}秒)", duration);
+    
+    let language = if config.translate {
+        "en".to_string()
+    } else if let Some(language) = language {
+        language.to_string()
+    } else {
+        json.get("language").and_then(|l| l.as_str()).map(language_code).unwrap_or_else(|| AUTO_LANGUAGE.to_string())
+    };
+    
+    // 無音確率は幻覚の判定に使うため、セグメントを返さない場合も求めておく
+    let segments = parse_verbose_json_segments(&json);
+    let no_speech_prob = average_no_speech_prob(&segments);
+    
+    Ok(TranscriptionResult {
+        text: text.trim().to_string(),
+        language,
+        duration_sec: duration,
+        is_final: true,
+        sequence: 0,
+        segments: if config.timestamps { segments } else { Vec::new() },
+        no_speech_prob,
+        diagnostics: None,
+        fallback_engine: None,
+        epoch: None,
+    })
+}